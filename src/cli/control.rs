@@ -0,0 +1,202 @@
+// control socket for talking to a running daemon
+// one JSON request per connection, answered with one JSON line (or a stream of lines for events)
+
+use crate::config::config_manager::RustySyncConfig;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+
+/// What a folder in the daemon is doing right now
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(tag = "state", rename_all = "kebab-case")]
+pub enum FolderActivity {
+    #[default]
+    Idle,
+    Scanning,
+    Uploading { done: usize, total: usize },
+    Error { message: String },
+    Waiting, // Outside the folder's schedule windows
+}
+
+impl fmt::Display for FolderActivity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FolderActivity::Idle => write!(f, "idle"),
+            FolderActivity::Scanning => write!(f, "scanning"),
+            FolderActivity::Uploading { done, total } => write!(f, "uploading {}/{}", done, total),
+            FolderActivity::Error { message } => write!(f, "error: {}", message),
+            FolderActivity::Waiting => write!(f, "waiting for schedule window"),
+        }
+    }
+}
+
+/// Live state of one folder, as reported by the daemon
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FolderStatus {
+    pub path: String,
+    pub bucket: String,
+    pub activity: FolderActivity,
+    pub paused: bool,
+    pub pending: Vec<String>, // Changed paths waiting for the next push
+    pub failures: u32,        // Failed pushes in a row
+}
+
+/// Requests a client can send, `folder` None means every folder
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ControlRequest {
+    Status,
+    Pause {
+        #[serde(default)]
+        folder: Option<String>,
+    },
+    Resume {
+        #[serde(default)]
+        folder: Option<String>,
+    },
+    SyncNow {
+        #[serde(default)]
+        folder: Option<String>,
+    },
+    /// Answered like Status, then a FolderStatus line every time a folder changes
+    Events,
+}
+
+/// Answer to a request, carrying the state of the folders it touched
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ControlResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub folders: Vec<FolderStatus>,
+}
+
+impl ControlResponse {
+    pub fn success(folders: Vec<FolderStatus>) -> Self {
+        ControlResponse {
+            ok: true,
+            error: None,
+            folders,
+        }
+    }
+
+    pub fn failure(error: String) -> Self {
+        ControlResponse {
+            ok: false,
+            error: Some(error),
+            folders: Vec::new(),
+        }
+    }
+}
+
+/// Where the daemon listens
+pub fn socket_path() -> Result<PathBuf, String> {
+    Ok(RustySyncConfig::get_config_dir()?.join("daemon.sock"))
+}
+
+pub struct ControlClient;
+
+#[cfg(unix)]
+impl ControlClient {
+    fn connect() -> Result<std::os::unix::net::UnixStream, String> {
+        let path = socket_path()?;
+        std::os::unix::net::UnixStream::connect(&path)
+            .map_err(|e| format!("Daemon is not running ({}: {})", path.display(), e))
+    }
+
+    /// Check if a daemon is listening
+    pub fn is_running() -> bool {
+        Self::connect().is_ok()
+    }
+
+    /// Send a request and wait for the answer
+    pub fn request(request: &ControlRequest) -> Result<ControlResponse, String> {
+        use std::io::BufRead;
+
+        let mut stream = Self::connect()?;
+        send_line(&mut stream, request)?;
+
+        let mut line = String::new();
+        std::io::BufReader::new(stream)
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read daemon response: {}", e))?;
+        serde_json::from_str(&line).map_err(|e| format!("Invalid daemon response: {}", e))
+    }
+
+    /// Subscribe to folder updates, calling `on_update` for the current state and every change after
+    /// Returns when the daemon goes away or `on_update` returns false
+    pub fn events(mut on_update: impl FnMut(&FolderStatus) -> bool) -> Result<(), String> {
+        use std::io::BufRead;
+
+        let mut stream = Self::connect()?;
+        send_line(&mut stream, &ControlRequest::Events)?;
+        let mut lines = std::io::BufReader::new(stream).lines();
+
+        let first = lines
+            .next()
+            .ok_or("Daemon closed the connection")?
+            .map_err(|e| format!("Failed to read daemon response: {}", e))?;
+        let response: ControlResponse =
+            serde_json::from_str(&first).map_err(|e| format!("Invalid daemon response: {}", e))?;
+        for folder in &response.folders {
+            if !on_update(folder) {
+                return Ok(());
+            }
+        }
+
+        for line in lines {
+            let line = line.map_err(|e| format!("Lost connection to the daemon: {}", e))?;
+            let folder: FolderStatus =
+                serde_json::from_str(&line).map_err(|e| format!("Invalid daemon event: {}", e))?;
+            if !on_update(&folder) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+impl ControlClient {
+    pub fn is_running() -> bool {
+        false
+    }
+
+    pub fn request(_request: &ControlRequest) -> Result<ControlResponse, String> {
+        Err("The daemon control socket needs Unix domain sockets".to_string())
+    }
+
+    pub fn events(_on_update: impl FnMut(&FolderStatus) -> bool) -> Result<(), String> {
+        Err("The daemon control socket needs Unix domain sockets".to_string())
+    }
+}
+
+/// Write a value as one JSON line
+pub fn send_line<T: Serialize>(stream: &mut impl std::io::Write, value: &T) -> Result<(), String> {
+    let mut line = serde_json::to_string(value).map_err(|e| format!("Failed to encode message: {}", e))?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .map_err(|e| format!("Failed to send message: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_messages() {
+        let request: ControlRequest = serde_json::from_str(r#"{"command":"sync-now"}"#).unwrap();
+        assert_eq!(request, ControlRequest::SyncNow { folder: None });
+        let request: ControlRequest = serde_json::from_str(r#"{"command":"pause","folder":"/data"}"#).unwrap();
+        assert_eq!(request, ControlRequest::Pause { folder: Some("/data".to_string()) });
+
+        let activity = FolderActivity::Uploading { done: 3, total: 10 };
+        assert_eq!(
+            serde_json::to_string(&activity).unwrap(),
+            r#"{"state":"uploading","done":3,"total":10}"#
+        );
+        assert_eq!(activity.to_string(), "uploading 3/10");
+    }
+}
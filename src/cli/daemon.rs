@@ -0,0 +1,570 @@
+// background daemon that keeps every registered folder in sync with its bucket
+// each folder gets a watcher thread, the actual pushes run on a shared pool of transfer workers
+// a control socket reports what every folder is doing and takes pause/resume/sync-now requests
+
+use crate::cli::control::{ControlRequest, FolderActivity, FolderStatus};
+#[cfg(unix)]
+use crate::cli::control::{ControlClient, ControlResponse, send_line, socket_path};
+use crate::cli::interface_init::InitInterface;
+use crate::config::config_manager::{RustySyncConfig, SyncFolder};
+use crate::config::folder_settings::FolderSettings;
+use crate::core::minio_util::{MinioUtil, TransferLimits};
+use crate::utils::schedule::{TransferWindow, local_now, minutes_until_change, transfer_window};
+use crate::utils::tree_serializer::{FileNode, ReadFileTree};
+use crate::utils::watcher::FolderWatcher;
+use std::collections::BTreeSet;
+#[cfg(unix)]
+use std::io::{BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often folder threads check for due syncs while no events arrive
+const TICK: Duration = Duration::from_secs(5);
+
+/// Retry delays after failures double from the first to the last
+const FIRST_RETRY: Duration = Duration::from_secs(5);
+const MAX_RETRY: Duration = Duration::from_secs(300);
+
+/// Options for a daemon run
+#[derive(Debug, Clone)]
+pub struct DaemonOptions {
+    pub workers: usize,     // Transfers running at the same time across all folders
+    pub interval: Duration, // Time between full syncs of a folder unless it sets its own
+    pub debounce: Duration, // Quiet time after the last event before a folder is pushed
+}
+
+impl Default for DaemonOptions {
+    fn default() -> Self {
+        DaemonOptions {
+            workers: 2,
+            interval: Duration::from_secs(900),
+            debounce: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Delay before the next attempt after `failures` failed ones in a row
+pub fn retry_delay(failures: u32) -> Duration {
+    let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+    FIRST_RETRY.saturating_mul(factor).min(MAX_RETRY)
+}
+
+/// Sync state of one folder, shared between its watcher thread, the workers and control connections
+#[derive(Default)]
+struct FolderState {
+    pending: BTreeSet<PathBuf>, // Paths changed since the last push
+    full_sync: bool,            // Next push must rescan everything
+    tree: Option<FileNode>,     // Tree as of the last push, None until the first full sync
+    queued: bool,               // Waiting for or running on a worker
+    paused: bool,               // Changes are collected but not pushed
+    activity: FolderActivity,
+    failures: u32,              // Failed pushes in a row
+    retry_at: Option<Instant>,  // Earliest time to try again after a failure
+    last_full_sync: Option<Instant>,
+}
+
+impl FolderState {
+    /// Check if the folder should be handed to a worker now
+    fn is_due(&self, interval: Duration) -> bool {
+        if self.paused || self.queued || self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return false;
+        }
+
+        self.full_sync
+            || !self.pending.is_empty()
+            || self.last_full_sync.is_none_or(|at| at.elapsed() >= interval)
+    }
+}
+
+struct ManagedFolder {
+    folder: SyncFolder,
+    interval: Duration,
+    state: Mutex<FolderState>,
+}
+
+/// Everything the watcher threads, the transfer pool and control connections share
+struct Daemon {
+    folders: Vec<ManagedFolder>,
+    jobs: Sender<usize>,                   // Queue of the transfer pool, by index into `folders`
+    subscribers: Mutex<Vec<Sender<String>>>, // Connections streaming events
+}
+
+impl Daemon {
+    /// Snapshot of a folder for the control socket
+    fn status(&self, index: usize) -> FolderStatus {
+        let managed = &self.folders[index];
+        let state = managed.state.lock().unwrap();
+        FolderStatus {
+            path: managed.folder.path.clone(),
+            bucket: managed.folder.bucket.clone(),
+            activity: state.activity.clone(),
+            paused: state.paused,
+            pending: state.pending.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+            failures: state.failures,
+        }
+    }
+
+    /// Send a folder's current state to everyone streaming events
+    fn publish(&self, index: usize) {
+        let Ok(line) = serde_json::to_string(&self.status(index)) else {
+            return;
+        };
+        // Subscribers whose connection is gone have dropped their receiver
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(line.clone()).is_ok());
+    }
+
+    fn set_activity(&self, index: usize, activity: FolderActivity) {
+        self.folders[index].state.lock().unwrap().activity = activity;
+        self.publish(index);
+    }
+
+    /// Hand a folder to the transfer pool if a sync is due and its schedule allows it
+    fn queue_if_due(&self, index: usize) {
+        let managed = &self.folders[index];
+        let (weekday, minute) = local_now();
+        let closed = transfer_window(&managed.folder.schedule, weekday, minute) == TransferWindow::Closed;
+
+        let changed = {
+            let mut state = managed.state.lock().unwrap();
+            let before = state.activity.clone();
+            if closed {
+                // Changes keep collecting until the next window opens
+                if !state.queued && matches!(state.activity, FolderActivity::Idle) {
+                    state.activity = FolderActivity::Waiting;
+                }
+            } else {
+                if matches!(state.activity, FolderActivity::Waiting) {
+                    state.activity = FolderActivity::Idle;
+                }
+                if state.is_due(managed.interval) {
+                    state.queued = true;
+                    let _ = self.jobs.send(index);
+                }
+            }
+            state.activity != before
+        };
+        if changed {
+            self.publish(index);
+        }
+    }
+
+    /// Indexes of the folders a request is about, all of them for None
+    fn select(&self, folder: Option<&str>) -> Result<Vec<usize>, String> {
+        let Some(folder) = folder else {
+            return Ok((0..self.folders.len()).collect());
+        };
+
+        let folder = folder.trim_end_matches('/');
+        self.folders
+            .iter()
+            .position(|managed| managed.folder.path == folder)
+            .map(|index| vec![index])
+            .ok_or_else(|| format!("'{}' is not managed by the daemon", folder))
+    }
+
+    /// Apply a control request and report the folders it is about
+    fn handle(&self, request: &ControlRequest) -> Result<Vec<FolderStatus>, String> {
+        let folder = match request {
+            ControlRequest::Pause { folder } | ControlRequest::Resume { folder } | ControlRequest::SyncNow { folder } => {
+                folder.as_deref()
+            }
+            ControlRequest::Status | ControlRequest::Events => None,
+        };
+        let indexes = self.select(folder)?;
+
+        for &index in &indexes {
+            {
+                let mut state = self.folders[index].state.lock().unwrap();
+                match request {
+                    ControlRequest::Pause { .. } => state.paused = true,
+                    ControlRequest::Resume { .. } => state.paused = false,
+                    ControlRequest::SyncNow { .. } => {
+                        if state.paused {
+                            return Err(format!("'{}' is paused, resume it first", self.folders[index].folder.path));
+                        }
+                        // A full sync, skipping any retry delay
+                        state.full_sync = true;
+                        state.retry_at = None;
+                    }
+                    ControlRequest::Status | ControlRequest::Events => continue,
+                }
+            }
+            self.publish(index);
+            self.queue_if_due(index);
+        }
+
+        Ok(indexes.into_iter().map(|index| self.status(index)).collect())
+    }
+}
+
+pub struct SyncDaemon;
+
+impl SyncDaemon {
+    /// Run until killed, syncing every folder registered in the config
+    pub fn run(options: DaemonOptions) -> Result<(), String> {
+        let config = RustySyncConfig::load()?;
+        if config.sync_folders.is_empty() {
+            return Err("No folders registered. Add one with 'rusty-sync daemon add <folder> [bucket]'".to_string());
+        }
+        #[cfg(unix)]
+        let listener = Self::bind_control_socket()?;
+
+        // Shared transfer pool, fed with indexes into `folders`
+        let (jobs, queue) = mpsc::channel::<usize>();
+        let daemon = Arc::new(Daemon {
+            folders: config
+                .sync_folders
+                .into_iter()
+                .map(|folder| ManagedFolder {
+                    interval: folder.interval.map(Duration::from_secs).unwrap_or(options.interval),
+                    folder,
+                    state: Mutex::new(FolderState::default()),
+                })
+                .collect(),
+            jobs,
+            subscribers: Mutex::new(Vec::new()),
+        });
+
+        println!(
+            "rusty-sync daemon: {} folder(s), {} transfer worker(s)",
+            daemon.folders.len(),
+            options.workers
+        );
+
+        let queue = Arc::new(Mutex::new(queue));
+        for _ in 0..options.workers.max(1) {
+            let queue = Arc::clone(&queue);
+            let daemon = Arc::clone(&daemon);
+            thread::spawn(move || Self::worker(queue, daemon));
+        }
+
+        #[cfg(unix)]
+        {
+            let daemon = Arc::clone(&daemon);
+            thread::spawn(move || Self::serve(listener, daemon));
+        }
+        #[cfg(not(unix))]
+        eprintln!("Warning: No control socket on this platform, the daemon can't be queried");
+
+        let watchers: Vec<_> = (0..daemon.folders.len())
+            .map(|index| {
+                let daemon = Arc::clone(&daemon);
+                let debounce = options.debounce;
+                thread::spawn(move || Self::watch(index, daemon, debounce))
+            })
+            .collect();
+
+        for watcher in watchers {
+            let _ = watcher.join();
+        }
+        Ok(())
+    }
+
+    /// Feed a folder's changes into its state and queue it whenever a sync is due
+    fn watch(index: usize, daemon: Arc<Daemon>, debounce: Duration) {
+        let managed = &daemon.folders[index];
+        let path = managed.folder.path.clone();
+        let mut watcher: Option<FolderWatcher> = None;
+        let mut watch_failures = 0;
+
+        loop {
+            // A folder on a drive that isn't mounted yet is retried, not given up on
+            if watcher.is_none() {
+                match FolderWatcher::new(Path::new(&path)) {
+                    Ok(created) => {
+                        watcher = Some(created);
+                        watch_failures = 0;
+                        // Changes made while nobody watched are only found by a full rescan
+                        managed.state.lock().unwrap().full_sync = true;
+                    }
+                    Err(e) => {
+                        watch_failures += 1;
+                        let delay = retry_delay(watch_failures);
+                        eprintln!("[{}] {}, retrying in {}s", path, e, delay.as_secs());
+                        daemon.set_activity(index, FolderActivity::Error { message: e });
+                        thread::sleep(delay);
+                        continue;
+                    }
+                }
+            }
+
+            daemon.queue_if_due(index);
+
+            if let Some(active) = &watcher {
+                match active.wait_for_changes(debounce, Some(TICK)) {
+                    Ok(Some(batch)) => {
+                        {
+                            let mut state = managed.state.lock().unwrap();
+                            state.full_sync |= batch.rescan;
+                            state.pending.extend(batch.paths);
+                        }
+                        daemon.publish(index);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        eprintln!("[{}] {}", path, e);
+                        watcher = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Take queued folders off the shared queue and push them, one folder at a time per worker
+    fn worker(queue: Arc<Mutex<Receiver<usize>>>, daemon: Arc<Daemon>) {
+        loop {
+            let index = match queue.lock().unwrap().recv() {
+                Ok(index) => index,
+                Err(_) => return,
+            };
+            let managed = &daemon.folders[index];
+
+            // Take the work out so the watcher can keep collecting while we push
+            let (paths, full_sync, mut tree) = {
+                let mut state = managed.state.lock().unwrap();
+                let full_sync = state.full_sync
+                    || state.tree.is_none()
+                    || state.last_full_sync.is_none_or(|at| at.elapsed() >= managed.interval);
+                state.full_sync = false;
+                state.activity = FolderActivity::Scanning;
+                (std::mem::take(&mut state.pending), full_sync, state.tree.take())
+            };
+            daemon.publish(index);
+
+            // Transfers slow down to the window's cap, and none start once the window closes
+            let (weekday, minute) = local_now();
+            let schedule = &managed.folder.schedule;
+            let limits = TransferLimits {
+                max_bandwidth: match transfer_window(schedule, weekday, minute) {
+                    TransferWindow::Open { limit } => limit,
+                    TransferWindow::Closed => None,
+                },
+                deadline: minutes_until_change(schedule, weekday, minute)
+                    .map(|minutes| Instant::now() + Duration::from_secs(minutes as u64 * 60)),
+            };
+            let result = MinioUtil::with_limits(limits, || Self::push(&daemon, index, &paths, full_sync, &mut tree));
+            let window_ended = limits.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+            {
+                let mut state = managed.state.lock().unwrap();
+                state.queued = false;
+                match result {
+                    Ok(()) => {
+                        state.tree = tree;
+                        state.activity = FolderActivity::Idle;
+                        state.failures = 0;
+                        state.retry_at = None;
+                        if full_sync {
+                            state.last_full_sync = Some(Instant::now());
+                        }
+                    }
+                    Err(_) if window_ended => {
+                        // Not a failure: the rest goes out with a full sync in the next window
+                        state.full_sync = true;
+                        state.activity = FolderActivity::Waiting;
+                        println!("[{}] Schedule window ended, continuing in the next one", managed.folder.path);
+                    }
+                    Err(e) => {
+                        // Whatever half-happened is sorted out by a full sync on the retry
+                        state.failures += 1;
+                        state.full_sync = true;
+                        let delay = retry_delay(state.failures);
+                        state.retry_at = Some(Instant::now() + delay);
+                        eprintln!(
+                            "[{}] Sync failed ({} in a row), retrying in {}s: {}",
+                            managed.folder.path,
+                            state.failures,
+                            delay.as_secs(),
+                            e
+                        );
+                        state.activity = FolderActivity::Error { message: e };
+                    }
+                }
+            }
+            daemon.publish(index);
+        }
+    }
+
+    /// Push one folder: a full sync, or just the changed paths on top of the last tree
+    fn push(
+        daemon: &Daemon,
+        index: usize,
+        paths: &BTreeSet<PathBuf>,
+        full_sync: bool,
+        tree: &mut Option<FileNode>,
+    ) -> Result<(), String> {
+        let folder = &daemon.folders[index].folder;
+        let mut progress = |done, total| daemon.set_activity(index, FolderActivity::Uploading { done, total });
+
+        match tree {
+            Some(tree) if !full_sync => {
+                let settings = FolderSettings::load(&folder.path);
+                let changed: Vec<PathBuf> = paths.iter().cloned().collect();
+                let rescan = ReadFileTree::rescan_paths(tree, &folder.path, &settings, &changed);
+                for error in &rescan.errors {
+                    eprintln!("[{}] Could not read {}", folder.path, error);
+                }
+                if rescan.updated.is_empty() {
+                    return Ok(()); // Only ignored paths changed
+                }
+                InitInterface::push_tree(&folder.path, &folder.bucket, tree, &mut progress)
+            }
+            _ => {
+                let structure_file = format!("{}/rusty-sync-structure.json", folder.path);
+                if !Path::new(&structure_file).exists() {
+                    InitInterface::initialize_folder(&folder.path)?;
+                }
+
+                let scan = ReadFileTree::scan_tree_preserving_sync_data(&folder.path)
+                    .map_err(|e| format!("Failed to scan '{}': {}", folder.path, e))?;
+                for error in &scan.errors {
+                    eprintln!("[{}] Could not read {}", folder.path, error);
+                }
+                for issue in &scan.name_issues {
+                    eprintln!("[{}] Warning: {}", folder.path, issue);
+                }
+
+                let mut scanned = scan.tree;
+                InitInterface::push_tree(&folder.path, &folder.bucket, &mut scanned, &mut progress)?;
+                *tree = Some(scanned);
+                Ok(())
+            }
+        }
+    }
+
+    /// Listen on the control socket, refusing to start next to a running daemon
+    #[cfg(unix)]
+    fn bind_control_socket() -> Result<UnixListener, String> {
+        use std::os::unix::fs::PermissionsExt;
+
+        if ControlClient::is_running() {
+            return Err("Another daemon is already running".to_string());
+        }
+
+        // Left behind by a daemon that was killed
+        let path = socket_path()?;
+        let _ = std::fs::remove_file(&path);
+
+        let listener = UnixListener::bind(&path)
+            .map_err(|e| format!("Failed to listen on {}: {}", path.display(), e))?;
+        // Only the user running the daemon may control it
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict {}: {}", path.display(), e))?;
+        Ok(listener)
+    }
+
+    #[cfg(unix)]
+    fn serve(listener: UnixListener, daemon: Arc<Daemon>) {
+        for stream in listener.incoming().flatten() {
+            let daemon = Arc::clone(&daemon);
+            thread::spawn(move || {
+                if let Err(e) = Self::handle_connection(stream, &daemon) {
+                    eprintln!("Warning: Control connection failed: {}", e);
+                }
+            });
+        }
+    }
+
+    /// Answer one request, or stream events until the client hangs up
+    #[cfg(unix)]
+    fn handle_connection(mut stream: UnixStream, daemon: &Daemon) -> Result<(), String> {
+        use std::io::BufRead;
+
+        let mut line = String::new();
+        let read = BufReader::new(&stream)
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read request: {}", e))?;
+        if read == 0 {
+            return Ok(()); // Just checking whether a daemon is running
+        }
+
+        let request = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => request,
+            Err(e) => return send_line(&mut stream, &ControlResponse::failure(format!("Invalid request: {}", e))),
+        };
+
+        if request != ControlRequest::Events {
+            let response = match daemon.handle(&request) {
+                Ok(folders) => ControlResponse::success(folders),
+                Err(e) => ControlResponse::failure(e),
+            };
+            return send_line(&mut stream, &response);
+        }
+
+        // Subscribe before the snapshot, so no change falls between the two
+        let (events, updates) = mpsc::channel();
+        daemon.subscribers.lock().unwrap().push(events);
+        send_line(&mut stream, &ControlResponse::success(daemon.handle(&request)?))?;
+
+        for update in updates {
+            if stream.write_all(format!("{}\n", update).as_bytes()).is_err() {
+                break; // Client went away
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a systemd user unit that runs the daemon, returns its path
+    pub fn install_user_unit() -> Result<PathBuf, String> {
+        let exe = std::env::current_exe()
+            .map_err(|e| format!("Failed to locate the rusty-sync binary: {}", e))?;
+        // Source builds find their Python scripts relative to the working directory
+        let working_dir = std::env::current_dir()
+            .map_err(|e| format!("Failed to get current directory: {}", e))?;
+
+        let unit_dir = dirs::config_dir()
+            .ok_or("Could not find the user config directory")?
+            .join("systemd/user");
+        std::fs::create_dir_all(&unit_dir)
+            .map_err(|e| format!("Failed to create {}: {}", unit_dir.display(), e))?;
+
+        let unit_path = unit_dir.join("rusty-sync.service");
+        std::fs::write(&unit_path, Self::user_unit(&exe, &working_dir))
+            .map_err(|e| format!("Failed to write {}: {}", unit_path.display(), e))?;
+        Ok(unit_path)
+    }
+
+    /// Contents of the systemd user unit
+    fn user_unit(exe: &Path, working_dir: &Path) -> String {
+        format!(
+            "[Unit]\n\
+             Description=Rusty Sync daemon\n\
+             After=network-online.target\n\
+             Wants=network-online.target\n\
+             \n\
+             [Service]\n\
+             Type=simple\n\
+             ExecStart={} daemon run\n\
+             WorkingDirectory={}\n\
+             Restart=on-failure\n\
+             RestartSec=30\n\
+             \n\
+             [Install]\n\
+             WantedBy=default.target\n",
+            exe.display(),
+            working_dir.display()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_doubles_up_to_the_cap() {
+        assert_eq!(retry_delay(1), Duration::from_secs(5));
+        assert_eq!(retry_delay(2), Duration::from_secs(10));
+        assert_eq!(retry_delay(4), Duration::from_secs(40));
+        assert_eq!(retry_delay(10), MAX_RETRY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY);
+    }
+}
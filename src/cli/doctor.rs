@@ -0,0 +1,433 @@
+// rusty-sync doctor: checks everything a sync depends on, from the Python bridge to the folder itself
+// and says what to fix, so a failing push doesn't have to be debugged from its error alone
+
+use crate::cli::control::FolderActivity;
+use crate::cli::interface_init::{absolute_folder, associated_bucket, daemon_folder_status, read_local_manifest};
+use crate::config::config_manager::RustySyncConfig;
+use crate::config::credentials::ResolvedCredentials;
+use crate::core::encryption::{EncryptionKey, ENCRYPTION_NEXT_KEY, ENCRYPTION_PARAMS_KEY, SALT_METADATA};
+use crate::core::minio_util::{MinioUtil, ProbeCheck};
+use crate::utils::tree_serializer::{FileNode, JsonManager, MANIFEST_KEY};
+use chrono::{DateTime, Utc};
+use std::path::Path;
+
+/// Clock difference to the server worth mentioning
+const SKEW_WARNING_SECONDS: i64 = 60;
+/// Clock difference past which S3 servers refuse signed requests (RequestTimeTooSkewed)
+const SKEW_LIMIT_SECONDS: i64 = 15 * 60;
+
+/// Collects the outcome of each check as it is printed
+#[derive(Default)]
+pub struct Doctor {
+    problems: usize,
+    warnings: usize,
+}
+
+impl Doctor {
+    /// Check the Python bridge, the config, the connection and the state of `folder`
+    /// Errors if any check found a problem, warnings alone pass
+    pub fn run(folder: Option<String>) -> Result<(), String> {
+        let mut doctor = Doctor::default();
+
+        println!("Python bridge:");
+        let bridge_ok = doctor.check_python();
+
+        println!("Configuration:");
+        let config = doctor.check_config();
+
+        println!("Folder:");
+        let folder = absolute_folder(folder)?;
+        let bucket = doctor.check_folder(&folder, config.as_ref());
+
+        println!("Connection:");
+        let connected = match (bridge_ok, &config) {
+            (true, Some(_)) => doctor.check_connection(bucket.as_deref()),
+            _ => {
+                doctor.warn("Skipped, fix the problems above first");
+                false
+            }
+        };
+
+        if let Some(bucket) = &bucket {
+            println!("Encryption:");
+            match connected {
+                true => doctor.check_encryption(bucket),
+                false => doctor.warn("Skipped, the bucket can't be reached"),
+            }
+        }
+
+        println!();
+        match (doctor.problems, doctor.warnings) {
+            (0, 0) => {
+                println!("No problems found");
+                Ok(())
+            }
+            (0, warnings) => {
+                println!("No problems found, {} warning(s)", warnings);
+                Ok(())
+            }
+            (problems, warnings) => Err(format!("{} problem(s) and {} warning(s) found", problems, warnings)),
+        }
+    }
+
+    /// Check the configured server can be reached and accepts the credentials, and with a bucket
+    /// that objects can be listed, written and deleted in it
+    pub fn test_connection(bucket: Option<&str>) -> Result<(), String> {
+        let mut doctor = Doctor::default();
+        match doctor.check_connection(bucket) {
+            true => Ok(()),
+            false => Err("Connection test failed".to_string()),
+        }
+    }
+
+    fn ok(&self, message: impl AsRef<str>) {
+        println!("  ✓ {}", message.as_ref());
+    }
+
+    fn warn(&mut self, message: impl AsRef<str>) {
+        self.warnings += 1;
+        println!("  ⚠ {}", message.as_ref());
+    }
+
+    fn problem(&mut self, message: impl AsRef<str>) {
+        self.problems += 1;
+        println!("  ✗ {}", message.as_ref());
+    }
+
+    /// The interpreter and scripts the bridge runs, false if transfers can't work
+    fn check_python(&mut self) -> bool {
+        let setup = match MinioUtil::python_setup() {
+            Ok(setup) => setup,
+            Err(e) => {
+                self.problem(e);
+                return false;
+            }
+        };
+
+        self.ok(format!("Scripts: {}", setup.main_py.display()));
+        if setup.python.is_absolute() {
+            self.ok(format!("Python: {}", setup.python.display()));
+        } else {
+            self.warn(format!("No virtualenv next to the scripts, using '{}' from PATH", setup.python.display()));
+        }
+
+        match setup.boto3_version {
+            Ok(version) => {
+                self.ok(format!("boto3 {}", version));
+                true
+            }
+            Err(e) => {
+                self.problem(format!(
+                    "boto3 can't be imported ({}). Install it with 'pip install -r requirements.txt'",
+                    e
+                ));
+                false
+            }
+        }
+    }
+
+    /// config.json and the credentials it resolves to, None if there is nothing to connect with
+    /// Only reads, a missing config is not created and plaintext secrets are not moved
+    fn check_config(&mut self) -> Option<RustySyncConfig> {
+        let config_path = match RustySyncConfig::config_path() {
+            Ok(path) => path,
+            Err(e) => {
+                self.problem(e);
+                return None;
+            }
+        };
+
+        let config = match RustySyncConfig::read_existing() {
+            Ok(Some(config)) => {
+                self.ok(format!("{} is valid, {} server(s)", config_path.display(), config.minio_servers.len()));
+                config
+            }
+            Ok(None) => {
+                self.warn(format!("No config file at {}, run 'rusty-sync config' to add a server", config_path.display()));
+                RustySyncConfig::default()
+            }
+            Err(e) => {
+                self.problem(format!("{} ({})", e, config_path.display()));
+                return None;
+            }
+        };
+        if config.has_plaintext_secrets() {
+            self.warn("config.json holds secret keys, move them with 'rusty-sync config migrate-secrets'");
+        }
+
+        for server in &config.minio_servers {
+            if !server.endpoint_url.starts_with("http://") && !server.endpoint_url.starts_with("https://") {
+                self.problem(format!("Endpoint '{}' should start with http:// or https://", server.endpoint_url));
+            }
+            if let Err(e) = server.tls.validate() {
+                self.problem(format!("TLS settings of {}: {}", server.endpoint_url, e));
+            } else if server.tls.insecure {
+                self.warn(format!("Certificates of {} are not verified", server.endpoint_url));
+            } else if !server.tls.is_default() {
+                self.ok(format!("TLS for {}: {}", server.endpoint_url, server.tls));
+            }
+            if let Err(e) = server.network.validate() {
+                self.problem(format!("Network settings of {}: {}", server.endpoint_url, e));
+            }
+        }
+        if let Some(index) = config.current_server
+            && index >= config.minio_servers.len()
+            && !config.minio_servers.is_empty()
+        {
+            self.problem(format!(
+                "Active server {} doesn't exist, choose one with 'rusty-sync config'",
+                index + 1
+            ));
+            return None;
+        }
+        for folder in &config.sync_folders {
+            if !Path::new(&folder.path).is_dir() {
+                self.warn(format!("Daemon folder {} no longer exists", folder.path));
+            }
+        }
+
+        match ResolvedCredentials::resolve(&config) {
+            Ok(credentials) => {
+                self.ok(format!(
+                    "Credentials for {}: access key {} from {}",
+                    credentials.endpoint_url, credentials.access_key, credentials.source
+                ));
+                if let Some(expiration) = credentials.expiration {
+                    self.ok(format!("Session credentials expire at {}", expiration.to_rfc3339()));
+                }
+                let network = config
+                    .server_for(&credentials.endpoint_url)
+                    .map(|server| server.network.clone())
+                    .unwrap_or_default();
+                match network.proxy_for(&credentials.endpoint_url) {
+                    Some((proxy, source)) => self.ok(format!("Connecting through proxy {} ({}), {}", proxy, source, network)),
+                    None => self.ok(format!("Connecting directly, {}", network)),
+                }
+                if let Some(server) = config.server_for(&credentials.endpoint_url)
+                    && !server.compatibility.is_default()
+                {
+                    self.ok(format!("S3 settings: {}", server.compatibility));
+                }
+                Some(config)
+            }
+            Err(e) => {
+                self.problem(e);
+                None
+            }
+        }
+    }
+
+    /// Step through the probe of the server, false if any step failed
+    fn check_connection(&mut self, bucket: Option<&str>) -> bool {
+        match MinioUtil::probe(bucket) {
+            Ok(checks) => self.report_probe(&checks),
+            Err(e) => {
+                self.problem(e);
+                false
+            }
+        }
+    }
+
+    /// Report each probe step up to the first failed one, false if there was one
+    fn report_probe(&mut self, checks: &[ProbeCheck]) -> bool {
+        for check in checks {
+            if !check.ok {
+                self.problem(format!("{}: {}", check_name(&check.check), check.detail));
+                return false;
+            }
+            self.ok(format!("{}: {}", check_name(&check.check), check.detail));
+            if let Some(date) = &check.date {
+                self.check_clock(date);
+            }
+        }
+        true
+    }
+
+    /// Compare our clock with the server's Date header, signatures are only valid within 15 minutes
+    fn check_clock(&mut self, date: &str) {
+        let Ok(server_time) = DateTime::parse_from_rfc2822(date) else {
+            self.warn(format!("Server sent an unreadable Date header: {}", date));
+            return;
+        };
+
+        let skew = (Utc::now() - server_time.with_timezone(&Utc)).num_seconds();
+        let message = format!(
+            "Clock is {}s {} the server's",
+            skew.abs(),
+            if skew >= 0 { "ahead of" } else { "behind" }
+        );
+        if skew.abs() >= SKEW_LIMIT_SECONDS {
+            self.problem(format!("{}, requests will be refused. Synchronize the system clock", message));
+        } else if skew.abs() >= SKEW_WARNING_SECONDS {
+            self.warn(format!("{}, synchronize the system clock", message));
+        } else {
+            self.ok(format!("Clock within {}s of the server's", SKEW_WARNING_SECONDS));
+        }
+    }
+
+    /// Whether the folder is initialized, associated and readable, and what the daemon does with it
+    /// Returns the associated bucket
+    fn check_folder(&mut self, folder: &str, config: Option<&RustySyncConfig>) -> Option<String> {
+        if !Path::new(folder).is_dir() {
+            self.problem(format!("{} is not a directory", folder));
+            return None;
+        }
+
+        let structure_file = format!("{}/rusty-sync-structure.json", folder);
+        if !Path::new(&structure_file).exists() {
+            self.warn(format!(
+                "{} is not initialized. Run 'rusty-sync init {}' or 'rusty-sync clone <bucket> {}'",
+                folder, folder, folder
+            ));
+            return None;
+        }
+        match JsonManager::read_from_json::<FileNode>(&structure_file) {
+            Ok(_) => self.ok(format!("{} is initialized", folder)),
+            Err(e) => self.problem(format!("{} is unreadable: {}. Run 'rusty-sync init {}' again", structure_file, e, folder)),
+        }
+
+        let bucket = match associated_bucket(folder) {
+            Ok(bucket) => {
+                self.ok(format!("Associated with bucket '{}'", bucket));
+                Some(bucket)
+            }
+            Err(e) => {
+                self.warn(e);
+                None
+            }
+        };
+
+        let manifest = format!("{}/{}", folder, MANIFEST_KEY);
+        if read_local_manifest(folder).is_some() {
+            self.ok("Copy of the bucket's manifest from the last push or pull");
+        } else if Path::new(&manifest).exists() {
+            self.problem(format!("{} is unreadable, the next pull can't tell remote deletions apart. Pull again", manifest));
+        } else if bucket.is_some() {
+            self.warn("Never pushed or pulled, the first sync compares against the whole bucket");
+        }
+
+        let registered = config.and_then(|config| {
+            let local = Path::new(folder).canonicalize().ok();
+            config
+                .sync_folders
+                .iter()
+                .find(|registered| Path::new(&registered.path).canonicalize().ok() == local)
+        });
+        match (registered, daemon_folder_status(folder)) {
+            (_, Some(live)) => match &live.activity {
+                FolderActivity::Error { message } => {
+                    self.warn(format!("Daemon reports an error after {} attempt(s): {}", live.failures, message))
+                }
+                _ if live.paused => self.warn("Daemon has paused syncing this folder"),
+                activity => self.ok(format!("Daemon is running: {}", activity)),
+            },
+            (Some(registered), None) => {
+                self.warn(format!("Registered with the daemon (bucket '{}') but no daemon is running", registered.bucket))
+            }
+            (None, None) => self.ok("Not registered with the daemon"),
+        }
+
+        bucket
+    }
+
+    /// Whether this machine holds the current key of an encrypted bucket
+    fn check_encryption(&mut self, bucket: &str) {
+        let metadata = match MinioUtil::object_metadata(bucket, ENCRYPTION_PARAMS_KEY) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                self.problem(e);
+                return;
+            }
+        };
+        let local = match EncryptionKey::load(bucket) {
+            Ok(local) => local,
+            Err(e) => {
+                self.problem(e);
+                return;
+            }
+        };
+
+        match (metadata, local) {
+            (None, _) => self.ok(format!("Bucket '{}' is not encrypted", bucket)),
+            (Some(_), None) => self.warn(format!(
+                "Bucket '{}' is encrypted but locked on this machine. Run 'rusty-sync encryption unlock'",
+                bucket
+            )),
+            (Some(metadata), Some(key)) => match metadata.get(SALT_METADATA) {
+                Some(salt) if salt != key.salt() && key.retired.iter().all(|retired| &retired.salt != salt) => {
+                    self.problem(format!(
+                        "The key of bucket '{}' was changed. Unlock it again with 'rusty-sync encryption unlock'",
+                        bucket
+                    ))
+                }
+                _ => self.ok(format!("Bucket '{}' is encrypted and unlocked on this machine", bucket)),
+            },
+        }
+
+        match MinioUtil::object_etag(bucket, ENCRYPTION_NEXT_KEY) {
+            Ok(Some(_)) => self.warn("A key change is in progress. Run 'rusty-sync encryption rekey' to finish it"),
+            Ok(None) => {}
+            Err(e) => self.problem(e),
+        }
+    }
+}
+
+/// Heading of a probe step
+fn check_name(check: &str) -> &str {
+    match check {
+        "reachable" => "Server reachable",
+        "authentication" => "Credentials accepted",
+        "list" => "List objects",
+        "put" => "Write probe object",
+        "delete" => "Delete probe object",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock_result(skew_seconds: i64) -> (usize, usize) {
+        let mut doctor = Doctor::default();
+        let server_time = Utc::now() - chrono::Duration::seconds(skew_seconds);
+        doctor.check_clock(&server_time.to_rfc2822());
+        (doctor.problems, doctor.warnings)
+    }
+
+    #[test]
+    fn test_clock_skew_thresholds() {
+        assert_eq!(clock_result(0), (0, 0));
+        assert_eq!(clock_result(SKEW_WARNING_SECONDS - 5), (0, 0));
+        assert_eq!(clock_result(SKEW_WARNING_SECONDS + 5), (0, 1));
+        assert_eq!(clock_result(-SKEW_WARNING_SECONDS - 5), (0, 1));
+        assert_eq!(clock_result(SKEW_LIMIT_SECONDS + 5), (1, 0));
+        assert_eq!(clock_result(-SKEW_LIMIT_SECONDS - 5), (1, 0));
+
+        let mut doctor = Doctor::default();
+        doctor.check_clock("yesterday-ish");
+        assert_eq!((doctor.problems, doctor.warnings), (0, 1));
+    }
+
+    #[test]
+    fn test_probe_stops_at_the_first_failed_step() {
+        let step = |check: &str, ok: bool, date: Option<String>| ProbeCheck {
+            check: check.to_string(),
+            ok,
+            detail: String::new(),
+            date,
+        };
+
+        let mut doctor = Doctor::default();
+        let skewed = (Utc::now() - chrono::Duration::seconds(SKEW_LIMIT_SECONDS + 5)).to_rfc2822();
+        assert!(doctor.report_probe(&[step("reachable", true, Some(skewed)), step("list", true, None)]));
+        assert_eq!((doctor.problems, doctor.warnings), (1, 0)); // The clock, the steps themselves passed
+
+        let mut doctor = Doctor::default();
+        let checks = [step("reachable", true, None), step("put", false, None), step("delete", false, None)];
+        assert!(!doctor.report_probe(&checks));
+        assert_eq!(doctor.problems, 1);
+
+        assert!(Doctor::default().report_probe(&[]));
+    }
+}
@@ -1,11 +1,34 @@
 use crate::sync_test_service::SyncTestService;
-use crate::utils::tree_serializer::{JsonManager, ReadFileTree, SyncManager, FileNode, BucketManager};
+use crate::utils::tree_serializer::{JsonManager, ReadFileTree, SyncManager, FileNode, BucketManager, METADATA_DIR, MANIFEST_KEY};
+use crate::utils::git_util::{GitRepoKind, GitRestorer};
+use crate::utils::path_safety::{key_to_local, NameIssue};
+use crate::utils::remote_watcher::{diff_manifests, RemoteWatcher};
+use crate::utils::schedule::ScheduleWindow;
+use crate::utils::walker::WalkError;
+use crate::utils::watcher::FolderWatcher;
+use crate::core::encryption::{
+    read_secret, stored_in_plaintext, EncryptionKey, EncryptionParams, TempFile, ENCRYPTION_METADATA,
+    ENCRYPTION_NEXT_KEY, ENCRYPTION_PARAMS_KEY, SALT_METADATA,
+};
 use crate::core::minio_util::MinioUtil;
-use crate::config::config_manager::RustySyncConfig;
+use crate::cli::control::{ControlClient, ControlRequest, FolderActivity, FolderStatus};
+use crate::cli::daemon::{DaemonOptions, SyncDaemon};
+use crate::cli::doctor::Doctor;
+use crate::config::config_manager::{RustySyncConfig, SyncFolder};
+use crate::config::credentials::ResolvedCredentials;
+use crate::config::folder_settings::FolderSettings;
+use std::collections::BTreeMap;
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use std::process;
-use std::io::{self, Write};
+use std::io::Write;
+
+/// Quiet time after the last event before a watch pushes
+const DEFAULT_WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// How often watch-remote checks the bucket's manifest
+const DEFAULT_REMOTE_POLL_INTERVAL: Duration = Duration::from_secs(30);
 
 pub struct InitInterface;
 
@@ -25,8 +48,9 @@ impl InitInterface {
         println!("Initializing OneDrive sync for folder: {}", folder_path);
 
         // Generate the file tree structure
-        let file_tree = ReadFileTree::generate_tree(folder_path)
+        let scan = ReadFileTree::scan_tree(folder_path)
             .map_err(|e| format!("Failed to generate file tree: {}", e))?;
+        let file_tree = scan.tree;
 
         // Create JSON file in the target folder
         let json_file_path = format!("{}/rusty-sync-structure.json", folder_path);
@@ -51,6 +75,8 @@ impl InitInterface {
             total_size,
             total_size as f64 / 1_048_576.0
         );
+        print_scan_errors(&scan.errors);
+        print_name_issues(&scan.name_issues);
 
         Ok(())
     }
@@ -79,11 +105,34 @@ impl InitInterface {
         println!("Syncing folder: {} to bucket: {}", folder_path, bucket);
 
         // Generate current file tree, preserving existing sync metadata
-        let mut current_tree = ReadFileTree::generate_tree_preserving_sync_data(folder_path)
+        let scan = ReadFileTree::scan_tree_preserving_sync_data(folder_path)
             .map_err(|e| format!("Failed to generate file tree: {}", e))?;
+        let mut current_tree = scan.tree;
+
+        Self::push_tree(folder_path, bucket, &mut current_tree, &mut |_, _| {})?;
+        print_scan_errors(&scan.errors);
+        print_name_issues(&scan.name_issues);
+
+        Ok(())
+    }
+
+    /// Upload what changed in `current_tree`, apply folder deletions and publish the tree as the manifest
+    /// `progress` is called with (done, total) as uploads complete
+    pub(crate) fn push_tree(
+        folder_path: &str,
+        bucket: &str,
+        current_tree: &mut FileNode,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<(), String> {
+        // The bucket's key may have changed since this process looked it up
+        MinioUtil::refresh_bucket_key(bucket)?;
+
+        // What the bucket looked like after our last push or pull
+        let known_remote = read_local_manifest(folder_path);
+        let json_file_path = format!("{}/rusty-sync-structure.json", folder_path);
 
         // Upload changed files
-        match SyncManager::upload_changed_files(&mut current_tree, bucket) {
+        match SyncManager::upload_changed_files(current_tree, bucket, progress) {
             Ok(uploaded_files) => {
                 if uploaded_files.is_empty() {
                     println!("All files are up to date - nothing to sync");
@@ -93,19 +142,219 @@ impl InitInterface {
                         println!("  - {}", file);
                     }
                 }
+            }
+            Err(e) => return Err(format!("Sync failed: {}", e)),
+        }
+
+        // Deletions seen here become tombstones in the manifest and are removed from the bucket
+        let deleted = SyncManager::record_deletions(current_tree, known_remote.as_ref(), Path::new(folder_path));
+        Self::delete_remote_paths(bucket, &deleted)?;
+
+        // Update the JSON file with current state
+        JsonManager::write_to_json(&json_file_path, current_tree)
+            .map_err(|e| format!("Failed to update structure file: {}", e))?;
 
-                // Update the JSON file with current state
-                JsonManager::write_to_json(&json_file_path, &current_tree)
-                    .map_err(|e| format!("Failed to update structure file: {}", e))?;
+        println!("Updated structure file: {}", json_file_path);
+
+        // Publish the tree so clone/pull on other machines can restore git repos and folders
+        MinioUtil::upload_file(&json_file_path, bucket, MANIFEST_KEY)
+            .map_err(|e| format!("Failed to upload remote manifest: {}", e))?;
+        save_local_manifest(folder_path, current_tree)
+    }
+
+    /// Watch a folder and push changed paths as they happen, until interrupted
+    pub fn watch_folder(folder_path: &str, bucket: &str, debounce: Duration) -> Result<(), String> {
+        let watcher = FolderWatcher::new(Path::new(folder_path))?;
+        println!("Watching '{}' for changes (bucket '{}'), press Ctrl+C to stop", folder_path, bucket);
+
+        // Catch up on anything that changed while nobody was watching
+        Self::sync_folder(folder_path, bucket)?;
+        let settings = FolderSettings::load(folder_path);
+        let structure_file = format!("{}/rusty-sync-structure.json", folder_path);
+        let mut tree = JsonManager::read_from_json::<FileNode>(&structure_file)
+            .map_err(|e| format!("Failed to read structure file: {}", e))?;
+
+        loop {
+            let Some(batch) = watcher.wait_for_changes(debounce, None)? else {
+                continue;
+            };
+
+            let result = if batch.rescan {
+                // Events were dropped, the only safe thing left is a full rescan
+                println!("Watch queue overflowed, rescanning '{}'", folder_path);
+                Self::sync_folder(folder_path, bucket).and_then(|_| {
+                    tree = JsonManager::read_from_json(&structure_file)
+                        .map_err(|e| format!("Failed to read structure file: {}", e))?;
+                    Ok(())
+                })
+            } else {
+                let paths: Vec<PathBuf> = batch.paths.into_iter().collect();
+                let rescan = ReadFileTree::rescan_paths(&mut tree, folder_path, &settings, &paths);
+                print_scan_errors(&rescan.errors);
+                if rescan.updated.is_empty() {
+                    continue; // Only ignored paths changed
+                }
+
+                for path in &rescan.updated {
+                    println!("Changed: {}", path);
+                }
+                Self::push_tree(folder_path, bucket, &mut tree, &mut |_, _| {})
+            };
 
-                println!("Updated structure file: {}", json_file_path);
+            // A failed push is retried with the next batch, the watch keeps running
+            if let Err(e) = result {
+                eprintln!("Warning: Push failed, will retry on the next change: {}", e);
+            }
+        }
+    }
+
+    /// Watch a bucket and pull what other machines push to it, until interrupted
+    pub fn watch_remote(folder_path: &str, bucket: &str, interval: Duration, notify: bool) -> Result<(), String> {
+        if !Path::new(&format!("{}/rusty-sync-structure.json", folder_path)).exists() {
+            return Err(format!(
+                "Folder '{}' is not initialized. Run 'rusty-sync clone {} {}' or 'rusty-sync pull' first",
+                folder_path, bucket, folder_path
+            ));
+        }
+
+        let mut watcher = RemoteWatcher::new(bucket, interval, notify)?;
+        println!(
+            "Watching bucket '{}' for changes to pull into '{}', press Ctrl+C to stop",
+            bucket, folder_path
+        );
+
+        loop {
+            let etag = watcher.wait_for_change();
+            match Self::pull_remote_changes(folder_path, bucket) {
+                Ok(_) => watcher.mark_handled(etag),
+                Err(e) => {
+                    eprintln!("Warning: Pull failed, will retry: {}", e);
+                    std::thread::sleep(interval);
+                }
             }
-            Err(e) => return Err(format!("Sync failed: {}", e)),
+        }
+    }
+
+    /// Pull only what changed in the remote manifest since our last pull or push
+    /// Files edited locally since their last sync are left alone. Returns the number of paths updated
+    pub fn pull_remote_changes(local_folder: &str, bucket: &str) -> Result<usize, String> {
+        let incoming = format!("{}/{}/incoming.json", local_folder, METADATA_DIR);
+        MinioUtil::download_file(bucket, MANIFEST_KEY, &incoming)
+            .map_err(|e| format!("Failed to download remote manifest: {}", e))?;
+        let remote_tree: FileNode = JsonManager::read_from_json(&incoming)
+            .map_err(|e| format!("Failed to read remote manifest: {}", e))?;
+
+        let previous_remote = read_local_manifest(local_folder);
+        let changes = diff_manifests(previous_remote.as_ref(), &remote_tree);
+        let local_tree = ReadFileTree::generate_tree_preserving_sync_data(local_folder)
+            .map_err(|e| format!("Failed to scan local folder: {}", e))?;
+
+        // Files that were never synced only count as local edits when the last manifest didn't have them
+        let edited_locally = |relative_path: &str| {
+            local_tree.find_by_relative_path(relative_path).is_some_and(|local| match local.last_synced {
+                Some(_) => local.content_changed(),
+                None => previous_remote
+                    .as_ref()
+                    .is_none_or(|previous| previous.find_by_relative_path(relative_path).is_none()),
+            })
+        };
+
+        SyncManager::restore_folders(&remote_tree, Some(&local_tree), Path::new(local_folder))?;
+
+        let mut downloaded = Vec::new();
+        for key in &changes.changed {
+            if edited_locally(key) {
+                eprintln!("Warning: {} changed both here and remotely, keeping the local copy", key);
+                continue;
+            }
+
+            let local_path = local_path_in(&local_tree, local_folder, key);
+            if let Some(parent) = Path::new(&local_path).parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
+            }
+            SyncManager::download_entry(bucket, key, &local_path, Some(&remote_tree))
+                .map_err(|e| format!("Failed to download {}: {}", key, e))?;
+            println!("Downloaded: {}", key);
+            downloaded.push(key.clone());
+        }
+
+        let mut removed = 0;
+        for key in &changes.removed {
+            if local_tree.find_by_relative_path(key).is_none() {
+                continue;
+            }
+            if edited_locally(key) {
+                eprintln!("Warning: {} was deleted remotely but changed here, keeping it", key);
+                continue;
+            }
+
+            let local_path = local_path_in(&local_tree, local_folder, key);
+            std::fs::remove_file(&local_path).map_err(|e| format!("Failed to remove {}: {}", local_path, e))?;
+            println!("Removed: {}", key);
+            removed += 1;
+        }
+
+        if let Some(previous_remote) = &previous_remote {
+            Self::apply_remote_folder_deletions(previous_remote, &remote_tree, &local_tree);
+        }
+        SyncManager::apply_folder_permissions(&remote_tree, Some(&local_tree), Path::new(local_folder))?;
+
+        // What was just downloaded matches the bucket, so it must not be pushed back
+        let mut updated_tree = ReadFileTree::generate_tree_preserving_sync_data(local_folder)
+            .map_err(|e| format!("Failed to update local structure: {}", e))?;
+        for key in &downloaded {
+            let _ = SyncManager::mark_file_as_synced(&mut updated_tree, key);
+        }
+        let structure_file = format!("{}/rusty-sync-structure.json", local_folder);
+        JsonManager::write_to_json(&structure_file, &updated_tree)
+            .map_err(|e| format!("Failed to update structure file: {}", e))?;
+
+        std::fs::rename(&incoming, format!("{}/{}", local_folder, MANIFEST_KEY))
+            .map_err(|e| format!("Failed to save remote manifest: {}", e))?;
+
+        Ok(downloaded.len() + removed)
+    }
+
+    /// Delete the objects of files and folders that were removed locally since the last push or pull
+    fn delete_remote_paths(bucket: &str, deleted: &[String]) -> Result<(), String> {
+        if deleted.is_empty() {
+            return Ok(());
+        }
+
+        let remote_files = MinioUtil::list_files_in_bucket(bucket)?;
+        for path in deleted {
+            let prefix = format!("{}/", path);
+            for key in remote_files.iter().filter(|key| *key == path || key.starts_with(&prefix)) {
+                MinioUtil::delete_file(bucket, key)
+                    .map_err(|e| format!("Failed to delete {}: {}", key, e))?;
+            }
+            println!("Deleted remotely: {}", path);
         }
 
         Ok(())
     }
 
+    /// Remove local folders that another machine deleted (per the manifest's tombstones), unless they hold unsynced changes
+    fn apply_remote_folder_deletions(previous_remote: &FileNode, remote_tree: &FileNode, local_tree: &FileNode) {
+        for folder in SyncManager::folders_removed_remotely(previous_remote, remote_tree) {
+            let Some(local) = local_tree.find_by_relative_path(&folder) else {
+                continue; // Already gone here too
+            };
+
+            if local.get_all_objects().iter().any(|object| object.needs_sync()) {
+                eprintln!("Warning: Keeping {}, it was deleted remotely but has local changes", folder);
+                continue;
+            }
+
+            let local_path = PathBuf::from(&local.path);
+            match std::fs::remove_dir_all(&local_path) {
+                Ok(()) => println!("Removed folder deleted remotely: {}", folder),
+                Err(e) => eprintln!("Warning: Could not remove {}: {}", local_path.display(), e),
+            }
+        }
+    }
+
     /// Clone (download) a bucket to a local folder - git-like clone command
     pub fn clone_bucket(bucket: &str, local_folder: &str) -> Result<(), String> {
         println!("Cloning bucket '{}' to '{}'...", bucket, local_folder);
@@ -122,22 +371,31 @@ impl InitInterface {
             return Ok(());
         }
 
+        // Convert to absolute path to avoid working directory issues
+        let local_folder_abs = if Path::new(local_folder).is_absolute() {
+            local_folder.to_string()
+        } else {
+            env::current_dir()
+                .map_err(|e| format!("Failed to get current directory: {}", e))?
+                .join(local_folder)
+                .to_string_lossy()
+                .to_string()
+        };
+
+        // Restore git repositories before any files land inside them
+        let remote_tree = Self::fetch_remote_metadata(bucket, &files, &local_folder_abs)?;
+        if let Some(remote_tree) = &remote_tree {
+            SyncManager::restore_folders(remote_tree, None, Path::new(&local_folder_abs))?;
+        }
+
         println!("Downloading {} files from bucket '{}'...", files.len(), bucket);
 
         // Download each file
-        for file in &files {
-            // Convert to absolute path to avoid working directory issues
-            let local_folder_abs = if Path::new(local_folder).is_absolute() {
-                local_folder.to_string()
-            } else {
-                env::current_dir()
-                    .map_err(|e| format!("Failed to get current directory: {}", e))?
-                    .join(local_folder)
-                    .to_string_lossy()
-                    .to_string()
-            };
-            
-            let local_path = format!("{}/{}", local_folder_abs, file);
+        for file in files
+            .iter()
+            .filter(|f| !is_metadata_key(f) && !deleted_remotely(f, remote_tree.as_ref()))
+        {
+            let local_path = local_path_for_key(&local_folder_abs, file);
             
             // Create parent directories if needed
             if let Some(parent) = Path::new(&local_path).parent() {
@@ -145,7 +403,7 @@ impl InitInterface {
                     .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
             }
 
-            match MinioUtil::download_file(bucket, file, &local_path) {
+            match SyncManager::download_entry(bucket, file, &local_path, remote_tree.as_ref()) {
                 Ok(_) => println!("Downloaded: {}", file),
                 Err(e) => {
                     eprintln!("  ✗ Failed to download {}: {}", file, e);
@@ -154,17 +412,11 @@ impl InitInterface {
             }
         }
 
-        // Auto-initialize the cloned folder like git - no manual init needed
-        let local_folder_abs = if Path::new(local_folder).is_absolute() {
-            local_folder.to_string()
-        } else {
-            env::current_dir()
-                .map_err(|e| format!("Failed to get current directory: {}", e))?
-                .join(local_folder)
-                .to_string_lossy()
-                .to_string()
-        };
+        if let Some(remote_tree) = &remote_tree {
+            SyncManager::apply_folder_permissions(remote_tree, None, Path::new(&local_folder_abs))?;
+        }
 
+        // Auto-initialize the cloned folder like git - no manual init needed
         // Generate structure file for the cloned folder (like git init)
         let file_tree = ReadFileTree::generate_tree(&local_folder_abs)
             .map_err(|e| format!("Failed to generate file tree: {}", e))?;
@@ -174,7 +426,7 @@ impl InitInterface {
             .map_err(|e| format!("Failed to create structure file: {}", e))?;
 
         // Save bucket association for future operations
-        if let Err(_) = BucketManager::save_bucket_association(&local_folder_abs, bucket) {
+        if BucketManager::save_bucket_association(&local_folder_abs, bucket).is_err() {
             // Non-critical error, just warn
             eprintln!("Warning: Could not save bucket association");
         }
@@ -184,6 +436,32 @@ impl InitInterface {
         Ok(())
     }
 
+    /// Download rusty-sync metadata objects and re-create git repositories from the remote manifest
+    /// Returns the remote manifest when the bucket has one
+    fn fetch_remote_metadata(bucket: &str, remote_files: &[String], local_folder: &str) -> Result<Option<FileNode>, String> {
+        for file in remote_files.iter().filter(|f| is_metadata_key(f)) {
+            let local_path = local_path_for_key(local_folder, file);
+            MinioUtil::download_file(bucket, file, &local_path)
+                .map_err(|e| format!("Failed to download {}: {}", file, e))?;
+        }
+
+        let manifest_path = format!("{}/{}", local_folder, MANIFEST_KEY);
+        let remote_tree = match JsonManager::read_from_json::<FileNode>(&manifest_path) {
+            Ok(tree) => tree,
+            Err(_) => return Ok(None), // Bucket was pushed before manifests existed
+        };
+
+        for (relative_path, result) in GitRestorer::restore_repositories(&remote_tree, Path::new(local_folder)) {
+            match result {
+                Ok(true) => println!("Restored git repository: {}", relative_path),
+                Ok(false) => {} // Already exists, left alone
+                Err(e) => eprintln!("Warning: Could not restore git repository {}: {}", relative_path, e),
+            }
+        }
+
+        Ok(Some(remote_tree))
+    }
+
     /// List available buckets on the server - git-like remote list
     pub fn list_remote_buckets() -> Result<Vec<String>, String> {
         println!("Discovering available buckets on MinIO server...");
@@ -258,10 +536,20 @@ impl InitInterface {
         // Get remote files
         let remote_files = MinioUtil::list_files_in_bucket(bucket)?;
 
+        // Restore git repositories that are missing locally before files land inside them
+        let previous_remote = read_local_manifest(local_folder);
+        let remote_tree = Self::fetch_remote_metadata(bucket, &remote_files, local_folder)?;
+        if let Some(remote_tree) = &remote_tree {
+            SyncManager::restore_folders(remote_tree, Some(&local_tree), Path::new(local_folder))?;
+        }
+
         // Download files that don't exist locally or are newer remotely
         let mut downloaded_count = 0;
-        for remote_file in &remote_files {
-            let local_path = format!("{}/{}", local_folder, remote_file);
+        for remote_file in remote_files
+            .iter()
+            .filter(|f| !is_metadata_key(f) && !deleted_remotely(f, remote_tree.as_ref()))
+        {
+            let local_path = local_path_in(&local_tree, local_folder, remote_file);
             
             // Always download to ensure we have the latest version (simple approach)
             let should_download = true;
@@ -273,7 +561,7 @@ impl InitInterface {
                         .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
                 }
 
-                match MinioUtil::download_file(bucket, remote_file, &local_path) {
+                match SyncManager::download_entry(bucket, remote_file, &local_path, remote_tree.as_ref()) {
                     Ok(_) => {
                         println!("Downloaded: {}", remote_file);
                         downloaded_count += 1;
@@ -285,12 +573,18 @@ impl InitInterface {
             }
         }
 
+        if let Some(remote_tree) = &remote_tree {
+            if let Some(previous_remote) = &previous_remote {
+                Self::apply_remote_folder_deletions(previous_remote, remote_tree, &local_tree);
+            }
+            SyncManager::apply_folder_permissions(remote_tree, Some(&local_tree), Path::new(local_folder))?;
+        }
+
         // Update or create structure file (auto-initialize if needed)
         let updated_tree = if was_uninitialized {
             // Generate new structure file
-            let tree = ReadFileTree::generate_tree(local_folder)
-                .map_err(|e| format!("Failed to generate file tree: {}", e))?;
-            tree
+            ReadFileTree::generate_tree(local_folder)
+                .map_err(|e| format!("Failed to generate file tree: {}", e))?
         } else {
             // Update existing structure file
             ReadFileTree::generate_tree_preserving_sync_data(local_folder)
@@ -301,7 +595,7 @@ impl InitInterface {
             .map_err(|e| format!("Failed to update structure file: {}", e))?;
 
         // Save bucket association
-        if let Err(_) = BucketManager::save_bucket_association(local_folder, bucket) {
+        if BucketManager::save_bucket_association(local_folder, bucket).is_err() {
             eprintln!("Warning: Could not save bucket association");
         }
 
@@ -327,29 +621,136 @@ impl InitInterface {
         }
 
         // Load current structure
-        let file_tree = ReadFileTree::generate_tree_preserving_sync_data(local_folder)
+        let scan = ReadFileTree::scan_tree_preserving_sync_data(local_folder)
             .map_err(|e| format!("Failed to scan folder: {}", e))?;
+        let file_tree = scan.tree;
 
-        // Count files that need syncing
+        // Count files and links whose contents need syncing, permission-only changes are listed apart
         let files_needing_sync: Vec<&FileNode> = file_tree
+            .get_all_objects()
+            .into_iter()
+            .filter(|f| f.needs_sync() && !f.metadata_changed())
+            .collect();
+        let permission_changes: Vec<&FileNode> = file_tree
             .get_all_files()
             .into_iter()
-            .filter(|f| f.needs_sync())
+            .filter(|f| f.permissions_changed())
             .collect();
 
         let total_files = file_tree.get_all_files().len();
         
         println!("Total files: {}", total_files);
         
-        if files_needing_sync.is_empty() {
+        let xattr_changes: Vec<&FileNode> = file_tree
+            .get_all_files()
+            .into_iter()
+            .filter(|f| f.xattrs_changed())
+            .collect();
+
+        // A running daemon knows better than a fresh scan what is about to be pushed
+        if let Some(live) = daemon_folder_status(local_folder) {
+            println!("Live state from the sync daemon (bucket '{}'):", live.bucket);
+            print_daemon_folder(&live);
+            if live.pending.is_empty() && live.activity == FolderActivity::Idle {
+                println!(" All files are synchronized");
+            }
+            for path in &live.pending {
+                let path = Path::new(path);
+                println!("  {}", path.strip_prefix(local_folder).unwrap_or(path).display());
+            }
+        } else if files_needing_sync.is_empty() && permission_changes.is_empty() && xattr_changes.is_empty() {
             println!(" All files are synchronized");
-        } else {
+        } else if !files_needing_sync.is_empty() {
             println!("Files that need syncing: {}", files_needing_sync.len());
             for file in &files_needing_sync {
+                match &file.link_target {
+                    Some(target) => println!("  {} -> {}", file.relative_path, target),
+                    None => println!("  {}", file.relative_path),
+                }
+            }
+        }
+
+        if !permission_changes.is_empty() {
+            println!("Permission changes: {}", permission_changes.len());
+            for file in &permission_changes {
+                println!(
+                    "  {} ({:o} -> {:o})",
+                    file.relative_path,
+                    file.synced_mode.unwrap_or_default(),
+                    file.mode.unwrap_or_default()
+                );
+            }
+        }
+
+        if !xattr_changes.is_empty() {
+            println!("Extended attribute changes: {}", xattr_changes.len());
+            for file in &xattr_changes {
                 println!("  {}", file.relative_path);
             }
         }
 
+        // Show how each embedded git repository was handled
+        let repos = file_tree.get_git_repositories();
+        if !repos.is_empty() {
+            println!("Git repositories: {}", repos.len());
+            for repo in &repos {
+                let mode = repo
+                    .git_info
+                    .as_ref()
+                    .map(|git| match git.kind {
+                        GitRepoKind::Standard => git.mode.to_string(),
+                        kind => format!("{}, {}", git.mode, kind),
+                    })
+                    .unwrap_or_default();
+                let remote = repo.git_remote.as_deref().unwrap_or("no remote");
+                let pending = if repo.needs_sync() { " (bundle needs upload)" } else { "" };
+                println!("  {} [{}] {}{}", repo.relative_path, mode, remote, pending);
+
+                if let Some(git) = &repo.git_info {
+                    if let Some(head) = &git.head {
+                        let branch = git.branch.as_deref().unwrap_or("detached");
+                        println!("    {} @ {}", branch, &head[..head.len().min(12)]);
+                    }
+                    if let (Some(ahead), Some(behind)) = (git.ahead, git.behind)
+                        && (ahead > 0 || behind > 0)
+                    {
+                        println!("    {} ahead, {} behind upstream", ahead, behind);
+                    }
+                    if git.dirty == Some(true) {
+                        println!("    uncommitted changes");
+                    }
+                }
+            }
+
+            // Commits that live only here are lost with this disk, make that impossible to miss
+            let at_risk: Vec<&&FileNode> = repos
+                .iter()
+                .filter(|repo| repo.git_info.as_ref().is_some_and(|git| git.has_local_only_commits()))
+                .collect();
+            if !at_risk.is_empty() {
+                println!();
+                println!("!!! WARNING: rusty-sync is NOT backing up these commits !!!");
+                for repo in &at_risk {
+                    let git = repo.git_info.as_ref().unwrap();
+                    let reason = if git.remotes.is_empty() {
+                        "repository has no remote".to_string()
+                    } else {
+                        "not pushed to any remote".to_string()
+                    };
+                    println!(
+                        "  {}: {} commit(s) exist only on this machine ({})",
+                        repo.relative_path,
+                        git.unpushed.unwrap_or(0),
+                        reason
+                    );
+                }
+                println!("  Push them to a remote or run 'rusty-sync settings set git-mode bundle'");
+            }
+        }
+
+        print_scan_errors(&scan.errors);
+        print_name_issues(&scan.name_issues);
+
         Ok(())
     }
 
@@ -446,22 +847,41 @@ impl InitInterface {
     pub fn handle_clone_command() -> Result<(), String> {
         let args: Vec<String> = env::args().collect();
 
-        if args.len() < 3 {
-            return Err("Usage: rusty-sync clone <bucket_name> [local_folder]".to_string());
-        }
+        let usage = "Usage: rusty-sync clone <bucket_name> [local_folder] [--key-file <path>]";
 
-        if args[1] != "clone" {
+        if args.get(1).map(String::as_str) != Some("clone") {
             return Err("Invalid command. Use 'clone' to clone a bucket".to_string());
         }
 
-        let bucket = &args[2];
-        let local_folder = if args.len() == 4 {
-            args[3].clone()
-        } else {
+        let mut positional = Vec::new();
+        let mut key_file = None;
+        let mut rest = args.iter().skip(2);
+        while let Some(arg) = rest.next() {
+            if arg == "--key-file" {
+                key_file = Some(rest.next().ok_or(usage)?.clone());
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        if positional.is_empty() || positional.len() > 2 {
+            return Err(usage.to_string());
+        }
+
+        let bucket = &positional[0];
+        let local_folder = match positional.get(1) {
+            Some(folder) => folder.clone(),
             // Clone to current directory with bucket name as folder
-            format!("./{}", bucket)
+            None => format!("./{}", bucket),
         };
 
+        // An encrypted bucket needs its key on this machine before anything can be downloaded
+        if let Some(params) = Self::fetch_encryption_params(bucket)?
+            && EncryptionKey::load(bucket)?.is_none_or(|key| key.salt() != params.salt)
+        {
+            println!("Bucket '{}' is encrypted", bucket);
+            Self::unlock_bucket(bucket, &params, key_file.as_deref())?;
+        }
+
         Self::clone_bucket(bucket, &local_folder)
     }
 
@@ -571,7 +991,7 @@ impl InitInterface {
         };
 
         // Save bucket association for future auto-detection
-        if let Err(_) = BucketManager::save_bucket_association(&local_folder, &bucket) {
+        if BucketManager::save_bucket_association(&local_folder, &bucket).is_err() {
             // Non-critical error, just warn
             eprintln!(" Warning: Could not save bucket association");
         }
@@ -659,7 +1079,7 @@ impl InitInterface {
         match MinioUtil::check_bucket_exists(bucket_name) {
             Ok(true) => {
                 println!(" Bucket '{}' already exists", bucket_name);
-                return Ok(());
+                Ok(())
             }
             Ok(false) => {
                 // Bucket doesn't exist, create it
@@ -671,9 +1091,750 @@ impl InitInterface {
         }
     }
 
+    /// Handle settings command - show or change per-folder settings
+    pub fn handle_settings_command() -> Result<(), String> {
+        let args: Vec<String> = env::args().collect();
+
+        let current_dir = || -> Result<String, String> {
+            Ok(env::current_dir()
+                .map_err(|e| format!("Failed to get current directory: {}", e))?
+                .to_string_lossy()
+                .to_string())
+        };
+
+        if args.len() >= 5 && args[2] == "set" {
+            // rusty-sync settings set <key> <value> [folder]
+            let folder = match args.get(5) {
+                Some(folder) => folder.clone(),
+                None => current_dir()?,
+            };
+
+            let mut settings = FolderSettings::load(&folder);
+            settings.set(&args[3], &args[4])?;
+            settings.save(&folder)?;
+            println!("Set {} = {} for folder '{}'", args[3], args[4], folder);
+            Ok(())
+        } else if args.len() <= 3 {
+            // rusty-sync settings [folder]
+            let folder = match args.get(2) {
+                Some(folder) => folder.clone(),
+                None => current_dir()?,
+            };
+
+            println!("Settings for folder: {}", folder);
+            for (key, value) in FolderSettings::load(&folder).entries() {
+                println!("  {} = {}", key, value);
+            }
+            Ok(())
+        } else {
+            Err("Usage: rusty-sync settings [folder] or rusty-sync settings set <key> <value> [folder]".to_string())
+        }
+    }
+
+    /// Handle encryption command - encrypt a bucket's contents on this side of the connection
+    pub fn handle_encryption_command() -> Result<(), String> {
+        let args: Vec<String> = env::args().collect();
+        let usage = "Usage: rusty-sync encryption <enable | unlock | status | migrate | rekey | verify> [folder] [--key-file <path>] [--encrypt-names] [--deep]";
+
+        let mut positional = Vec::new();
+        let mut key_file = None;
+        let mut encrypt_names = false;
+        let mut deep = false;
+        let mut rest = args.iter().skip(2);
+        while let Some(arg) = rest.next() {
+            if arg == "--key-file" {
+                key_file = Some(rest.next().ok_or(usage)?.clone());
+            } else if arg == "--encrypt-names" {
+                encrypt_names = true;
+            } else if arg == "--deep" {
+                deep = true;
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        if positional.is_empty() || positional.len() > 2 {
+            return Err(usage.to_string());
+        }
+
+        let folder = absolute_folder(positional.get(1).cloned())?;
+        let bucket = associated_bucket(&folder)?;
+        let params = Self::fetch_encryption_params(&bucket)?;
+
+        match (positional[0].as_str(), params) {
+            ("enable", Some(_)) => Err(format!(
+                "Bucket '{}' is already encrypted. Use 'rusty-sync encryption unlock' on this machine",
+                bucket
+            )),
+            ("enable", None) => Self::enable_encryption(&folder, &bucket, key_file.as_deref(), encrypt_names),
+            ("unlock", Some(params)) => Self::unlock_bucket(&bucket, &params, key_file.as_deref()),
+            ("migrate", Some(params)) => match EncryptionKey::load(&bucket)? {
+                Some(key) if key.salt() == params.salt => {
+                    let converted = Self::encrypt_existing_objects(&bucket, &key)?;
+                    println!("Encrypted {} remaining object(s) in bucket '{}'", converted, bucket);
+                    Ok(())
+                }
+                _ => Err(format!("Bucket '{}' is locked on this machine. Run 'rusty-sync encryption unlock' first", bucket)),
+            },
+            ("rekey", Some(params)) => Self::rekey_bucket(&folder, &bucket, &params, key_file.as_deref()),
+            ("verify", Some(params)) => Self::verify_encryption(&bucket, &params, deep),
+            ("unlock" | "status" | "migrate" | "rekey" | "verify", None) => {
+                println!("Bucket '{}' is not encrypted", bucket);
+                Ok(())
+            }
+            ("status", Some(params)) => {
+                let names = if params.names.is_some() { "contents and names" } else { "contents" };
+                match EncryptionKey::load(&bucket)? {
+                    Some(key)
+                        if key.salt() == params.salt || key.retired.iter().any(|retired| retired.salt == params.salt) =>
+                    {
+                        println!("Bucket '{}' is encrypted ({}) and unlocked on this machine", bucket, names)
+                    }
+                    Some(_) => println!(
+                        "Bucket '{}' is encrypted with a key that changed since it was unlocked here. Run 'rusty-sync encryption unlock'",
+                        bucket
+                    ),
+                    None => println!(
+                        "Bucket '{}' is encrypted but locked on this machine. Run 'rusty-sync encryption unlock'",
+                        bucket
+                    ),
+                }
+                if MinioUtil::object_etag(&bucket, ENCRYPTION_NEXT_KEY)?.is_some() {
+                    println!("A key change is in progress. Run 'rusty-sync encryption rekey' to finish it");
+                }
+                Ok(())
+            }
+            _ => Err(usage.to_string()),
+        }
+    }
+
+    /// Encrypt everything pushed from now on and convert what the bucket already holds
+    fn enable_encryption(folder: &str, bucket: &str, key_file: Option<&str>, encrypt_names: bool) -> Result<(), String> {
+        let (params, key) = EncryptionParams::generate(&read_secret(key_file, true)?, encrypt_names)?;
+
+        // Parameters go up first, a key other machines can't derive would lock them out
+        Self::upload_encryption_params(folder, bucket, ENCRYPTION_PARAMS_KEY, &params)?;
+        key.save(bucket)?;
+        MinioUtil::forget_bucket_key(bucket);
+        println!("Encryption enabled for bucket '{}'", bucket);
+        println!("Keep the passphrase or key file safe, the bucket's contents can't be recovered without it");
+
+        let converted = Self::encrypt_existing_objects(bucket, &key).map_err(|e| {
+            format!(
+                "{}. Run 'rusty-sync encryption migrate' to finish encrypting the bucket",
+                e
+            )
+        })?;
+        println!("Encrypted {} existing object(s)", converted);
+        Ok(())
+    }
+
+    /// Replace objects stored before the bucket was encrypted with sealed copies, under encrypted names if enabled
+    /// Objects that were converted already are skipped, so an interrupted run can simply be repeated
+    fn encrypt_existing_objects(bucket: &str, key: &EncryptionKey) -> Result<usize, String> {
+        let stored = MinioUtil::without_encryption(|| MinioUtil::list_files_in_bucket(bucket))?;
+        let mut converted = 0;
+
+        for object in stored.iter().filter(|object| !stored_in_plaintext(object)) {
+            if key.names.is_some() && key.path_of(object).is_some() {
+                continue; // Stored under an encrypted name, so already converted
+            }
+            let metadata = MinioUtil::without_encryption(|| MinioUtil::object_metadata(bucket, object))?
+                .unwrap_or_default();
+            if metadata.contains_key(ENCRYPTION_METADATA) {
+                continue;
+            }
+
+            let download = TempFile::new()?;
+            let path = download.path.to_string_lossy();
+            MinioUtil::without_encryption(|| MinioUtil::download_file(bucket, object, &path))?;
+            MinioUtil::upload_file_with_metadata(&path, bucket, object, &metadata)?;
+            if key.object_name(object) != *object {
+                // The original goes only once its sealed copy is really in the bucket
+                let sealed = MinioUtil::object_metadata(bucket, object)?;
+                if !sealed.is_some_and(|metadata| metadata.contains_key(ENCRYPTION_METADATA)) {
+                    return Err(format!("Sealed copy of {} is missing from the bucket, kept the original", object));
+                }
+                MinioUtil::without_encryption(|| MinioUtil::delete_file(bucket, object))?;
+            }
+            converted += 1;
+        }
+
+        Ok(converted)
+    }
+
+    /// Derive a bucket's key from its passphrase or key file and keep it on this machine
+    fn unlock_bucket(bucket: &str, params: &EncryptionParams, key_file: Option<&str>) -> Result<(), String> {
+        let key = params.derive(&read_secret(key_file, false)?)?;
+        key.save(bucket)?;
+        MinioUtil::forget_bucket_key(bucket);
+        println!("Unlocked bucket '{}' on this machine", bucket);
+        Ok(())
+    }
+
+    /// Switch a bucket to a new passphrase or key file
+    /// Data keys are re-wrapped in place, only objects from before data keys existed are uploaded again.
+    /// The new parameters wait in their own object until every object is converted, so an interrupted
+    /// rekey is finished by running it again
+    fn rekey_bucket(folder: &str, bucket: &str, params: &EncryptionParams, key_file: Option<&str>) -> Result<(), String> {
+        let locked = format!("Bucket '{}' is locked on this machine. Run 'rusty-sync encryption unlock' first", bucket);
+        let local = EncryptionKey::load(bucket)?.ok_or(locked.clone())?;
+
+        // Until the rekey finishes, objects sealed with the old key must stay readable here
+        let (next, mut key) = match Self::fetch_params_object(bucket, ENCRYPTION_NEXT_KEY)? {
+            Some(next) => {
+                println!("Resuming the key change of bucket '{}'", bucket);
+                if local.salt() == next.salt {
+                    (next, local) // Started on this machine, the old key is retired in it already
+                } else if local.salt() == params.salt {
+                    let mut key = next.derive(&read_secret(key_file, false)?)?;
+                    key.retire(&local);
+                    (next, key)
+                } else {
+                    return Err(locked);
+                }
+            }
+            None if local.salt() == params.salt => {
+                println!("Enter the new passphrase for bucket '{}'", bucket);
+                let (next, mut key) = EncryptionParams::rotate(&read_secret(key_file, true)?, &local)?;
+                Self::upload_encryption_params(folder, bucket, ENCRYPTION_NEXT_KEY, &next)?;
+                key.retire(&local);
+                (next, key)
+            }
+            None => return Err(locked),
+        };
+        key.save(bucket)?;
+        MinioUtil::forget_bucket_key(bucket);
+
+        let converted = Self::rewrap_objects(bucket, &key)?;
+        Self::upload_encryption_params(folder, bucket, ENCRYPTION_PARAMS_KEY, &next)?;
+        MinioUtil::delete_file(bucket, ENCRYPTION_NEXT_KEY)?;
+        // Other machines may have sealed objects with the old key while this ran
+        let stragglers = Self::rewrap_objects(bucket, &key)?;
+
+        key.retired.clear();
+        key.save(bucket)?;
+        MinioUtil::forget_bucket_key(bucket);
+        println!("Changed the key of bucket '{}', {} object(s) converted", bucket, converted + stragglers);
+        println!("Other machines need to run 'rusty-sync encryption unlock' with the new passphrase");
+
+        Self::verify_encryption(bucket, &next, false)
+    }
+
+    /// Wrap the data keys of all objects not yet sealed for `key`'s master key with it
+    fn rewrap_objects(bucket: &str, key: &EncryptionKey) -> Result<usize, String> {
+        let mut converted = 0;
+
+        for object in MinioUtil::list_files_in_bucket(bucket)? {
+            if stored_in_plaintext(&object) {
+                continue;
+            }
+            let Some(metadata) = MinioUtil::object_metadata(bucket, &object)? else {
+                continue; // Deleted since the listing
+            };
+            if metadata.get(SALT_METADATA).map(String::as_str) == Some(key.salt()) {
+                continue;
+            }
+
+            match key.rewrap(&metadata, &object)? {
+                Some(rewrapped) => MinioUtil::replace_encryption_metadata(bucket, &object, &rewrapped)?,
+                None => {
+                    // No data key to re-wrap, the contents have to be sealed again
+                    let download = TempFile::new()?;
+                    let path = download.path.to_string_lossy();
+                    MinioUtil::download_file(bucket, &object, &path)?;
+                    MinioUtil::upload_file(&path, bucket, &object)?;
+                }
+            }
+            converted += 1;
+        }
+
+        Ok(converted)
+    }
+
+    /// Check that every object of an encrypted bucket is sealed for its current key
+    /// With `deep` every object is downloaded and decrypted too, which catches corrupted contents
+    fn verify_encryption(bucket: &str, params: &EncryptionParams, deep: bool) -> Result<(), String> {
+        let key = match EncryptionKey::load(bucket)? {
+            Some(key) if key.salt() == params.salt => key,
+            _ => return Err(format!("Bucket '{}' is locked on this machine. Run 'rusty-sync encryption unlock' first", bucket)),
+        };
+        let stored = MinioUtil::without_encryption(|| MinioUtil::list_files_in_bucket(bucket))?;
+        let mut problems = Vec::new();
+        let mut checked = 0;
+
+        for object in stored.iter().filter(|object| !stored_in_plaintext(object)) {
+            let path = match key.names {
+                Some(_) => match key.path_of(object) {
+                    Some(path) => path,
+                    None => {
+                        problems.push(format!("{}: name isn't encrypted with the bucket's key", object));
+                        continue;
+                    }
+                },
+                None => object.clone(),
+            };
+            let metadata = MinioUtil::without_encryption(|| MinioUtil::object_metadata(bucket, object))?
+                .unwrap_or_default();
+            if let Err(e) = key.check_object(&metadata, &path) {
+                problems.push(format!("{}: {}", path, e));
+                continue;
+            }
+            if deep {
+                let download = TempFile::new()?;
+                if let Err(e) = MinioUtil::download_file(bucket, &path, &download.path.to_string_lossy()) {
+                    problems.push(format!("{}: {}", path, e));
+                    continue;
+                }
+            }
+            checked += 1;
+        }
+
+        if MinioUtil::object_etag(bucket, ENCRYPTION_NEXT_KEY)?.is_some() {
+            problems.push("A key change is in progress. Run 'rusty-sync encryption rekey' to finish it".to_string());
+        }
+        if problems.is_empty() {
+            println!("All {} object(s) in bucket '{}' are encrypted with its current key", checked, bucket);
+            return Ok(());
+        }
+        for problem in &problems {
+            eprintln!("  {}", problem);
+        }
+        Err(format!("{} problem(s) found in bucket '{}'", problems.len(), bucket))
+    }
+
+    /// Write key derivation parameters into the metadata folder and upload them as `object_name`
+    /// The salt goes into the object metadata too, so machines notice a changed key with a HEAD request
+    fn upload_encryption_params(
+        folder: &str,
+        bucket: &str,
+        object_name: &str,
+        params: &EncryptionParams,
+    ) -> Result<(), String> {
+        let params_path = format!("{}/{}", folder, object_name);
+        std::fs::create_dir_all(format!("{}/{}", folder, METADATA_DIR))
+            .map_err(|e| format!("Failed to create metadata folder: {}", e))?;
+        JsonManager::write_to_json(&params_path, params)
+            .map_err(|e| format!("Failed to write encryption parameters: {}", e))?;
+        let metadata = BTreeMap::from([(SALT_METADATA.to_string(), params.salt.clone())]);
+        MinioUtil::upload_file_with_metadata(&params_path, bucket, object_name, &metadata)
+    }
+
+    /// Key derivation parameters of a bucket, None if it isn't encrypted
+    fn fetch_encryption_params(bucket: &str) -> Result<Option<EncryptionParams>, String> {
+        Self::fetch_params_object(bucket, ENCRYPTION_PARAMS_KEY)
+    }
+
+    fn fetch_params_object(bucket: &str, object_name: &str) -> Result<Option<EncryptionParams>, String> {
+        if MinioUtil::object_etag(bucket, object_name)?.is_none() {
+            return Ok(None);
+        }
+
+        let download = TempFile::new()?;
+        let path = download.path.to_string_lossy();
+        MinioUtil::download_file(bucket, object_name, &path)?;
+        JsonManager::read_from_json(&path)
+            .map(Some)
+            .map_err(|e| format!("Failed to read encryption parameters of '{}': {}", bucket, e))
+    }
+
+    /// Handle watch command - push changes continuously
+    pub fn handle_watch_command() -> Result<(), String> {
+        let args: Vec<String> = env::args().collect();
+
+        let mut folder = None;
+        let mut debounce = DEFAULT_WATCH_DEBOUNCE;
+        let mut rest = args.iter().skip(2);
+        while let Some(arg) = rest.next() {
+            if arg == "--debounce" {
+                let seconds: f64 = rest
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|seconds: &f64| *seconds >= 0.0)
+                    .ok_or("Usage: rusty-sync watch [folder] [--debounce <seconds>]")?;
+                debounce = Duration::from_secs_f64(seconds);
+            } else {
+                folder = Some(arg.clone());
+            }
+        }
+
+        let folder = absolute_folder(folder)?;
+        let bucket = associated_bucket(&folder)?;
+
+        Self::watch_folder(&folder, &bucket, debounce)
+    }
+
+    /// Handle watch-remote command - pull changes from the bucket continuously
+    pub fn handle_watch_remote_command() -> Result<(), String> {
+        let args: Vec<String> = env::args().collect();
+        let usage = "Usage: rusty-sync watch-remote [folder] [--interval <seconds>] [--notify]";
+
+        let mut folder = None;
+        let mut interval = DEFAULT_REMOTE_POLL_INTERVAL;
+        let mut notify = false;
+        let mut rest = args.iter().skip(2);
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--interval" => {
+                    let seconds: f64 = rest
+                        .next()
+                        .and_then(|value| value.parse().ok())
+                        .filter(|seconds: &f64| *seconds > 0.0)
+                        .ok_or(usage)?;
+                    interval = Duration::from_secs_f64(seconds);
+                }
+                "--notify" => notify = true,
+                _ if arg.starts_with("--") => return Err(usage.to_string()),
+                _ => folder = Some(arg.clone()),
+            }
+        }
+
+        let folder = absolute_folder(folder)?;
+        let bucket = associated_bucket(&folder)?;
+
+        Self::watch_remote(&folder, &bucket, interval, notify)
+    }
+
+    /// Handle daemon command - run the daemon or manage the folders it syncs
+    pub fn handle_daemon_command() -> Result<(), String> {
+        let args: Vec<String> = env::args().collect();
+        let usage = "Usage: rusty-sync daemon [run [--workers <n>] [--interval <s>] [--debounce <s>] | add <folder> [bucket] [--interval <s>] [--window \"<days> <HH:MM-HH:MM> [limit=<rate>]\"]... | remove <folder> | list | status | pause [folder] | resume [folder] | sync-now [folder] | events | install-unit]";
+
+        let (command, rest) = match args.get(2).map(String::as_str) {
+            Some(command) if !command.starts_with("--") => (command, &args[3..]),
+            _ => ("run", args.get(2..).unwrap_or_default()),
+        };
+
+        // Split --flag <value> pairs from positional arguments
+        let mut positional = Vec::new();
+        let mut flags = Vec::new();
+        let mut iter = rest.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(flag) = arg.strip_prefix("--") {
+                flags.push((flag, iter.next().ok_or(usage)?.as_str()));
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        let seconds = |value: &str| {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|seconds| *seconds >= 0.0)
+                .ok_or_else(|| usage.to_string())
+        };
+
+        match command {
+            "run" => {
+                let mut options = DaemonOptions::default();
+                for (flag, value) in flags {
+                    match flag {
+                        "workers" => {
+                            options.workers = value
+                                .parse::<usize>()
+                                .ok()
+                                .filter(|workers| *workers > 0)
+                                .ok_or_else(|| usage.to_string())?
+                        }
+                        "interval" => options.interval = Duration::from_secs_f64(seconds(value)?),
+                        "debounce" => options.debounce = Duration::from_secs_f64(seconds(value)?),
+                        _ => return Err(usage.to_string()),
+                    }
+                }
+                SyncDaemon::run(options)
+            }
+            "add" => {
+                if positional.is_empty() || positional.len() > 2 {
+                    return Err(usage.to_string());
+                }
+                let mut interval = None;
+                let mut schedule = Vec::new();
+                for (flag, value) in flags {
+                    match flag {
+                        "interval" => interval = Some(seconds(value)? as u64),
+                        "window" => schedule.push(value.parse::<ScheduleWindow>()?),
+                        _ => return Err(usage.to_string()),
+                    }
+                }
+
+                let path = absolute_folder(positional.first().cloned())?;
+                if !Path::new(&path).is_dir() {
+                    return Err(format!("'{}' is not a directory", path));
+                }
+                let bucket = match positional.get(1) {
+                    Some(bucket) => bucket.clone(),
+                    None => associated_bucket(&path)?,
+                };
+
+                let mut config = RustySyncConfig::load()?;
+                config.add_sync_folder(SyncFolder {
+                    path: path.clone(),
+                    bucket: bucket.clone(),
+                    interval,
+                    schedule: schedule.clone(),
+                })?;
+                println!("Daemon will sync '{}' to bucket '{}'", path, bucket);
+                for window in &schedule {
+                    println!("  during: {}", window);
+                }
+                Ok(())
+            }
+            "remove" => {
+                if positional.len() != 1 || !flags.is_empty() {
+                    return Err(usage.to_string());
+                }
+                let path = absolute_folder(positional.first().cloned())?;
+                let mut config = RustySyncConfig::load()?;
+                if config.remove_sync_folder(&path)? {
+                    println!("Daemon will no longer sync '{}'", path);
+                    Ok(())
+                } else {
+                    Err(format!("'{}' is not registered with the daemon", path))
+                }
+            }
+            "list" => {
+                let config = RustySyncConfig::load()?;
+                if config.sync_folders.is_empty() {
+                    println!("No folders registered with the daemon");
+                }
+                for folder in &config.sync_folders {
+                    match folder.interval {
+                        Some(interval) => println!("{} -> {} (full sync every {}s)", folder.path, folder.bucket, interval),
+                        None => println!("{} -> {}", folder.path, folder.bucket),
+                    }
+                    for window in &folder.schedule {
+                        println!("  during: {}", window);
+                    }
+                }
+                Ok(())
+            }
+            "status" => {
+                let response = ControlClient::request(&ControlRequest::Status)?;
+                response.folders.iter().for_each(print_daemon_folder);
+                Ok(())
+            }
+            "pause" | "resume" | "sync-now" => {
+                if positional.len() > 1 || !flags.is_empty() {
+                    return Err(usage.to_string());
+                }
+                let folder = match positional.first() {
+                    Some(folder) => Some(absolute_folder(Some(folder.clone()))?),
+                    None => None,
+                };
+                let request = match command {
+                    "pause" => ControlRequest::Pause { folder },
+                    "resume" => ControlRequest::Resume { folder },
+                    _ => ControlRequest::SyncNow { folder },
+                };
+
+                let response = ControlClient::request(&request)?;
+                if let Some(error) = response.error {
+                    return Err(error);
+                }
+                response.folders.iter().for_each(print_daemon_folder);
+                Ok(())
+            }
+            "events" => ControlClient::events(|folder| {
+                print_daemon_folder(folder);
+                true
+            }),
+            "install-unit" => {
+                let unit_path = SyncDaemon::install_user_unit()?;
+                println!("Wrote {}", unit_path.display());
+                println!("Enable and start it with:");
+                println!("  systemctl --user daemon-reload");
+                println!("  systemctl --user enable --now rusty-sync.service");
+                Ok(())
+            }
+            _ => Err(usage.to_string()),
+        }
+    }
+
+    /// Handle doctor command - check everything a sync of the folder depends on
+    pub fn handle_doctor_command() -> Result<(), String> {
+        let args: Vec<String> = env::args().collect();
+        if args.len() > 3 {
+            return Err("Usage: rusty-sync doctor [folder]".to_string());
+        }
+        Doctor::run(args.get(2).cloned())
+    }
+
     /// Handle config command - manage MinIO server configurations
     pub fn handle_config_command() -> Result<(), String> {
-        RustySyncConfig::interactive_setup()
+        let args: Vec<String> = env::args().collect();
+        let usage = "Usage: rusty-sync config [show | import-mc [--file <path>] [alias...] | migrate-secrets | tls [--ca-file <path>] [--client-cert <path>] [--client-key <path>] [--insecure | --verify] [--clear] | network [--proxy <url>] [--no-proxy <hosts>] [--connect-timeout <s>] [--read-timeout <s>] [--max-connections <n>] [--clear] | compat [--region <name>] [--addressing auto|path|virtual] [--signature v4|v2] [--checksums when-supported|when-required] [--clear]]";
+
+        match args.get(2).map(String::as_str) {
+            None => RustySyncConfig::interactive_setup(),
+            Some("show") => {
+                let config = RustySyncConfig::load()?;
+                if config.has_plaintext_secrets() {
+                    eprintln!("Warning: config.json holds secret keys, move them with 'rusty-sync config migrate-secrets'");
+                }
+                let credentials = ResolvedCredentials::resolve(&config)?;
+                println!("Endpoint:   {}", credentials.endpoint_url);
+                println!("Access key: {}", credentials.access_key);
+                println!("From:       {}", credentials.source);
+                match (&credentials.session_token, credentials.expiration) {
+                    (_, Some(expiration)) => println!("Expires:    {}", expiration.to_rfc3339()),
+                    (Some(_), None) => println!("Expires:    unknown (session token)"),
+                    (None, None) => {}
+                }
+                if let Some(server) = config.server_for(&credentials.endpoint_url)
+                    && (credentials.endpoint_url.starts_with("https://") || !server.tls.is_default())
+                {
+                    println!("TLS:        {}", server.tls);
+                }
+                let network = config
+                    .server_for(&credentials.endpoint_url)
+                    .map(|server| server.network.clone())
+                    .unwrap_or_default();
+                match network.proxy_for(&credentials.endpoint_url) {
+                    Some((proxy, source)) => println!("Proxy:      {} ({})", proxy, source),
+                    None => println!("Proxy:      none"),
+                }
+                println!("Network:    {}", network);
+                let compatibility = config
+                    .server_for(&credentials.endpoint_url)
+                    .map(|server| server.compatibility.clone())
+                    .unwrap_or_default();
+                println!("S3:         {}", compatibility);
+                Ok(())
+            }
+            Some("compat") => {
+                let mut config = RustySyncConfig::load()?;
+                let server = config.get_current_server_mut()?;
+                let mut compatibility = server.compatibility.clone();
+                let mut rest = args.iter().skip(3);
+                if rest.len() == 0 {
+                    println!("{}: {}", server.endpoint_url, compatibility);
+                    return Ok(());
+                }
+                while let Some(arg) = rest.next() {
+                    match arg.as_str() {
+                        "--region" => {
+                            let region = rest.next().ok_or(usage)?;
+                            compatibility.region = Some(region.clone()).filter(|region| !region.is_empty());
+                        }
+                        "--addressing" => compatibility.addressing = rest.next().ok_or(usage)?.parse()?,
+                        "--signature" => compatibility.signature = rest.next().ok_or(usage)?.parse()?,
+                        "--checksums" => compatibility.checksums = rest.next().ok_or(usage)?.parse()?,
+                        "--clear" => compatibility = Default::default(),
+                        _ => return Err(usage.to_string()),
+                    }
+                }
+                println!("{}: {}", server.endpoint_url, compatibility);
+                server.compatibility = compatibility;
+                config.save()
+            }
+            Some("network") => {
+                let mut config = RustySyncConfig::load()?;
+                let server = config.get_current_server_mut()?;
+                let mut network = server.network.clone();
+                let mut rest = args.iter().skip(3);
+                if rest.len() == 0 {
+                    println!("{}: {}", server.endpoint_url, network);
+                    return Ok(());
+                }
+                let number = |value: Option<&String>| -> Result<u64, String> {
+                    value.and_then(|value| value.parse().ok()).ok_or_else(|| usage.to_string())
+                };
+                while let Some(arg) = rest.next() {
+                    match arg.as_str() {
+                        "--proxy" => network.proxy = Some(rest.next().ok_or(usage)?.clone()),
+                        "--no-proxy" => {
+                            network.no_proxy = rest
+                                .next()
+                                .ok_or(usage)?
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|host| !host.is_empty())
+                                .map(str::to_string)
+                                .collect()
+                        }
+                        "--connect-timeout" => network.connect_timeout = Some(number(rest.next())?),
+                        "--read-timeout" => network.read_timeout = Some(number(rest.next())?),
+                        "--max-connections" => {
+                            network.max_connections = Some(number(rest.next())?.try_into().map_err(|_| usage.to_string())?)
+                        }
+                        "--clear" => network = Default::default(),
+                        _ => return Err(usage.to_string()),
+                    }
+                }
+                network.validate()?;
+                println!("{}: {}", server.endpoint_url, network);
+                server.network = network;
+                config.save()
+            }
+            Some("tls") => {
+                let mut config = RustySyncConfig::load()?;
+                let server = config.get_current_server_mut()?;
+                let mut tls = server.tls.clone();
+                let mut rest = args.iter().skip(3);
+                if rest.len() == 0 {
+                    println!("{}: {}", server.endpoint_url, tls);
+                    return Ok(());
+                }
+                while let Some(arg) = rest.next() {
+                    match arg.as_str() {
+                        "--ca-file" | "--client-cert" | "--client-key" => {
+                            let path = rest.next().ok_or(usage)?;
+                            let path = Path::new(path)
+                                .canonicalize()
+                                .map_err(|e| format!("Cannot use '{}': {}", path, e))?
+                                .to_string_lossy()
+                                .to_string();
+                            match arg.as_str() {
+                                "--ca-file" => tls.ca_file = Some(path),
+                                "--client-cert" => tls.client_cert = Some(path),
+                                _ => tls.client_key = Some(path),
+                            }
+                        }
+                        "--insecure" => tls.insecure = true,
+                        "--verify" => tls.insecure = false,
+                        "--clear" => tls = Default::default(),
+                        _ => return Err(usage.to_string()),
+                    }
+                }
+                tls.validate()?;
+                if tls.insecure {
+                    eprintln!(
+                        "Warning: certificates of {} will not be verified, anyone on the network path can read and change what is synced",
+                        server.endpoint_url
+                    );
+                }
+                println!("{}: {}", server.endpoint_url, tls);
+                server.tls = tls;
+                config.save()
+            }
+            Some("import-mc") => {
+                let mut file = None;
+                let mut only = Vec::new();
+                let mut rest = args.iter().skip(3);
+                while let Some(arg) = rest.next() {
+                    if arg == "--file" {
+                        file = Some(rest.next().ok_or(usage)?.clone());
+                    } else {
+                        only.push(arg.clone());
+                    }
+                }
+
+                let mut config = RustySyncConfig::load()?;
+                let imported = config.import_mc_aliases(file.as_deref(), &only)?;
+                println!("Imported {} server(s) from the MinIO client config", imported);
+                Ok(())
+            }
+            Some("migrate-secrets") => {
+                let mut config = RustySyncConfig::load()?;
+                if !config.has_plaintext_secrets() {
+                    println!("config.json holds no secret keys");
+                    return Ok(());
+                }
+                let moved = config.migrate_secrets()?;
+                println!("Moved the secret keys of {} server(s) out of config.json", moved);
+                Ok(())
+            }
+            _ => Err(usage.to_string()),
+        }
     }
 
     /// Handle test command
@@ -778,6 +1939,41 @@ impl InitInterface {
                     process::exit(1);
                 }
             },
+            "watch" => match Self::handle_watch_command() {
+                Ok(_) => {},
+                Err(e) => {
+                    eprintln!(" Error: {}", e);
+                    process::exit(1);
+                }
+            },
+            "watch-remote" => match Self::handle_watch_remote_command() {
+                Ok(_) => {},
+                Err(e) => {
+                    eprintln!(" Error: {}", e);
+                    process::exit(1);
+                }
+            },
+            "daemon" => match Self::handle_daemon_command() {
+                Ok(_) => {},
+                Err(e) => {
+                    eprintln!(" Error: {}", e);
+                    process::exit(1);
+                }
+            },
+            "settings" => match Self::handle_settings_command() {
+                Ok(_) => {},
+                Err(e) => {
+                    eprintln!(" Error: {}", e);
+                    process::exit(1);
+                }
+            },
+            "encryption" => match Self::handle_encryption_command() {
+                Ok(_) => {},
+                Err(e) => {
+                    eprintln!(" Error: {}", e);
+                    process::exit(1);
+                }
+            },
             "config" => match Self::handle_config_command() {
                 Ok(_) => {},
                 Err(e) => {
@@ -785,6 +1981,13 @@ impl InitInterface {
                     process::exit(1);
                 }
             },
+            "doctor" => match Self::handle_doctor_command() {
+                Ok(_) => {},
+                Err(e) => {
+                    eprintln!(" Error: {}", e);
+                    process::exit(1);
+                }
+            },
             "-h" | "--help" => {
                 print_usage();
             }
@@ -797,6 +2000,114 @@ impl InitInterface {
     }
 }
 
+/// One line per daemon folder: where it syncs to and what it is doing
+fn print_daemon_folder(folder: &FolderStatus) {
+    let mut line = format!("{} -> {}: {}", folder.path, folder.bucket, folder.activity);
+    if folder.paused {
+        line.push_str(" (paused)");
+    }
+    if !folder.pending.is_empty() {
+        line.push_str(&format!(", {} change(s) pending", folder.pending.len()));
+    }
+    if folder.failures > 0 {
+        line.push_str(&format!(", {} failed attempt(s)", folder.failures));
+    }
+    println!("{}", line);
+}
+
+/// State of a folder in the running daemon, None if no daemon runs or it doesn't manage the folder
+pub(crate) fn daemon_folder_status(local_folder: &str) -> Option<FolderStatus> {
+    let response = ControlClient::request(&ControlRequest::Status).ok()?;
+    let local = Path::new(local_folder).canonicalize().ok()?;
+    response
+        .folders
+        .into_iter()
+        .find(|folder| Path::new(&folder.path).canonicalize().is_ok_and(|path| path == local))
+}
+
+/// Absolute path of a folder argument, the current directory if none was given
+pub(crate) fn absolute_folder(folder: Option<String>) -> Result<String, String> {
+    match folder {
+        Some(folder) if Path::new(&folder).is_absolute() => Ok(folder.trim_end_matches('/').to_string()),
+        folder => Ok(env::current_dir()
+            .map_err(|e| format!("Failed to get current directory: {}", e))?
+            .join(folder.unwrap_or_default())
+            .to_string_lossy()
+            .trim_end_matches('/')
+            .to_string()),
+    }
+}
+
+/// Bucket a folder was associated with through `remote add`
+pub(crate) fn associated_bucket(folder: &str) -> Result<String, String> {
+    BucketManager::detect_bucket_name(folder).map_err(|_| {
+        format!(
+            "No bucket associated with '{}'. Run 'rusty-sync remote add {} <bucket>' first",
+            folder, folder
+        )
+    })
+}
+
+/// Print the paths a folder scan could not read
+fn print_scan_errors(errors: &[WalkError]) {
+    if errors.is_empty() {
+        return;
+    }
+
+    eprintln!("Could not read {} path(s), they were left out:", errors.len());
+    for error in errors {
+        eprintln!("  {}", error);
+    }
+}
+
+/// Print names that won't survive a checkout on every platform
+fn print_name_issues(issues: &[NameIssue]) {
+    if issues.is_empty() {
+        return;
+    }
+
+    eprintln!("{} name(s) are not portable across platforms:", issues.len());
+    for issue in issues {
+        eprintln!("  {}", issue);
+    }
+}
+
+/// Local file path for an object key, escaped where this platform can't store the name
+fn local_path_for_key(local_folder: &str, key: &str) -> String {
+    Path::new(local_folder).join(key_to_local(key)).to_string_lossy().to_string()
+}
+
+/// Local file path for an object key, keeping the on-disk names of what the last scan found
+fn local_path_in(local_tree: &FileNode, local_folder: &str, key: &str) -> String {
+    SyncManager::local_path(Some(local_tree), Path::new(local_folder), key)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Check if an object key lives under the rusty-sync metadata prefix
+fn is_metadata_key(key: &str) -> bool {
+    key.starts_with(&format!("{}/", METADATA_DIR))
+}
+
+/// Check if an object was deleted on another machine: the manifest doesn't list it and a tombstone covers it
+/// Such objects are leftovers of a deletion and must not be brought back
+fn deleted_remotely(key: &str, remote_tree: Option<&FileNode>) -> bool {
+    remote_tree.is_some_and(|tree| tree.find_by_relative_path(key).is_none() && tree.is_deleted(key))
+}
+
+/// Read the local copy of the remote manifest, i.e. the bucket state after the last push or pull
+pub(crate) fn read_local_manifest(folder_path: &str) -> Option<FileNode> {
+    JsonManager::read_from_json(&format!("{}/{}", folder_path, MANIFEST_KEY)).ok()
+}
+
+/// Keep a local copy of the manifest that was just published
+fn save_local_manifest(folder_path: &str, tree: &FileNode) -> Result<(), String> {
+    std::fs::create_dir_all(format!("{}/{}", folder_path, METADATA_DIR))
+        .map_err(|e| format!("Failed to create metadata directory: {}", e))?;
+    JsonManager::write_to_json(&format!("{}/{}", folder_path, MANIFEST_KEY), tree)
+        .map_err(|e| format!("Failed to save local manifest: {}", e))
+}
+
 /// Count total items (files and folders) in the tree
 fn count_items(node: &crate::utils::tree_serializer::FileNode) -> usize {
     let mut count = 1; // Count this node
@@ -826,11 +2137,42 @@ fn print_usage() {
     println!("    clone <bucket>              Clone bucket to current directory");
     println!();
     println!("  Git-like Commands:");
-    println!("    clone <bucket> <folder>     Clone a bucket to local folder");
+    println!("    clone <bucket> <folder> [--key-file <path>]  Clone a bucket to local folder");
     println!("    pull <bucket> [folder]      Pull changes from bucket to local folder");
     println!("    push <bucket> [folder]      Push local changes to bucket");
     println!("    status [folder]             Show sync status of local folder");
     println!("    remote [list]               List available buckets on server");
+    println!("    watch [folder] [--debounce <s>]  Push changes as they happen");
+    println!("    watch-remote [folder] [--interval <s>] [--notify]  Pull changes pushed elsewhere");
+    println!("    daemon [run] [--workers <n>] [--interval <s>]  Sync all registered folders");
+    println!("    daemon add <folder> [bucket] | remove <folder> | list  Manage daemon folders");
+    println!("    daemon add <folder> --window \"mon-fri 19:00-08:00 limit=2M\"  Only sync in these hours");
+    println!("    daemon status | events      Show what the running daemon is doing");
+    println!("    daemon pause | resume | sync-now [folder]  Control the running daemon");
+    println!("    daemon install-unit         Write a systemd user unit for the daemon");
+    println!("    encryption enable [folder] [--key-file <path>] [--encrypt-names]  Encrypt files before they leave this machine");
+    println!("    encryption unlock | status [folder]  Enter the key of an encrypted bucket on this machine");
+    println!("    encryption migrate [folder]  Finish encrypting objects after an interrupted enable");
+    println!("    encryption rekey [folder] [--key-file <path>]  Change the passphrase, re-wrapping every object's key");
+    println!("    encryption verify [folder] [--deep]  Check every object is encrypted with the current key");
+    println!("    config                      Add, choose or remove servers interactively");
+    println!("    config show                 Show which endpoint and credentials are used");
+    println!("    config import-mc [--file <path>] [alias...]  Import servers from the MinIO client");
+    println!("    config migrate-secrets      Move secret keys written by older versions out of config.json");
+    println!("    config tls [--ca-file <path>] [--client-cert <path>] [--client-key <path>] [--insecure | --verify] [--clear]");
+    println!("                                TLS settings of the active server, shown without options");
+    println!("    config network [--proxy <url>] [--no-proxy <host,...>] [--connect-timeout <s>] [--read-timeout <s>]");
+    println!("                   [--max-connections <n>] [--clear]  Proxy and timeouts of the active server");
+    println!("    config compat [--region <name>] [--addressing auto | path | virtual] [--signature v4 | v2]");
+    println!("                  [--checksums when-supported | when-required] [--clear]  S3 dialect of the active server");
+    println!("    doctor [folder]             Check Python, config, server connection and folder state");
+    println!("    settings [folder]           Show per-folder settings");
+    println!("    settings set <key> <value> [folder]  Change a per-folder setting");
+    println!("                                  git-mode: skip | remote-only | bundle | worktree");
+    println!("                                  max-depth: <n> | none, one-file-system: true | false");
+    println!("                                  symlinks: ignore | store | follow-inside");
+    println!("                                  preserve-owner: true | false, xattrs: true | false");
+    println!("                                  key-normalization: nfc | none");
     println!();
     println!("  Classic Commands:");
     println!("    -i, --init <folder_path>    Initialize a folder for sync");
@@ -1 +1,4 @@
+pub mod control;
+pub mod daemon;
+pub mod doctor;
 pub mod interface_init;
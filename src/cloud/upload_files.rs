@@ -9,6 +9,7 @@ impl FileUpload {
             .parent()
             .unwrap()
             .to_path_buf();
+        #[allow(unused_variables)]
         let file_tree: FileNode =
             serde_json::from_reader(File::open(json_path).map_err(|e| e.to_string())?)
                 .map_err(|e| e.to_string())?;
@@ -1,81 +1,264 @@
+use crate::cli::doctor::Doctor;
+use crate::config::credentials::{parse_expiration, read_mc_aliases, ResolvedCredentials, SessionCredentials};
+use crate::config::secret_store::{write_private, SecretRef};
+use crate::config::server_options::{NetworkOptions, S3Compatibility, TlsOptions};
+use crate::utils::schedule::ScheduleWindow;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use std::io::{self, Write};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct MinioConfig {
     pub endpoint_url: String,
     pub access_key: String,
-    pub secret_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub secret_key: String, // Plaintext, only until it's moved into a secret store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_ref: Option<SecretRef>, // Where the secret key is kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_token: Option<String>, // Plaintext, only until it's moved into a secret store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_ref: Option<SecretRef>, // Where the session token of temporary credentials is kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<String>, // RFC 3339 time the session token stops working
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_process: Option<String>, // Command printing fresh credentials, replaces the keys above
+    #[serde(default, skip_serializing_if = "TlsOptions::is_default")]
+    pub tls: TlsOptions,
+    #[serde(default, skip_serializing_if = "NetworkOptions::is_default")]
+    pub network: NetworkOptions,
+    #[serde(default, skip_serializing_if = "S3Compatibility::is_default")]
+    pub compatibility: S3Compatibility,
     pub alias: Option<String>, // Optional alias for the server
 }
 
+impl MinioConfig {
+    /// The server's secret key, looked up in its secret store
+    pub fn secret_key(&self) -> Result<String, String> {
+        match &self.secret_ref {
+            Some(secret_ref) => secret_ref.resolve(),
+            None if !self.secret_key.is_empty() => Ok(self.secret_key.clone()),
+            None => Err(format!("No secret key configured for {}", self.endpoint_url)),
+        }
+    }
+
+    /// The configured key pair, with the session token and its expiry for temporary credentials
+    pub fn session_credentials(&self) -> Result<SessionCredentials, String> {
+        let session_token = match (&self.session_ref, &self.session_token) {
+            (Some(session_ref), _) => Some(session_ref.resolve()?),
+            (None, token) => token.clone(),
+        };
+        Ok(SessionCredentials {
+            access_key: self.access_key.clone(),
+            secret_key: self.secret_key()?,
+            session_token,
+            expiration: self.expiration.as_deref().map(parse_expiration).transpose()?,
+        })
+    }
+
+    /// Move a plaintext secret key and session token into a secret store, returns false if there were none
+    /// The config only changes once both are stored, so a failure leaves the plaintext values in place
+    pub fn store_secret(&mut self) -> Result<bool, String> {
+        let id = format!("{}@{}", self.access_key, self.endpoint_url);
+        let secret_ref = match self.secret_key.is_empty() {
+            true => None,
+            false => Some(SecretRef::store(&id, &self.secret_key)?),
+        };
+        let session_ref = self
+            .session_token
+            .as_ref()
+            .map(|token| SecretRef::store(&format!("{}#session", id), token))
+            .transpose()?;
+
+        let stored = secret_ref.is_some() || session_ref.is_some();
+        if let Some(secret_ref) = secret_ref {
+            self.secret_ref = Some(secret_ref);
+            self.secret_key.clear();
+        }
+        if let Some(session_ref) = session_ref {
+            self.session_ref = Some(session_ref);
+            self.session_token = None;
+        }
+        Ok(stored)
+    }
+}
+
+/// What a MinIO utility command runs with, see `RustySyncConfig::minio_env`
+pub struct MinioEnv {
+    pub vars: Vec<(&'static str, String)>,
+    pub network: NetworkOptions, // Of the server the credentials are for
+}
+
+/// A folder the daemon keeps in sync with a bucket
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SyncFolder {
+    pub path: String,          // Absolute path of the local folder
+    pub bucket: String,
+    pub interval: Option<u64>, // Seconds between full syncs, None for the daemon default
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduleWindow>, // When transfers may run, empty for any time
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct RustySyncConfig {
     pub minio_servers: Vec<MinioConfig>,
     pub current_server: Option<usize>, // Index of currently active server
+    #[serde(default)]
+    pub sync_folders: Vec<SyncFolder>, // Folders managed by the daemon
 }
 
 impl RustySyncConfig {
-    /// Get the config file path (in user's home directory)
-    fn get_config_path() -> Result<PathBuf, String> {
-        let home_dir = dirs::home_dir()
-            .ok_or_else(|| "Could not find home directory".to_string())?;
-        
-        let config_dir = home_dir.join(".rusty-sync");
+    /// Get the config directory (in user's home directory), creating it if needed
+    pub fn get_config_dir() -> Result<PathBuf, String> {
+        let config_dir = Self::config_dir()?;
         if !config_dir.exists() {
             fs::create_dir_all(&config_dir)
                 .map_err(|e| format!("Failed to create config directory: {}", e))?;
         }
         
-        Ok(config_dir.join("config.json"))
+        Ok(config_dir)
+    }
+
+    /// The config directory, without creating it
+    fn config_dir() -> Result<PathBuf, String> {
+        let home_dir = dirs::home_dir()
+            .ok_or_else(|| "Could not find home directory".to_string())?;
+        Ok(home_dir.join(".rusty-sync"))
+    }
+
+    /// Get the config file path (in user's home directory)
+    pub fn config_path() -> Result<PathBuf, String> {
+        Ok(Self::config_dir()?.join("config.json"))
     }
 
     /// Load configuration from file, or create default if not exists
     pub fn load() -> Result<Self, String> {
-        let config_path = Self::get_config_path()?;
-        
-        if config_path.exists() {
-            let config_content = fs::read_to_string(&config_path)
-                .map_err(|e| format!("Failed to read config file: {}", e))?;
-            
-            let config: RustySyncConfig = serde_json::from_str(&config_content)
-                .map_err(|e| format!("Failed to parse config file: {}", e))?;
-            
-            Ok(config)
-        } else {
-            // Create default config
-            let default_config = RustySyncConfig::default();
-            default_config.save()?;
-            Ok(default_config)
+        match Self::read_existing()? {
+            Some(config) => Ok(config),
+            None => {
+                // Create default config
+                let default_config = RustySyncConfig::default();
+                default_config.save()?;
+                Ok(default_config)
+            }
         }
     }
 
+    /// Read the config file without creating or changing anything, None if there is none
+    pub fn read_existing() -> Result<Option<Self>, String> {
+        let config_path = Self::config_path()?;
+        if !config_path.exists() {
+            return Ok(None);
+        }
+
+        let config_content = fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        serde_json::from_str(&config_content)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse config file: {}", e))
+    }
+
     /// Save configuration to file
     pub fn save(&self) -> Result<(), String> {
-        let config_path = Self::get_config_path()?;
+        let config_path = Self::get_config_dir()?.join("config.json");
         
         let config_json = serde_json::to_string_pretty(self)
             .map_err(|e| format!("Failed to serialize config: {}", e))?;
         
-        fs::write(&config_path, config_json)
+        write_private(&config_path, config_json.as_bytes())
             .map_err(|e| format!("Failed to write config file: {}", e))?;
         
         Ok(())
     }
 
-    /// Add a new MinIO server configuration
-    pub fn add_server(&mut self, config: MinioConfig) -> Result<(), String> {
+    /// Check if the config still holds secret keys in plaintext, as configs from older versions do
+    pub fn has_plaintext_secrets(&self) -> bool {
+        self.minio_servers
+            .iter()
+            .any(|server| !server.secret_key.is_empty() || server.session_token.is_some())
+    }
+
+    /// Move plaintext secret keys into a secret store, leaving a server's in place if that fails
+    /// Only run on request (`config migrate-secrets` or the interactive setup), returns the servers moved
+    pub fn migrate_secrets(&mut self) -> Result<usize, String> {
+        let mut moved = 0;
+        for server in &mut self.minio_servers {
+            match server.store_secret() {
+                Ok(stored) => moved += usize::from(stored),
+                Err(e) => eprintln!("Warning: Secret key of {} stays in config.json: {}", server.endpoint_url, e),
+            }
+        }
+        if moved > 0 {
+            self.save()?;
+        }
+        Ok(moved)
+    }
+
+    /// Add a new MinIO server configuration, its secret key goes into a secret store
+    pub fn add_server(&mut self, mut config: MinioConfig) -> Result<(), String> {
+        config.store_secret()?;
+        if let Some(secret_ref) = &config.secret_ref {
+            println!("Secret key stored in {}", secret_ref.store);
+        }
         self.minio_servers.push(config);
         self.save()?;
         Ok(())
     }
 
+    /// Add the MinIO client's aliases as servers, all of them or the ones named in `only`
+    /// Aliases whose name, or endpoint and access key, are configured already are skipped
+    pub fn import_mc_aliases(&mut self, path: Option<&str>, only: &[String]) -> Result<usize, String> {
+        let mut imported = 0;
+
+        for server in read_mc_aliases(path)? {
+            let alias = server.alias.clone().unwrap_or_default();
+            if !only.is_empty() && !only.contains(&alias) {
+                continue;
+            }
+            let known = self.minio_servers.iter().any(|existing| {
+                existing.alias == server.alias
+                    || (existing.endpoint_url == server.endpoint_url && existing.access_key == server.access_key)
+            });
+            if known {
+                println!("Skipping {}: already configured", alias);
+                continue;
+            }
+
+            let endpoint_url = server.endpoint_url.clone();
+            self.add_server(server)?;
+            println!("Imported {} ({})", alias, endpoint_url);
+            imported += 1;
+        }
+
+        Ok(imported)
+    }
+
+    /// Register a folder with the daemon, replacing an existing entry for the same path
+    pub fn add_sync_folder(&mut self, folder: SyncFolder) -> Result<(), String> {
+        self.sync_folders.retain(|existing| existing.path != folder.path);
+        self.sync_folders.push(folder);
+        self.save()
+    }
+
+    /// Unregister a folder, returns false if it wasn't registered
+    pub fn remove_sync_folder(&mut self, path: &str) -> Result<bool, String> {
+        let before = self.sync_folders.len();
+        self.sync_folders.retain(|existing| existing.path != path);
+        if self.sync_folders.len() == before {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
     /// Get the current active server configuration
     pub fn get_current_server(&self) -> Result<&MinioConfig, String> {
         if self.minio_servers.is_empty() {
-            return Err("No MinIO servers configured. Run 'rusty-sync config' to add one.".to_string());
+            return Err(
+                "No MinIO servers configured. Run 'rusty-sync config' to add one, or set RUSTY_SYNC_ENDPOINT_URL, RUSTY_SYNC_ACCESS_KEY and RUSTY_SYNC_SECRET_KEY."
+                    .to_string(),
+            );
         }
 
         let index = self.current_server.unwrap_or(0);
@@ -86,6 +269,20 @@ impl RustySyncConfig {
         Ok(&self.minio_servers[index])
     }
 
+    /// The active server, for changing its settings
+    pub fn get_current_server_mut(&mut self) -> Result<&mut MinioConfig, String> {
+        self.get_current_server()?;
+        let index = self.current_server.unwrap_or(0);
+        Ok(&mut self.minio_servers[index])
+    }
+
+    /// The configured server with this endpoint URL
+    pub fn server_for(&self, endpoint_url: &str) -> Option<&MinioConfig> {
+        self.minio_servers
+            .iter()
+            .find(|server| server.endpoint_url.trim_end_matches('/') == endpoint_url.trim_end_matches('/'))
+    }
+
     /// Set the current active server
     pub fn set_current_server(&mut self, index: usize) -> Result<(), String> {
         if index >= self.minio_servers.len() {
@@ -103,6 +300,9 @@ impl RustySyncConfig {
         println!("==================================");
         
         let mut config = Self::load()?;
+        if config.has_plaintext_secrets() && config.migrate_secrets()? > 0 {
+            println!("Moved secret keys out of config.json");
+        }
         
         loop {
             println!("\nCurrent servers:");
@@ -170,7 +370,12 @@ impl RustySyncConfig {
                     
                     match input.trim().parse::<usize>() {
                         Ok(num) if num >= 1 && num <= config.minio_servers.len() => {
-                            config.minio_servers.remove(num - 1);
+                            let removed = config.minio_servers.remove(num - 1);
+                            for secret_ref in removed.secret_ref.iter().chain(&removed.session_ref) {
+                                if let Err(e) = secret_ref.delete() {
+                                    eprintln!("Warning: {}", e);
+                                }
+                            }
                             
                             // Reset current server if needed
                             if config.current_server.is_some() && config.current_server.unwrap() >= config.minio_servers.len() {
@@ -187,8 +392,9 @@ impl RustySyncConfig {
                     match config.get_current_server() {
                         Ok(server) => {
                             println!("Testing connection to {}...", server.endpoint_url);
-                            // We'll implement connection testing later
-                            println!("Connection test would be implemented here");
+                            if let Err(e) = Doctor::test_connection(None) {
+                                println!(" {}", e);
+                            }
                         }
                         Err(e) => println!(" {}", e),
                     }
@@ -231,31 +437,67 @@ impl RustySyncConfig {
             .map_err(|e| format!("Failed to read access key: {}", e))?;
         let access_key = access_key.trim().to_string();
 
-        print!("Secret Key: ");
-        io::stdout().flush().unwrap();
-        let mut secret_key = String::new();
-        io::stdin().read_line(&mut secret_key)
+        let secret_key = rpassword::prompt_password("Secret Key: ")
             .map_err(|e| format!("Failed to read secret key: {}", e))?;
         let secret_key = secret_key.trim().to_string();
 
+        // Temporary credentials: a fixed session token, or a command that prints fresh credentials
+        let session_token = rpassword::prompt_password("Session token (empty for none): ")
+            .map_err(|e| format!("Failed to read session token: {}", e))?;
+        let session_token = Some(session_token.trim().to_string()).filter(|token| !token.is_empty());
+        let expiration = match session_token {
+            Some(_) => {
+                let expiration = Self::prompt_line("Token expires at (RFC 3339, empty if unknown): ")?;
+                if !expiration.is_empty() {
+                    parse_expiration(&expiration)?;
+                }
+                Some(expiration).filter(|expiration| !expiration.is_empty())
+            }
+            None => None,
+        };
+        let credential_process = Self::prompt_line("Credential process command (empty for none): ")?;
+
         Ok(MinioConfig {
             endpoint_url: endpoint,
             access_key,
             secret_key,
+            session_token,
+            expiration,
+            credential_process: Some(credential_process).filter(|command| !command.is_empty()),
             alias: if alias.is_empty() { None } else { Some(alias) },
+            ..Default::default()
         })
     }
 
-    /// Create environment variables from current server config for MinIO utility
-    pub fn export_to_env(&self) -> Result<(), String> {
-        let server = self.get_current_server()?;
-        
-        unsafe {
-            std::env::set_var("MINIO_ENDPOINT_URL", &server.endpoint_url);
-            std::env::set_var("MINIO_ACCESS_KEY", &server.access_key);
-            std::env::set_var("MINIO_SECRET_KEY", &server.secret_key);
+    fn prompt_line(prompt: &str) -> Result<String, String> {
+        print!("{}", prompt);
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        io::stdin().read_line(&mut line)
+            .map_err(|e| format!("Failed to read input: {}", e))?;
+        Ok(line.trim().to_string())
+    }
+
+    /// Environment the MinIO utility runs with, for the credentials `ResolvedCredentials` picks
+    pub fn minio_env(&self) -> Result<MinioEnv, String> {
+        let credentials = ResolvedCredentials::resolve(self)?;
+
+        let mut vars = vec![
+            ("MINIO_ENDPOINT_URL", credentials.endpoint_url.clone()),
+            ("MINIO_ACCESS_KEY", credentials.access_key),
+            ("MINIO_SECRET_KEY", credentials.secret_key),
+        ];
+        if let Some(token) = credentials.session_token {
+            vars.push(("MINIO_SESSION_TOKEN", token));
         }
-        
-        Ok(())
+        // Settings of a configured server follow its endpoint, whichever source the credentials came from
+        let server = self.server_for(&credentials.endpoint_url);
+        if let Some(server) = server {
+            vars.extend(server.tls.env(&server.endpoint_url)?);
+        }
+        let network = server.map(|server| server.network.clone()).unwrap_or_default();
+        vars.extend(network.env(&credentials.endpoint_url)?);
+        vars.extend(server.map(|server| server.compatibility.clone()).unwrap_or_default().env());
+        Ok(MinioEnv { vars, network })
     }
 }
@@ -0,0 +1,367 @@
+// credentials from outside config.json: environment variables, AWS shared config files and MinIO client aliases
+// lets CI and containers connect without running the interactive setup
+
+use crate::config::config_manager::{MinioConfig, RustySyncConfig};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+/// Credentials from a credential process are fetched again this long before they expire
+const REFRESH_MARGIN_SECONDS: i64 = 120;
+
+// Credentials credential processes printed, per command, reused until shortly before they expire
+static PROCESS_CREDENTIALS: Mutex<BTreeMap<String, SessionCredentials>> = Mutex::new(BTreeMap::new());
+
+/// Credentials a command connects with, and where they came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedCredentials {
+    pub endpoint_url: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,      // Set for temporary (STS) credentials
+    pub expiration: Option<DateTime<Utc>>, // When temporary credentials stop working, if known
+    pub source: String,                    // Shown to tell which of the sources won
+}
+
+/// A key pair, with a session token for temporary credentials
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SessionCredentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+    pub expiration: Option<DateTime<Utc>>,
+}
+
+/// What a credential process prints, the same JSON the AWS CLI's credential_process expects
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ProcessOutput {
+    version: u32,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    expiration: Option<String>,
+}
+
+/// One profile of ~/.aws/credentials and ~/.aws/config
+#[derive(Debug, Default, PartialEq)]
+pub struct AwsProfile {
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    pub session_token: Option<String>,
+    pub endpoint_url: Option<String>,
+    pub credential_process: Option<String>, // Used when the profile has no keys of its own
+}
+
+impl ResolvedCredentials {
+    /// Resolve in order: RUSTY_SYNC_* variables, the AWS profile named by RUSTY_SYNC_PROFILE, the active
+    /// configured server, and only when no server is configured AWS_* variables, the AWS_PROFILE profile
+    /// and the default AWS profile. AWS credentials meant for other tools don't override a configured server.
+    /// The endpoint comes from RUSTY_SYNC_ENDPOINT_URL or AWS_ENDPOINT_URL(_S3) when set, otherwise from
+    /// the profile or the configured server
+    pub fn resolve(config: &RustySyncConfig) -> Result<Self, String> {
+        let endpoint = env_var("RUSTY_SYNC_ENDPOINT_URL")
+            .or_else(|| env_var("AWS_ENDPOINT_URL_S3"))
+            .or_else(|| env_var("AWS_ENDPOINT_URL"));
+        let configured = config.get_current_server().ok();
+
+        if let Some(keys) = env_keys("RUSTY_SYNC_ACCESS_KEY", "RUSTY_SYNC_SECRET_KEY", "RUSTY_SYNC_SESSION_TOKEN")? {
+            return Self::with_endpoint(endpoint, configured, keys, "RUSTY_SYNC_* variables");
+        }
+        if let Some(name) = env_var("RUSTY_SYNC_PROFILE") {
+            return Self::from_profile(&name, endpoint, configured);
+        }
+
+        if let Some(server) = configured {
+            let keys = match &server.credential_process {
+                Some(process) => run_credential_process(process)?,
+                None => server.session_credentials()?,
+            };
+            let name = server.alias.as_deref().unwrap_or(&server.endpoint_url);
+            let credentials = Self::with_endpoint(endpoint, configured, keys, &format!("configured server {}", name))?;
+            if server.credential_process.is_none()
+                && let Some(expiration) = credentials.expiration
+                && expiration <= Utc::now()
+            {
+                return Err(format!(
+                    "The session token of {} expired at {}. Add new credentials with 'rusty-sync config'",
+                    name,
+                    expiration.to_rfc3339()
+                ));
+            }
+            return Ok(credentials);
+        }
+        if let Some(keys) = env_keys("AWS_ACCESS_KEY_ID", "AWS_SECRET_ACCESS_KEY", "AWS_SESSION_TOKEN")? {
+            return Self::with_endpoint(endpoint, None, keys, "AWS_* variables");
+        }
+        if let Some(name) = env_var("AWS_PROFILE") {
+            return Self::from_profile(&name, endpoint, None);
+        }
+        match AwsProfile::load("default")? {
+            Some(profile) if profile.access_key.is_some() || profile.credential_process.is_some() => {
+                Self::from_profile("default", endpoint, None)
+            }
+            _ => Err(config.get_current_server().unwrap_err()),
+        }
+    }
+
+    /// Forget credentials fetched by credential processes, so the next command fetches new ones
+    /// Returns false if there were none, then nothing can be refreshed
+    pub fn expire_cached() -> bool {
+        let mut cached = PROCESS_CREDENTIALS.lock().unwrap();
+        let had_any = !cached.is_empty();
+        cached.clear();
+        had_any
+    }
+
+    fn from_profile(name: &str, endpoint: Option<String>, configured: Option<&MinioConfig>) -> Result<Self, String> {
+        let profile = AwsProfile::load(name)?.ok_or_else(|| format!("AWS profile '{}' not found", name))?;
+        let keys = match (profile.access_key, profile.secret_key, &profile.credential_process) {
+            (Some(access_key), Some(secret_key), _) => SessionCredentials {
+                access_key,
+                secret_key,
+                session_token: profile.session_token,
+                expiration: None,
+            },
+            (None, None, Some(process)) => run_credential_process(process)?,
+            _ => {
+                return Err(format!(
+                    "AWS profile '{}' has no aws_access_key_id and aws_secret_access_key, or credential_process",
+                    name
+                ));
+            }
+        };
+        let source = format!("AWS profile '{}'", name);
+        Self::with_endpoint(endpoint.or(profile.endpoint_url), configured, keys, &source)
+    }
+
+    fn with_endpoint(
+        endpoint: Option<String>,
+        configured: Option<&MinioConfig>,
+        keys: SessionCredentials,
+        source: &str,
+    ) -> Result<Self, String> {
+        let endpoint_url = endpoint
+            .or_else(|| configured.map(|server| server.endpoint_url.clone()))
+            .ok_or_else(|| format!("No endpoint for the credentials from {}. Set RUSTY_SYNC_ENDPOINT_URL", source))?;
+        Ok(ResolvedCredentials {
+            endpoint_url,
+            access_key: keys.access_key,
+            secret_key: keys.secret_key,
+            session_token: keys.session_token,
+            expiration: keys.expiration,
+            source: source.to_string(),
+        })
+    }
+}
+
+/// Credentials printed by `command`, reused from an earlier run until they are about to expire
+pub fn run_credential_process(command: &str) -> Result<SessionCredentials, String> {
+    if let Some(cached) = PROCESS_CREDENTIALS.lock().unwrap().get(command)
+        && cached
+            .expiration
+            .is_none_or(|expiration| expiration - Utc::now() > Duration::seconds(REFRESH_MARGIN_SECONDS))
+    {
+        return Ok(cached.clone());
+    }
+
+    #[cfg(windows)]
+    let output = Command::new("cmd").arg("/C").arg(command).output();
+    #[cfg(unix)]
+    let output = Command::new("sh").arg("-c").arg(command).output();
+
+    let output = output.map_err(|e| format!("Failed to run credential process: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Credential process failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let printed: ProcessOutput = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Invalid credential process output: {}", e))?;
+    if printed.version != 1 {
+        return Err(format!("Unsupported credential process output version {}", printed.version));
+    }
+
+    let credentials = SessionCredentials {
+        access_key: printed.access_key_id,
+        secret_key: printed.secret_access_key,
+        session_token: printed.session_token,
+        expiration: printed.expiration.as_deref().map(parse_expiration).transpose()?,
+    };
+    PROCESS_CREDENTIALS
+        .lock()
+        .unwrap()
+        .insert(command.to_string(), credentials.clone());
+    Ok(credentials)
+}
+
+/// An RFC 3339 expiry time, such as 2026-10-19T15:00:00Z
+pub fn parse_expiration(text: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(text.trim())
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid expiry time '{}': {}", text, e))
+}
+
+impl AwsProfile {
+    /// A profile from the shared credentials and config files, None if neither has it
+    /// Values in the credentials file win over the config file, like the AWS CLI does
+    pub fn load(name: &str) -> Result<Option<AwsProfile>, String> {
+        let home = dirs::home_dir().ok_or("Could not find home directory")?;
+        let credentials = env_var("AWS_SHARED_CREDENTIALS_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".aws").join("credentials"));
+        let config = env_var("AWS_CONFIG_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".aws").join("config"));
+
+        // The config file names sections "profile x", except for the default profile
+        let config_section = match name {
+            "default" => name.to_string(),
+            _ => format!("profile {}", name),
+        };
+        let mut values = read_ini_section(&config, &config_section)?;
+        let from_credentials = read_ini_section(&credentials, name)?;
+        if values.is_none() && from_credentials.is_none() {
+            return Ok(None);
+        }
+        let values = values.get_or_insert_default();
+        values.extend(from_credentials.unwrap_or_default());
+
+        Ok(Some(AwsProfile {
+            access_key: values.remove("aws_access_key_id"),
+            secret_key: values.remove("aws_secret_access_key"),
+            session_token: values.remove("aws_session_token"),
+            endpoint_url: values.remove("endpoint_url"),
+            credential_process: values.remove("credential_process"),
+        }))
+    }
+}
+
+/// Servers configured as aliases of the MinIO client, from its config.json
+/// Aliases without keys (such as mc's default `local`) are left out
+pub fn read_mc_aliases(path: Option<&str>) -> Result<Vec<MinioConfig>, String> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => dirs::home_dir()
+            .ok_or("Could not find home directory")?
+            .join(".mc")
+            .join("config.json"),
+    };
+    let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let config: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+    // Version 10 files call them aliases, older ones hosts
+    let aliases = config["aliases"]
+        .as_object()
+        .or_else(|| config["hosts"].as_object())
+        .ok_or_else(|| format!("No aliases in {}", path.display()))?;
+
+    Ok(aliases
+        .iter()
+        .filter_map(|(alias, host)| {
+            let text = |field: &str| host[field].as_str().filter(|value| !value.is_empty()).map(str::to_string);
+            Some(MinioConfig {
+                endpoint_url: text("url")?,
+                access_key: text("accessKey")?,
+                secret_key: text("secretKey")?,
+                session_token: text("sessionToken"),
+                alias: Some(alias.clone()),
+                ..Default::default()
+            })
+        })
+        .collect())
+}
+
+/// Keys and values of one `[section]` of an INI file, None if the file or section doesn't exist
+fn read_ini_section(path: &Path, section: &str) -> Result<Option<BTreeMap<String, String>>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(parse_ini_section(&content, section))
+}
+
+fn parse_ini_section(content: &str, section: &str) -> Option<BTreeMap<String, String>> {
+    let mut values = None;
+    let mut current = false;
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            current = name.trim() == section;
+            if current {
+                values.get_or_insert_with(BTreeMap::new);
+            }
+        } else if current
+            && let Some((key, value)) = line.split_once('=')
+            && let Some(values) = values.as_mut()
+        {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    values
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// A key pair from two variables and an optional session token, an error if only one key is set
+fn env_keys(access: &str, secret: &str, token: &str) -> Result<Option<SessionCredentials>, String> {
+    match (env_var(access), env_var(secret)) {
+        (Some(access_key), Some(secret_key)) => Ok(Some(SessionCredentials {
+            access_key,
+            secret_key,
+            session_token: env_var(token),
+            expiration: None,
+        })),
+        (None, None) => Ok(None),
+        _ => Err(format!("Set both {} and {}", access, secret)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ini_section() {
+        let content = "\
+[default]
+aws_access_key_id = AKIDEFAULT
+
+# comment
+[minio]
+aws_access_key_id=AKIMINIO
+aws_secret_access_key = secret=with=equals
+[other]
+aws_access_key_id = AKIOTHER
+";
+        let minio = parse_ini_section(content, "minio").unwrap();
+        assert_eq!(minio["aws_access_key_id"], "AKIMINIO");
+        assert_eq!(minio["aws_secret_access_key"], "secret=with=equals");
+        assert_eq!(parse_ini_section(content, "default").unwrap().len(), 1);
+        assert!(parse_ini_section(content, "missing").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_credential_process_output() {
+        let command = r#"echo '{"Version": 1, "AccessKeyId": "AKI", "SecretAccessKey": "s", "SessionToken": "t", "Expiration": "2099-01-01T00:00:00Z"}'"#;
+        let credentials = run_credential_process(command).unwrap();
+        assert_eq!(credentials.access_key, "AKI");
+        assert_eq!(credentials.session_token.as_deref(), Some("t"));
+        assert_eq!(credentials.expiration, Some(parse_expiration("2099-01-01T00:00:00Z").unwrap()));
+
+        assert!(run_credential_process(r#"echo '{"Version": 2, "AccessKeyId": "a", "SecretAccessKey": "s"}'"#).is_err());
+        assert!(run_credential_process("exit 1").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+// Per-folder settings stored next to the structure file inside a sync folder
+use crate::utils::git_util::GitRepoMode;
+use crate::utils::path_safety::KeyNormalization;
+use crate::utils::walker::{SymlinkPolicy, WalkOptions};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Name of the settings file inside a sync folder
+pub const SETTINGS_FILE_NAME: &str = ".rusty-sync-settings.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FolderSettings {
+    pub git_mode: GitRepoMode,    // How embedded git repositories are synced
+    pub max_depth: Option<usize>, // Deepest folder level scanned, None for unlimited
+    pub one_file_system: bool,    // Don't scan into other mounted filesystems
+    pub symlinks: SymlinkPolicy,  // Ignore, store or follow symbolic links
+    pub preserve_owner: bool,     // Record uid/gid and restore them on download (needs root)
+    pub xattrs: bool,             // Capture user.* extended attributes and POSIX ACLs
+    pub key_normalization: KeyNormalization, // Unicode normalization applied to object keys
+}
+
+impl FolderSettings {
+    /// Path of the settings file for a sync folder
+    fn get_settings_path(folder_path: &str) -> String {
+        format!("{}/{}", folder_path, SETTINGS_FILE_NAME)
+    }
+
+    /// Load settings for a folder, falling back to defaults if missing or unreadable
+    pub fn load(folder_path: &str) -> Self {
+        let settings_path = Self::get_settings_path(folder_path);
+
+        match fs::read_to_string(&settings_path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("Warning: Ignoring invalid settings file {}: {}", settings_path, e);
+                FolderSettings::default()
+            }),
+            Err(_) => FolderSettings::default(),
+        }
+    }
+
+    /// Save settings into the folder
+    pub fn save(&self, folder_path: &str) -> Result<(), String> {
+        if !Path::new(folder_path).is_dir() {
+            return Err(format!("'{}' is not a directory", folder_path));
+        }
+
+        let settings_json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+        fs::write(Self::get_settings_path(folder_path), settings_json)
+            .map_err(|e| format!("Failed to write settings file: {}", e))
+    }
+
+    /// Update a single setting from its command line name and value
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "git-mode" => self.git_mode = value.parse()?,
+            "max-depth" => {
+                self.max_depth = match value {
+                    "none" => None,
+                    _ => Some(value.parse().map_err(|_| {
+                        format!("Invalid max-depth '{}'. Use a number or 'none'", value)
+                    })?),
+                }
+            }
+            "one-file-system" => self.one_file_system = parse_bool(value)?,
+            "symlinks" => self.symlinks = value.parse()?,
+            "preserve-owner" => self.preserve_owner = parse_bool(value)?,
+            "xattrs" => self.xattrs = parse_bool(value)?,
+            "key-normalization" => self.key_normalization = value.parse()?,
+            _ => {
+                return Err(format!(
+                    "Unknown setting '{}'. Available: git-mode, max-depth, one-file-system, symlinks, preserve-owner, xattrs, key-normalization",
+                    key
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// List settings as (name, value) pairs for display
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("git-mode", self.git_mode.to_string()),
+            (
+                "max-depth",
+                self.max_depth.map_or("none".to_string(), |depth| depth.to_string()),
+            ),
+            ("one-file-system", self.one_file_system.to_string()),
+            ("symlinks", self.symlinks.to_string()),
+            ("preserve-owner", self.preserve_owner.to_string()),
+            ("xattrs", self.xattrs.to_string()),
+            ("key-normalization", self.key_normalization.to_string()),
+        ]
+    }
+
+    /// Walker options for scanning this folder
+    pub fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            max_depth: self.max_depth,
+            one_file_system: self.one_file_system,
+        }
+    }
+}
+
+/// Parse a boolean setting value
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("Invalid value '{}'. Use true or false", value)),
+    }
+}
@@ -1,4 +1,8 @@
 // Configuration management module
 // This module will handle app settings, MinIO configuration, etc.
 
-pub mod config_manager;
\ No newline at end of file
+pub mod config_manager;
+pub mod credentials;
+pub mod folder_settings;
+pub mod secret_store;
+pub mod server_options;
@@ -0,0 +1,262 @@
+// secret keys of configured servers, kept out of config.json
+// they go into the OS keyring when there is one, otherwise into a passphrase-encrypted file
+
+use crate::core::encryption::{read_passphrase, EncryptionKey, EncryptionParams};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Service name secrets are filed under in the OS keyring
+const KEYRING_SERVICE: &str = "rusty-sync";
+/// File holding the secrets when the OS keyring can't be used
+const SECRETS_FILE: &str = "secrets.json";
+/// Passphrase of the secrets file, asked for when unset
+pub const SECRETS_PASSPHRASE_ENV: &str = "RUSTY_SYNC_SECRETS_PASSPHRASE";
+/// Forces a store for new secrets: "keyring" or "file"
+pub const SECRET_STORE_ENV: &str = "RUSTY_SYNC_SECRET_STORE";
+
+// Secrets already looked up by this process, so the file passphrase is only asked for once
+static RESOLVED: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+static FILE_KEY: Mutex<Option<EncryptionKey>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretStore {
+    Keyring, // Secret Service, Keychain or Credential Manager
+    File,    // ~/.rusty-sync/secrets.json, sealed with a passphrase
+}
+
+/// Where a server's secret key is kept, the only part of it that config.json holds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SecretRef {
+    pub store: SecretStore,
+    pub id: String, // Name of the entry within the store
+}
+
+impl std::fmt::Display for SecretStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretStore::Keyring => write!(f, "the OS keyring"),
+            SecretStore::File => write!(f, "the encrypted secrets file"),
+        }
+    }
+}
+
+/// The passphrase-encrypted secrets file
+#[derive(Serialize, Deserialize)]
+struct SecretsFile {
+    params: EncryptionParams,          // Derives the key the secrets are sealed with
+    secrets: BTreeMap<String, String>, // Entry id to sealed secret
+}
+
+impl SecretRef {
+    /// Store `secret` under `id`, in the keyring if it's usable and the encrypted file otherwise
+    pub fn store(id: &str, secret: &str) -> Result<SecretRef, String> {
+        let forced = std::env::var(SECRET_STORE_ENV).ok();
+        let store = match forced.as_deref() {
+            Some("file") => SecretStore::File,
+            Some("keyring") => {
+                keyring_entry(id)?
+                    .set_password(secret)
+                    .map_err(|e| format!("Failed to store secret in the OS keyring: {}", e))?;
+                SecretStore::Keyring
+            }
+            Some(other) => return Err(format!("Unknown secret store '{}' in {}, use keyring or file", other, SECRET_STORE_ENV)),
+            None => match keyring_entry(id).and_then(|entry| entry.set_password(secret).map_err(|e| e.to_string())) {
+                Ok(()) => SecretStore::Keyring,
+                Err(e) => {
+                    eprintln!("Warning: OS keyring unavailable ({}), using the encrypted secrets file", e);
+                    SecretStore::File
+                }
+            },
+        };
+
+        if store == SecretStore::File {
+            store_in_file(&secrets_path()?, id, secret)?;
+        }
+
+        RESOLVED.lock().unwrap().insert(id.to_string(), secret.to_string());
+        Ok(SecretRef { store, id: id.to_string() })
+    }
+
+    /// The secret this refers to
+    pub fn resolve(&self) -> Result<String, String> {
+        if let Some(secret) = RESOLVED.lock().unwrap().get(&self.id) {
+            return Ok(secret.clone());
+        }
+
+        let secret = match self.store {
+            SecretStore::Keyring => keyring_entry(&self.id)?
+                .get_password()
+                .map_err(|e| format!("Failed to read secret '{}' from the OS keyring: {}", self.id, e))?,
+            SecretStore::File => return resolve_from_file(&secrets_path()?, &self.id),
+        };
+
+        RESOLVED.lock().unwrap().insert(self.id.clone(), secret.clone());
+        Ok(secret)
+    }
+
+    /// Remove the secret from its store, for servers that are removed from the config
+    pub fn delete(&self) -> Result<(), String> {
+        RESOLVED.lock().unwrap().remove(&self.id);
+        match self.store {
+            SecretStore::Keyring => keyring_entry(&self.id)?
+                .delete_credential()
+                .map_err(|e| format!("Failed to remove secret from the OS keyring: {}", e)),
+            SecretStore::File => {
+                // Sealed entries can be dropped without the passphrase
+                let path = secrets_path()?;
+                let Some(mut file) = SecretsFile::load(&path)? else {
+                    return Ok(());
+                };
+                file.secrets.remove(&self.id);
+                file.save(&path)
+            }
+        }
+    }
+}
+
+impl SecretsFile {
+    fn unlock(&self, path: &Path) -> Result<EncryptionKey, String> {
+        if let Some(key) = FILE_KEY.lock().unwrap().as_ref().filter(|key| key.salt() == self.params.salt) {
+            return Ok(key.clone());
+        }
+        let passphrase = read_passphrase("Secrets passphrase: ", SECRETS_PASSPHRASE_ENV, false)?;
+        let key = self
+            .params
+            .derive(&passphrase)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        *FILE_KEY.lock().unwrap() = Some(key.clone());
+        Ok(key)
+    }
+
+    fn load(path: &Path) -> Result<Option<SecretsFile>, String> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize secrets: {}", e))?;
+        write_private(path, json.as_bytes())
+    }
+}
+
+/// Seal `secret` into the secrets file at `path` under `id`, creating the file with a new passphrase if needed
+fn store_in_file(path: &Path, id: &str, secret: &str) -> Result<(), String> {
+    let (mut file, key) = match SecretsFile::load(path)? {
+        Some(file) => {
+            let key = file.unlock(path)?;
+            (file, key)
+        }
+        None => {
+            println!("Choose a passphrase for {}", path.display());
+            let passphrase = read_passphrase("Secrets passphrase: ", SECRETS_PASSPHRASE_ENV, true)?;
+            let (params, key) = EncryptionParams::generate(&passphrase, false)?;
+            *FILE_KEY.lock().unwrap() = Some(key.clone());
+            (SecretsFile { params, secrets: BTreeMap::new() }, key)
+        }
+    };
+    file.secrets.insert(id.to_string(), key.master.seal_value(secret.as_bytes())?);
+    file.save(path)
+}
+
+/// Open the secret `id` of the secrets file at `path`
+fn resolve_from_file(path: &Path, id: &str) -> Result<String, String> {
+    let file = SecretsFile::load(path)?.ok_or_else(|| format!("Secrets file {} is missing", path.display()))?;
+    if !file.secrets.contains_key(id) {
+        return Err(format!("No secret '{}' in {}", id, path.display()));
+    }
+    let key = file.unlock(path)?;
+
+    // Every entry opens with the same passphrase, keep them all for later lookups
+    let mut resolved = RESOLVED.lock().unwrap();
+    for (id, sealed) in &file.secrets {
+        if let Some(secret) = key.master.open_value(sealed).and_then(|secret| String::from_utf8(secret).ok()) {
+            resolved.insert(id.clone(), secret);
+        }
+    }
+    resolved
+        .get(id)
+        .cloned()
+        .ok_or_else(|| format!("Secret '{}' in {} is corrupted", id, path.display()))
+}
+
+fn keyring_entry(id: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, id).map_err(|e| format!("Failed to open the OS keyring: {}", e))
+}
+
+fn secrets_path() -> Result<PathBuf, String> {
+    Ok(crate::config::config_manager::RustySyncConfig::get_config_dir()?.join(SECRETS_FILE))
+}
+
+/// Write a file only the current user can read, tightening the permissions of an existing one
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                .map_err(|e| format!("Failed to restrict permissions of {}: {}", path.display(), e))?;
+        }
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start over like a new process would, with nothing unlocked yet
+    fn forget_unlocked() {
+        RESOLVED.lock().unwrap().clear();
+        *FILE_KEY.lock().unwrap() = None;
+    }
+
+    #[test]
+    fn test_secrets_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rusty-sync-secrets-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(SECRETS_FILE);
+
+        // SAFETY: no other test reads this variable
+        unsafe { std::env::set_var(SECRETS_PASSPHRASE_ENV, "right passphrase") };
+        store_in_file(&path, "key@http://minio", "s3cret").unwrap();
+        store_in_file(&path, "key@http://minio#session", "token").unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("s3cret"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        forget_unlocked();
+        assert_eq!(resolve_from_file(&path, "key@http://minio").unwrap(), "s3cret");
+        assert_eq!(resolve_from_file(&path, "key@http://minio#session").unwrap(), "token");
+        assert!(resolve_from_file(&path, "other@http://minio").is_err());
+
+        // A wrong passphrase opens nothing
+        forget_unlocked();
+        unsafe { std::env::set_var(SECRETS_PASSPHRASE_ENV, "wrong passphrase") };
+        assert!(resolve_from_file(&path, "key@http://minio").is_err());
+        assert!(RESOLVED.lock().unwrap().is_empty());
+
+        unsafe { std::env::remove_var(SECRETS_PASSPHRASE_ENV) };
+        forget_unlocked();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
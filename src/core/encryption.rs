@@ -0,0 +1,774 @@
+// client-side encryption of object contents, the server only ever sees ciphertext
+// every object is sealed with its own data key in XChaCha20-Poly1305 STREAM mode, and the data key is wrapped
+// with the bucket's master key, which comes from a passphrase or key file through Argon2id
+// optionally object names are encrypted too, per path component and deterministically so the same path always maps to the same key
+
+use crate::config::config_manager::RustySyncConfig;
+use argon2::{Algorithm, Argon2, Params, Version};
+use blake2::digest::consts::{U24, U32};
+use blake2::digest::Mac;
+use blake2::Blake2bMac;
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::XChaCha20Poly1305;
+use data_encoding::BASE32_DNSSEC;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Object holding the key derivation parameters, stored unencrypted so other machines can derive the key
+pub const ENCRYPTION_PARAMS_KEY: &str = ".rusty-sync/encryption.json";
+
+/// Parameters of the master key a rekey is switching to, present until the rekey finishes
+pub const ENCRYPTION_NEXT_KEY: &str = ".rusty-sync/encryption-next.json";
+
+/// Object metadata describing how an object was encrypted
+pub const ENCRYPTION_METADATA: &str = "rusty-sync-encryption"; // Format version
+pub const SALT_METADATA: &str = "rusty-sync-encryption-salt"; // Identifies the key it was sealed with
+pub const NONCE_METADATA: &str = "rusty-sync-encryption-nonce";
+pub const DATA_KEY_METADATA: &str = "rusty-sync-encryption-key"; // Data key wrapped with the master key
+
+/// Passphrase source for scripts and the daemon, which can't be prompted
+pub const PASSPHRASE_ENV: &str = "RUSTY_SYNC_PASSPHRASE";
+
+const PARAMS_VERSION: u32 = 1;
+// 1 sealed contents with the master key directly, 2 with a wrapped data key,
+// 3 also binds the contents and the wrapped key to the object's path so objects can't be swapped
+const FORMAT_VERSION: u32 = 3;
+const KEY_SIZE: usize = 32;
+const SALT_SIZE: usize = 16;
+const NONCE_PREFIX_SIZE: usize = 19; // 24 byte XChaCha nonce minus the 5 bytes STREAM uses for the chunk counter
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+const KEY_CHECK: &[u8] = b"rusty-sync key check";
+const NAME_SEGMENT_SIZE: usize = 200; // Longest piece of an encrypted name, file names are usually limited to 255 bytes
+const NAME_CONTINUED: char = '-'; // Ends a piece continued in the next one, not part of the base32 alphabet
+
+/// Everything needed to derive a bucket's key from its passphrase, none of it secret
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncryptionParams {
+    pub version: u32,
+    pub salt: String, // Hex
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub check: String, // Hex nonce and sealed KEY_CHECK, tells a wrong passphrase from a corrupt object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub names: Option<String>, // Hex nonce and sealed name key, when object names are encrypted
+}
+
+impl EncryptionParams {
+    /// Fresh parameters with a new salt, and the key they give for `secret`
+    /// With `encrypt_names` a random name key is created too, stored sealed with the derived key
+    pub fn generate(secret: &[u8], encrypt_names: bool) -> Result<(Self, EncryptionKey), String> {
+        let names = encrypt_names.then(|| {
+            let mut names = [0u8; KEY_SIZE];
+            OsRng.fill_bytes(&mut names);
+            NameKey(names)
+        });
+        Self::create(secret, names)
+    }
+
+    /// Parameters for a new passphrase of a bucket that uses `current`, keeping its name key
+    pub fn rotate(secret: &[u8], current: &EncryptionKey) -> Result<(Self, EncryptionKey), String> {
+        Self::create(secret, current.names.clone())
+    }
+
+    fn create(secret: &[u8], names: Option<NameKey>) -> Result<(Self, EncryptionKey), String> {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+
+        let mut params = EncryptionParams {
+            version: PARAMS_VERSION,
+            salt: to_hex(&salt),
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+            check: String::new(),
+            names: None,
+        };
+        let mut key = params.derive_unchecked(secret)?;
+        params.check = key.master.seal_value(KEY_CHECK)?;
+
+        if let Some(names) = names {
+            params.names = Some(key.master.seal_value(&names.0)?);
+            key.names = Some(names);
+        }
+
+        Ok((params, key))
+    }
+
+    /// Derive the key for `secret`, failing if it isn't the one the parameters were made with
+    pub fn derive(&self, secret: &[u8]) -> Result<EncryptionKey, String> {
+        if self.version != PARAMS_VERSION {
+            return Err(format!("Unsupported encryption parameters version {}", self.version));
+        }
+
+        let mut key = self.derive_unchecked(secret)?;
+        if key.master.open_value(&self.check).as_deref() != Some(KEY_CHECK) {
+            return Err("Wrong passphrase or key file".to_string());
+        }
+
+        if let Some(names) = &self.names {
+            let names = key
+                .master
+                .open_value(names)
+                .and_then(|names| <[u8; KEY_SIZE]>::try_from(names).ok())
+                .ok_or("Invalid name key in encryption parameters")?;
+            key.names = Some(NameKey(names));
+        }
+        Ok(key)
+    }
+
+    fn derive_unchecked(&self, secret: &[u8]) -> Result<EncryptionKey, String> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(KEY_SIZE))
+            .map_err(|e| format!("Invalid key derivation parameters: {}", e))?;
+        let mut key = [0u8; KEY_SIZE];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(secret, &from_hex(&self.salt)?, &mut key)
+            .map_err(|e| format!("Key derivation failed: {}", e))?;
+        Ok(EncryptionKey {
+            master: MasterKey {
+                key,
+                salt: self.salt.clone(),
+            },
+            names: None,
+            retired: Vec::new(),
+        })
+    }
+}
+
+/// Key for object names, separate from the content key so changing the passphrase doesn't rename objects
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NameKey(#[serde(with = "hex_bytes")] [u8; KEY_SIZE]);
+
+impl NameKey {
+    /// Keyed hash of a path, used as its nonce (the SIV construction: equal paths, equal object keys)
+    fn synthetic_nonce(&self, path: &[u8]) -> [u8; 24] {
+        let mut mac = Blake2bMac::<U24>::new_with_salt_and_personal(&self.0, &[], b"rusty-sync-siv")
+            .expect("key and personalization fit BLAKE2b");
+        mac.update(path);
+        mac.finalize().into_bytes().into()
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        let mac = Blake2bMac::<U32>::new_with_salt_and_personal(&self.0, &[], b"rusty-sync-name")
+            .expect("key and personalization fit BLAKE2b");
+        XChaCha20Poly1305::new(&mac.finalize().into_bytes())
+    }
+
+    /// Opaque object key for a relative path, encrypted component by component
+    /// Each component's nonce covers the path up to it, so equal names in different folders don't match.
+    /// Components too long for one file name are split into pieces ending in `NAME_CONTINUED`
+    fn encrypt(&self, path: &str) -> String {
+        let mut prefix = String::new();
+        let mut segments = Vec::new();
+        for component in path.split('/') {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(component);
+
+            let nonce = self.synthetic_nonce(prefix.as_bytes());
+            let sealed = self
+                .cipher()
+                .encrypt(GenericArray::from_slice(&nonce), component.as_bytes())
+                .expect("encrypting into memory can't fail");
+            let encoded = BASE32_DNSSEC.encode(&[nonce.as_slice(), &sealed].concat());
+            let pieces: Vec<&str> = encoded
+                .as_bytes()
+                .chunks(NAME_SEGMENT_SIZE)
+                .map(|piece| std::str::from_utf8(piece).expect("base32 is ASCII"))
+                .collect();
+            segments.push(pieces.join(&format!("{}/", NAME_CONTINUED)));
+        }
+        segments.join("/")
+    }
+
+    /// Relative path behind an object key, None for keys this name key didn't produce
+    fn decrypt(&self, object_name: &str) -> Option<String> {
+        let mut path = String::new();
+        let mut encoded = String::new();
+        for segment in object_name.split('/') {
+            if let Some(piece) = segment.strip_suffix(NAME_CONTINUED) {
+                encoded.push_str(piece);
+                continue;
+            }
+            encoded.push_str(segment);
+
+            let bytes = BASE32_DNSSEC.decode(encoded.as_bytes()).ok()?;
+            encoded.clear();
+            if bytes.len() < 24 {
+                return None;
+            }
+            let (nonce, sealed) = bytes.split_at(24);
+            let component = self.cipher().decrypt(GenericArray::from_slice(nonce), sealed).ok()?;
+            let component = String::from_utf8(component).ok().filter(|c| !c.contains('/'))?;
+
+            if !path.is_empty() {
+                path.push('/');
+            }
+            path.push_str(&component);
+            if self.synthetic_nonce(path.as_bytes()).as_slice() != nonce {
+                return None;
+            }
+        }
+        encoded.is_empty().then_some(path)
+    }
+}
+
+/// One generation of a bucket's master key, derived from one passphrase
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MasterKey {
+    #[serde(with = "hex_bytes")]
+    key: [u8; KEY_SIZE],
+    pub salt: String, // Salt of the parameters it was derived with, names the generation
+}
+
+impl MasterKey {
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(GenericArray::from_slice(&self.key))
+    }
+
+    /// Seal a small value (a key or key check), as hex nonce and ciphertext
+    pub fn seal_value(&self, value: &[u8]) -> Result<String, String> {
+        self.seal_bound(value, &[])
+    }
+
+    /// Open a value sealed by `seal_value`, None if it wasn't sealed with this key
+    pub fn open_value(&self, sealed: &str) -> Option<Vec<u8>> {
+        self.open_bound(sealed, &[])
+    }
+
+    /// Like `seal_value`, but the value only opens again for the same `aad`
+    fn seal_bound(&self, value: &[u8], aad: &[u8]) -> Result<String, String> {
+        let mut nonce = [0u8; 24];
+        OsRng.fill_bytes(&mut nonce);
+        let sealed = self
+            .cipher()
+            .encrypt(GenericArray::from_slice(&nonce), Payload { msg: value, aad })
+            .map_err(|_| "Encryption failed".to_string())?;
+        Ok(to_hex(&[nonce.as_slice(), &sealed].concat()))
+    }
+
+    fn open_bound(&self, sealed: &str, aad: &[u8]) -> Option<Vec<u8>> {
+        let bytes = from_hex(sealed).ok()?;
+        if bytes.len() < 24 {
+            return None;
+        }
+        let (nonce, sealed) = bytes.split_at(24);
+        self.cipher().decrypt(GenericArray::from_slice(nonce), Payload { msg: sealed, aad }).ok()
+    }
+}
+
+/// A bucket's keys, cached per bucket under ~/.rusty-sync/keys
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EncryptionKey {
+    #[serde(flatten)]
+    pub master: MasterKey, // Wraps the data keys of everything uploaded from now on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub names: Option<NameKey>, // Set when the bucket's object names are encrypted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retired: Vec<MasterKey>, // Earlier generations, kept while a rekey converts the objects they wrap
+}
+
+impl EncryptionKey {
+    /// Salt of the current master key
+    pub fn salt(&self) -> &str {
+        &self.master.salt
+    }
+
+    /// Keep an earlier master key around for reading until a rekey has converted everything
+    pub fn retire(&mut self, previous: &EncryptionKey) {
+        self.retired.push(previous.master.clone());
+        self.retired.extend(previous.retired.iter().cloned());
+    }
+
+    /// Master key of the generation an object was sealed with
+    fn master_for(&self, metadata: &BTreeMap<String, String>) -> Result<&MasterKey, String> {
+        let salt = metadata.get(SALT_METADATA).ok_or("Object has no encryption salt")?;
+        std::iter::once(&self.master)
+            .chain(&self.retired)
+            .find(|master| master.salt == *salt)
+            .ok_or_else(|| "Object was encrypted with a different key, unlock the bucket again".to_string())
+    }
+
+    /// Cipher for the contents of the object stored for `path`, and the associated data its chunks carry
+    /// That's its unwrapped data key, or the master key for format 1. Only format 3 is bound to the path
+    fn content_cipher<'a>(
+        &self,
+        metadata: &BTreeMap<String, String>,
+        path: &'a str,
+    ) -> Result<(XChaCha20Poly1305, &'a [u8]), String> {
+        let master = self.master_for(metadata)?;
+        let aad = bound_path(metadata, path);
+        match metadata.get(ENCRYPTION_METADATA).map(String::as_str) {
+            Some("1") => Ok((master.cipher(), aad)),
+            Some("2") | Some("3") => {
+                let data_key = metadata
+                    .get(DATA_KEY_METADATA)
+                    .and_then(|wrapped| master.open_bound(wrapped, aad))
+                    .filter(|data_key| data_key.len() == KEY_SIZE)
+                    .ok_or("Object's data key can't be unwrapped, or it belongs to another path")?;
+                Ok((XChaCha20Poly1305::new(GenericArray::from_slice(&data_key)), aad))
+            }
+            Some(version) => Err(format!("Unsupported encryption format version {}", version)),
+            None => Err("Object isn't encrypted".to_string()),
+        }
+    }
+
+    /// Metadata with the data key of the object at `path` wrapped by the current master key instead
+    /// None when there is no data key to re-wrap (format 1 or unencrypted), the contents must be sealed again
+    pub fn rewrap(&self, metadata: &BTreeMap<String, String>, path: &str) -> Result<Option<BTreeMap<String, String>>, String> {
+        if !matches!(metadata.get(ENCRYPTION_METADATA).map(String::as_str), Some("2") | Some("3")) {
+            return Ok(None);
+        }
+        let aad = bound_path(metadata, path);
+        let data_key = metadata
+            .get(DATA_KEY_METADATA)
+            .and_then(|wrapped| self.master_for(metadata).ok()?.open_bound(wrapped, aad))
+            .ok_or("Object's data key can't be unwrapped")?;
+
+        let mut rewrapped = metadata.clone();
+        rewrapped.insert(SALT_METADATA.to_string(), self.master.salt.clone());
+        rewrapped.insert(DATA_KEY_METADATA.to_string(), self.master.seal_bound(&data_key, aad)?);
+        Ok(Some(rewrapped))
+    }
+
+    /// Check that the object at `path` is sealed for the current master key and its data key opens
+    pub fn check_object(&self, metadata: &BTreeMap<String, String>, path: &str) -> Result<(), String> {
+        if metadata.contains_key(ENCRYPTION_METADATA) && metadata.get(SALT_METADATA) != Some(&self.master.salt) {
+            return Err("Still encrypted with an earlier key".to_string());
+        }
+        self.content_cipher(metadata, path).map(|_| ())
+    }
+
+    /// Object key a relative path is stored under
+    pub fn object_name(&self, path: &str) -> String {
+        match &self.names {
+            Some(names) => names.encrypt(path),
+            None => path.to_string(),
+        }
+    }
+
+    /// Relative path stored under an object key, None if it isn't an encrypted name of this bucket
+    pub fn path_of(&self, object_name: &str) -> Option<String> {
+        match &self.names {
+            Some(names) => names.decrypt(object_name),
+            None => Some(object_name.to_string()),
+        }
+    }
+
+    fn store_path(bucket: &str) -> Result<PathBuf, String> {
+        Ok(RustySyncConfig::get_config_dir()?.join("keys").join(format!("{}.json", bucket)))
+    }
+
+    /// Key saved on this machine for a bucket, None if the bucket was never unlocked here
+    pub fn load(bucket: &str) -> Result<Option<Self>, String> {
+        let path = Self::store_path(bucket)?;
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map(Some)
+                .map_err(|e| format!("Invalid key file {}: {}", path.display(), e)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Remember the key for a bucket, readable only by the current user
+    pub fn save(&self, bucket: &str) -> Result<(), String> {
+        let path = Self::store_path(bucket)?;
+        let dir = path.parent().ok_or("Invalid key store path")?;
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let json = serde_json::to_string(self).map_err(|e| format!("Failed to serialize key: {}", e))?;
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(&path)
+            .and_then(|mut file| file.write_all(json.as_bytes()))
+            .map_err(|e| format!("Failed to save key to {}: {}", path.display(), e))
+    }
+
+    /// Encrypt a file to be stored for `path` into a temporary file, returns it with the metadata to store on the object
+    /// The path is authenticated with every chunk and the wrapped data key, the object only opens under it
+    pub fn seal_file(&self, source: &Path, path: &str) -> Result<(TempFile, BTreeMap<String, String>), String> {
+        let aad = path.as_bytes();
+        let mut data_key = [0u8; KEY_SIZE];
+        OsRng.fill_bytes(&mut data_key);
+        let mut prefix = [0u8; NONCE_PREFIX_SIZE];
+        OsRng.fill_bytes(&mut prefix);
+        let cipher = XChaCha20Poly1305::new(GenericArray::from_slice(&data_key));
+        let mut encryptor = EncryptorBE32::from_aead(cipher, GenericArray::from_slice(&prefix));
+
+        let sealed = TempFile::new()?;
+        let mut reader = BufReader::new(
+            File::open(source).map_err(|e| format!("Failed to open {}: {}", source.display(), e))?,
+        );
+        let mut writer = BufWriter::new(
+            fs::OpenOptions::new()
+                .write(true)
+                .open(&sealed.path)
+                .map_err(|e| format!("Failed to open temporary file: {}", e))?,
+        );
+
+        let mut chunk = read_chunk(&mut reader, CHUNK_SIZE)?;
+        loop {
+            // The last chunk is sealed differently, so look one chunk ahead
+            let next = read_chunk(&mut reader, CHUNK_SIZE)?;
+            if next.is_empty() {
+                let sealed_chunk = encryptor
+                    .encrypt_last(Payload { msg: &chunk, aad })
+                    .map_err(|_| "Encryption failed")?;
+                writer.write_all(&sealed_chunk).map_err(|e| format!("Failed to write encrypted file: {}", e))?;
+                break;
+            }
+            let sealed_chunk = encryptor
+                .encrypt_next(Payload { msg: &chunk, aad })
+                .map_err(|_| "Encryption failed")?;
+            writer.write_all(&sealed_chunk).map_err(|e| format!("Failed to write encrypted file: {}", e))?;
+            chunk = next;
+        }
+        writer.flush().map_err(|e| format!("Failed to write encrypted file: {}", e))?;
+
+        let metadata = BTreeMap::from([
+            (ENCRYPTION_METADATA.to_string(), FORMAT_VERSION.to_string()),
+            (SALT_METADATA.to_string(), self.master.salt.clone()),
+            (NONCE_METADATA.to_string(), to_hex(&prefix)),
+            (DATA_KEY_METADATA.to_string(), self.master.seal_bound(&data_key, aad)?),
+        ]);
+        Ok((sealed, metadata))
+    }
+
+    /// Decrypt the object downloaded for `path` into `target`, which is only replaced if the whole object checks out
+    pub fn open_file(
+        &self,
+        metadata: &BTreeMap<String, String>,
+        path: &str,
+        source: &Path,
+        target: &Path,
+    ) -> Result<(), String> {
+        let (cipher, aad) = self.content_cipher(metadata, path)?;
+        let prefix = from_hex(metadata.get(NONCE_METADATA).map_or("", String::as_str))?;
+        if prefix.len() != NONCE_PREFIX_SIZE {
+            return Err("Invalid nonce in object metadata".to_string());
+        }
+        let mut decryptor = DecryptorBE32::from_aead(cipher, GenericArray::from_slice(&prefix));
+
+        let partial = partial_path(target);
+        let mut reader = BufReader::new(
+            File::open(source).map_err(|e| format!("Failed to open downloaded file: {}", e))?,
+        );
+        let _ = fs::remove_file(&partial); // Left over from an interrupted download
+        let mut writer = BufWriter::new(
+            create_private(&partial).map_err(|e| format!("Failed to create {}: {}", partial.display(), e))?,
+        );
+        let corrupt = |_| {
+            let _ = fs::remove_file(&partial);
+            "Decryption failed, the object is corrupt, was tampered with or belongs to another path".to_string()
+        };
+
+        let mut chunk = read_chunk(&mut reader, CHUNK_SIZE + TAG_SIZE)?;
+        loop {
+            let next = read_chunk(&mut reader, CHUNK_SIZE + TAG_SIZE)?;
+            if next.is_empty() {
+                let plain = decryptor.decrypt_last(Payload { msg: &chunk, aad }).map_err(corrupt)?;
+                writer.write_all(&plain).map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+                break;
+            }
+            let plain = decryptor.decrypt_next(Payload { msg: &chunk, aad }).map_err(corrupt)?;
+            writer.write_all(&plain).map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+            chunk = next;
+        }
+        writer.flush().map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+        drop(writer);
+
+        fs::rename(&partial, target).map_err(|e| format!("Failed to move decrypted file into place: {}", e))
+    }
+}
+
+/// Associated data an object's contents and data key are sealed with, empty before format 3
+fn bound_path<'a>(metadata: &BTreeMap<String, String>, path: &'a str) -> &'a [u8] {
+    match metadata.get(ENCRYPTION_METADATA).map(String::as_str) {
+        Some("1") | Some("2") => &[],
+        _ => path.as_bytes(),
+    }
+}
+
+/// Objects that stay readable in encrypted buckets, they hold what's needed to derive the keys
+pub fn stored_in_plaintext(object_name: &str) -> bool {
+    object_name == ENCRYPTION_PARAMS_KEY || object_name == ENCRYPTION_NEXT_KEY
+}
+
+/// Passphrase or key file contents, from `key_file`, the environment or a prompt
+pub fn read_secret(key_file: Option<&str>, confirm: bool) -> Result<Vec<u8>, String> {
+    if let Some(key_file) = key_file {
+        let secret = fs::read(key_file).map_err(|e| format!("Failed to read key file {}: {}", key_file, e))?;
+        if secret.is_empty() {
+            return Err(format!("Key file {} is empty", key_file));
+        }
+        return Ok(secret);
+    }
+    read_passphrase("Passphrase: ", PASSPHRASE_ENV, confirm)
+}
+
+/// Passphrase from `env_var`, or asked for on the terminal with `prompt`
+pub fn read_passphrase(prompt: &str, env_var: &str, confirm: bool) -> Result<Vec<u8>, String> {
+    if let Ok(passphrase) = std::env::var(env_var) {
+        return Ok(passphrase.into_bytes());
+    }
+
+    let prompt_failed = |e: std::io::Error| {
+        format!("Failed to read passphrase ({}). Without a terminal, set {}", e, env_var)
+    };
+    let passphrase = rpassword::prompt_password(prompt).map_err(prompt_failed)?;
+    if passphrase.is_empty() {
+        return Err("Passphrase must not be empty".to_string());
+    }
+    if confirm {
+        let again = rpassword::prompt_password(format!("Repeat {}", prompt.to_lowercase())).map_err(prompt_failed)?;
+        if again != passphrase {
+            return Err("Passphrases don't match".to_string());
+        }
+    }
+    Ok(passphrase.into_bytes())
+}
+
+/// A file in our private temp directory that is removed when dropped
+pub struct TempFile {
+    pub path: PathBuf,
+}
+
+impl TempFile {
+    /// Create an empty file with a random name under ~/.rusty-sync/tmp, readable only by us
+    pub fn new() -> Result<Self, String> {
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        let dir = RustySyncConfig::get_config_dir()?.join("tmp");
+        builder
+            .create(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let mut random = [0u8; 16];
+        OsRng.fill_bytes(&mut random);
+        let path = dir.join(format!("{}.tmp", to_hex(&random)));
+        create_private(&path).map_err(|e| format!("Failed to create temporary file: {}", e))?;
+        Ok(TempFile { path })
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Create a new file only we can read, failing rather than opening a file or link someone put there
+fn create_private(path: &Path) -> std::io::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Sibling of `target` the decrypted contents are written to before replacing it
+fn partial_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".rusty-sync-partial");
+    target.with_file_name(name)
+}
+
+/// Read up to `size` bytes, fewer only at the end of the file
+fn read_chunk(reader: &mut impl Read, size: usize) -> Result<Vec<u8>, String> {
+    let mut chunk = Vec::with_capacity(size);
+    reader
+        .take(size as u64)
+        .read_to_end(&mut chunk)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(chunk)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) {
+        return Err(format!("Invalid hex value '{}'", text));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| format!("Invalid hex value '{}'", text)))
+        .collect()
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8; super::KEY_SIZE], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::to_hex(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; super::KEY_SIZE], D::Error> {
+        let bytes = super::from_hex(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)?;
+        bytes
+            .try_into()
+            .map_err(|_| serde::de::Error::custom("key has the wrong length"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open_round_trip() {
+        let (params, key) = EncryptionParams::generate(b"correct horse", false).unwrap();
+        assert!(params.derive(b"wrong horse").is_err());
+        let key_again = params.derive(b"correct horse").unwrap();
+
+        let dir = std::env::temp_dir().join(format!("rusty-sync-encryption-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Empty, single chunk, and exactly/just over a chunk boundary
+        for size in [0, 100, CHUNK_SIZE, 2 * CHUNK_SIZE + 1] {
+            let source = dir.join("plain");
+            let contents: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            fs::write(&source, &contents).unwrap();
+
+            let (sealed, metadata) = key.seal_file(&source, "docs/plain").unwrap();
+            assert_ne!(fs::read(&sealed.path).unwrap(), contents);
+            let target = dir.join("opened");
+            key_again.open_file(&metadata, "docs/plain", &sealed.path, &target).unwrap();
+            assert_eq!(fs::read(&target).unwrap(), contents);
+
+            // Flipping a byte or cutting off the end is noticed
+            let mut tampered = fs::read(&sealed.path).unwrap();
+            tampered[0] ^= 1;
+            fs::write(&sealed.path, &tampered).unwrap();
+            assert!(key.open_file(&metadata, "docs/plain", &sealed.path, &target).is_err());
+            if size > CHUNK_SIZE {
+                tampered[0] ^= 1;
+                fs::write(&sealed.path, &tampered[..CHUNK_SIZE + TAG_SIZE]).unwrap();
+                assert!(key.open_file(&metadata, "docs/plain", &sealed.path, &target).is_err());
+            }
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_objects_only_open_under_their_path() {
+        let dir = std::env::temp_dir().join(format!("rusty-sync-bound-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (_, key) = EncryptionParams::generate(b"passphrase", false).unwrap();
+        fs::write(dir.join("a"), b"salary.csv contents").unwrap();
+        fs::write(dir.join("b"), b"notes.txt contents").unwrap();
+        let (sealed_a, metadata_a) = key.seal_file(&dir.join("a"), "hr/salary.csv").unwrap();
+        let (sealed_b, metadata_b) = key.seal_file(&dir.join("b"), "notes.txt").unwrap();
+
+        key.open_file(&metadata_a, "hr/salary.csv", &sealed_a.path, &dir.join("opened")).unwrap();
+        key.check_object(&metadata_a, "hr/salary.csv").unwrap();
+
+        // An object's body and metadata moved to another key don't open there
+        assert!(key.check_object(&metadata_a, "notes.txt").is_err());
+        assert!(key.open_file(&metadata_a, "notes.txt", &sealed_a.path, &dir.join("swapped")).is_err());
+        assert!(key.rewrap(&metadata_a, "notes.txt").is_err());
+        // Nor does a body with another object's metadata
+        assert!(key.open_file(&metadata_b, "notes.txt", &sealed_a.path, &dir.join("swapped")).is_err());
+        assert!(!dir.join("swapped").exists());
+
+        key.open_file(&metadata_b, "notes.txt", &sealed_b.path, &dir.join("opened")).unwrap();
+        assert_eq!(fs::read(dir.join("opened")).unwrap(), b"notes.txt contents");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rewrap_for_new_master_key() {
+        let dir = std::env::temp_dir().join(format!("rusty-sync-rewrap-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("plain");
+        fs::write(&source, b"quarterly numbers").unwrap();
+
+        let (_, old) = EncryptionParams::generate(b"old passphrase", true).unwrap();
+        let (sealed, metadata) = old.seal_file(&source, "docs/plain").unwrap();
+
+        let (params, mut new) = EncryptionParams::rotate(b"new passphrase", &old).unwrap();
+        assert_eq!(new.object_name("a.txt"), old.object_name("a.txt"));
+        assert!(new.check_object(&metadata, "docs/plain").is_err());
+
+        // Mid-rekey: the retired key still opens what hasn't been converted
+        new.retire(&old);
+        let rewrapped = new.rewrap(&metadata, "docs/plain").unwrap().unwrap();
+        new.open_file(&metadata, "docs/plain", &sealed.path, &dir.join("before")).unwrap();
+        new.check_object(&rewrapped, "docs/plain").unwrap();
+
+        // Afterwards only the new passphrase opens the same bytes
+        let finished = params.derive(b"new passphrase").unwrap();
+        finished.open_file(&rewrapped, "docs/plain", &sealed.path, &dir.join("after")).unwrap();
+        assert_eq!(fs::read(dir.join("after")).unwrap(), b"quarterly numbers");
+        assert!(old.open_file(&rewrapped, "docs/plain", &sealed.path, &dir.join("old")).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_temp_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let a = TempFile::new().unwrap();
+        let b = TempFile::new().unwrap();
+        assert_ne!(a.path, b.path);
+        assert_eq!(fs::metadata(&a.path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::metadata(a.path.parent().unwrap()).unwrap().permissions().mode() & 0o777, 0o700);
+
+        // A link planted where a file is about to be created is refused, not followed
+        let planted = a.path.with_extension("planted");
+        std::os::unix::fs::symlink(&b.path, &planted).unwrap();
+        assert!(create_private(&planted).is_err());
+        let _ = fs::remove_file(&planted);
+    }
+
+    #[test]
+    fn test_encrypted_object_names() {
+        let (params, key) = EncryptionParams::generate(b"correct horse", true).unwrap();
+        let key_again = params.derive(b"correct horse").unwrap();
+
+        let name = key.object_name("reviews/salary-review-2026.xlsx");
+        assert!(!name.contains("salary") && !name.contains("reviews"));
+        assert_eq!(key_again.object_name("reviews/salary-review-2026.xlsx"), name);
+        assert_eq!(key_again.path_of(&name).as_deref(), Some("reviews/salary-review-2026.xlsx"));
+        assert_ne!(key.object_name("reviews/other.xlsx"), name);
+
+        // Every piece fits in a file name, however long the path and its components get
+        let long = format!("{}/{}", "deeply-nested-folder/".repeat(10), "n".repeat(255));
+        let name = key.object_name(&long);
+        assert!(name.split('/').all(|piece| piece.len() <= 255));
+        assert_eq!(key.path_of(&name), Some(long));
+
+        // The same file name in another folder doesn't give itself away
+        let a = key.object_name("a/notes.txt");
+        let b = key.object_name("b/notes.txt");
+        assert_ne!(a.rsplit('/').next(), b.rsplit('/').next());
+
+        // Plain names and names from another bucket aren't ours
+        assert_eq!(key.path_of("notes.txt"), None);
+        let (_, other) = EncryptionParams::generate(b"correct horse", true).unwrap();
+        assert_eq!(other.path_of(&name), None);
+    }
+}
@@ -0,0 +1,473 @@
+"""In-memory S3 server for local testing, covering what the rusty-sync bridge uses.
+
+Buckets, objects (with user metadata), multipart uploads, ListObjectsV2, copy_object and
+MinIO's ListenBucketNotification. Requests are not authenticated and nothing touches disk.
+
+    python3 fake_server.py [--port PORT] [--expired-token TOKEN] [--tls-cert PEM --tls-key PEM [--client-ca PEM]] [--hang]
+                           [--domain DOMAIN] [--addressing path|virtual] [--signature v2|v4] [--region REGION]
+                           [--no-checksums]
+
+Requests signed with an --expired-token session token are refused with ExpiredToken.
+With --tls-cert it serves HTTPS, and with --client-ca only to clients presenting a certificate that CA signed.
+With --hang it accepts connections but never answers, like a server that has locked up.
+Requests for absolute URLs are served as if sent to the server directly, so it can stand in for an HTTP proxy.
+Over plain HTTP it takes SOCKS5 connections on the same port and serves what they tunnel the same way,
+standing in for a SOCKS5 proxy too.
+
+The remaining options make it behave like S3 servers other than MinIO: with --domain, BUCKET.DOMAIN hosts
+address buckets virtually, and --addressing, --signature and --region refuse requests made differently.
+--no-checksums refuses the flexible checksum headers of newer SDKs, which some servers don't support.
+
+Prints "Fake S3 server listening on http(s)://127.0.0.1:PORT" once it accepts requests.
+"""
+
+import argparse
+import hashlib
+import json
+import queue
+import ssl
+import threading
+import time
+import urllib.parse
+import uuid
+from email.utils import formatdate
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+from xml.sax.saxutils import escape
+
+
+class Store:
+    def __init__(self):
+        self.lock = threading.Lock()
+        self.buckets = {}   # bucket -> {key: object}
+        self.uploads = {}   # upload id -> (bucket, key, metadata, {part number: bytes})
+        self.listeners = [] # (bucket, queue) of open ListenBucketNotification requests
+
+    def put(self, bucket, key, data, metadata):
+        obj = {
+            "data": data,
+            "metadata": metadata,
+            "etag": '"%s"' % hashlib.md5(data).hexdigest(),
+            "modified": time.time(),
+        }
+        with self.lock:
+            self.buckets[bucket][key] = obj
+        self.notify(bucket, "s3:ObjectCreated:Put", key, obj)
+        return obj
+
+    def delete(self, bucket, key):
+        with self.lock:
+            obj = self.buckets[bucket].pop(key, None)
+        if obj is not None:
+            self.notify(bucket, "s3:ObjectRemoved:Delete", key, obj)
+
+    def notify(self, bucket, event, key, obj):
+        record = {
+            "eventName": event,
+            "s3": {
+                "bucket": {"name": bucket},
+                "object": {"key": urllib.parse.quote(key), "size": len(obj["data"]), "eTag": obj["etag"].strip('"')},
+            },
+        }
+        with self.lock:
+            listeners = [q for b, q in self.listeners if b == bucket]
+        for q in listeners:
+            q.put(record)
+
+
+STORE = Store()
+
+
+EXPIRED_TOKENS = set()
+HANG = threading.Event()
+COMPAT = argparse.Namespace(domain=None, addressing=None, signature=None, region=None, no_checksums=False)
+
+
+class Handler(BaseHTTPRequestHandler):
+    protocol_version = "HTTP/1.1"
+
+    def log_message(self, format, *args):
+        pass  # Keep test output readable
+
+    def handle(self):
+        # A SOCKS5 greeting instead of a request line, whatever it tunnels afterwards is served directly
+        if self.rfile.peek(1)[:1] == b"\x05" and not self.socks_handshake():
+            return
+        super().handle()
+
+    def socks_handshake(self):
+        # No authentication, or any username and password, and then CONNECT to any address
+        _, count = self.rfile.read(2)
+        method = 0x02 if 0x02 in self.rfile.read(count) else 0x00
+        self.wfile.write(bytes([0x05, method]))
+        if method == 0x02:
+            _, length = self.rfile.read(2)
+            self.rfile.read(length)
+            self.rfile.read(self.rfile.read(1)[0])
+            self.wfile.write(b"\x01\x00")
+
+        _, command, _, kind = self.rfile.read(4)
+        length = {0x01: 4, 0x04: 16}.get(kind) or self.rfile.read(1)[0]
+        self.rfile.read(length + 2)  # Address and port, every target is this server
+        succeeded = command == 0x01
+        self.wfile.write(bytes([0x05, 0x00 if succeeded else 0x07, 0x00, 0x01]) + bytes(6))
+        return succeeded
+
+    def parse_request(self):
+        if not super().parse_request():
+            return False
+        if HANG.is_set():
+            threading.Event().wait()
+        if self.headers.get("X-Amz-Security-Token") in EXPIRED_TOKENS:
+            self.close_connection = True  # The body is left unread
+            self.error(400, "ExpiredToken", "The provided token has expired.")
+            return False
+        refusal = self.compatibility_refusal()
+        if refusal:
+            self.close_connection = True
+            self.error(400, *refusal)
+            return False
+        return True
+
+    def compatibility_refusal(self):
+        # Error code and message for requests the emulated server wouldn't take, None if it takes them
+        virtual = self.virtual_bucket() is not None
+        if COMPAT.addressing == "path" and virtual:
+            return "InvalidRequest", "Virtual-host style requests are not supported"
+        if COMPAT.addressing == "virtual" and not virtual and urllib.parse.urlsplit(self.path).path != "/":
+            return "InvalidRequest", "Path style requests are not supported"
+
+        # Unsigned requests, such as reachability checks, pass
+        authorization = self.headers.get("Authorization")
+        if authorization:
+            signature = "v4" if authorization.startswith("AWS4-HMAC-SHA256") else "v2"
+            if COMPAT.signature and signature != COMPAT.signature:
+                return "InvalidRequest", "Signature %s is not supported, use %s" % (signature, COMPAT.signature)
+            scope = authorization.split("Credential=", 1)[-1].split(",", 1)[0].split("/")
+            region = scope[2] if len(scope) > 2 else ""
+            if COMPAT.region and signature == "v4" and region != COMPAT.region:
+                return "AuthorizationHeaderMalformed", "The region '%s' is wrong; expecting '%s'" % (region, COMPAT.region)
+
+        if COMPAT.no_checksums:
+            for name in self.headers.keys():
+                name = name.lower()
+                if name.startswith("x-amz-checksum-") or name in ("x-amz-sdk-checksum-algorithm", "x-amz-trailer"):
+                    return "NotImplemented", "Header %s is not supported" % name
+        return None
+
+    def virtual_bucket(self):
+        # Bucket named by the host of a virtual-host style request
+        if not COMPAT.domain:
+            return None
+        host = urllib.parse.urlsplit(self.path).netloc or self.headers.get("Host", "")
+        host = host.rsplit(":", 1)[0]
+        if host.endswith("." + COMPAT.domain):
+            return host[: -len(COMPAT.domain) - 1]
+        return None
+
+    # Request parsing
+
+    def parse(self):
+        url = urllib.parse.urlsplit(self.path)
+        virtual = self.virtual_bucket()
+        if virtual is not None:
+            key = urllib.parse.unquote(url.path.lstrip("/")) or None
+            return virtual, key, urllib.parse.parse_qs(url.query, keep_blank_values=True)
+        parts = url.path.lstrip("/").split("/", 1)
+        bucket = urllib.parse.unquote(parts[0]) if parts[0] else None
+        key = urllib.parse.unquote(parts[1]) if len(parts) > 1 and parts[1] else None
+        query = urllib.parse.parse_qs(url.query, keep_blank_values=True)
+        return bucket, key, query
+
+    def read_body(self):
+        if self.headers.get("Transfer-Encoding", "").lower() == "chunked":
+            body = self.read_chunks(self.rfile)
+        else:
+            body = self.rfile.read(int(self.headers.get("Content-Length", 0)))
+
+        # Newer SDKs stream uploads with their own chunk framing and a trailing checksum
+        if "aws-chunked" in self.headers.get("Content-Encoding", "") or self.headers.get(
+            "x-amz-content-sha256", ""
+        ).startswith("STREAMING-"):
+            import io
+            body = self.read_chunks(io.BytesIO(body))
+        return body
+
+    @staticmethod
+    def read_chunks(stream):
+        data = b""
+        while True:
+            line = stream.readline().strip()
+            size = int(line.split(b";")[0], 16)
+            if size == 0:
+                # Skip trailers up to the empty line that ends the body
+                while stream.readline().strip():
+                    pass
+                return data
+            data += stream.read(size)
+            stream.readline()
+
+    def metadata_from_headers(self):
+        return {
+            name[len("x-amz-meta-"):].lower(): value
+            for name, value in self.headers.items()
+            if name.lower().startswith("x-amz-meta-")
+        }
+
+    # Responses
+
+    def reply(self, status, body=b"", headers=None, content_type="application/xml"):
+        if isinstance(body, str):
+            body = body.encode()
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        for name, value in (headers or {}).items():
+            self.send_header(name, value)
+        self.end_headers()
+        if self.command != "HEAD":
+            self.wfile.write(body)
+
+    def error(self, status, code, message=""):
+        self.reply(status, "<Error><Code>%s</Code><Message>%s</Message></Error>" % (code, escape(message)))
+
+    def bucket_exists(self, bucket):
+        if bucket in STORE.buckets:
+            return True
+        self.error(404, "NoSuchBucket", bucket)
+        return False
+
+    def object_headers(self, obj):
+        headers = {
+            "ETag": obj["etag"],
+            "Last-Modified": formatdate(obj["modified"], usegmt=True),
+            "Accept-Ranges": "bytes",
+        }
+        for name, value in obj["metadata"].items():
+            headers["x-amz-meta-" + name] = value
+        return headers
+
+    # Verbs
+
+    def do_GET(self):
+        bucket, key, query = self.parse()
+        if bucket is None:
+            names = "".join(
+                "<Bucket><Name>%s</Name><CreationDate>2024-01-01T00:00:00.000Z</CreationDate></Bucket>" % escape(b)
+                for b in sorted(STORE.buckets)
+            )
+            return self.reply(200, "<ListAllMyBucketsResult><Buckets>%s</Buckets></ListAllMyBucketsResult>" % names)
+        if not self.bucket_exists(bucket):
+            return
+        if key is None and "events" in query:
+            return self.listen(bucket)
+        if key is None:
+            return self.list_objects(bucket, query)
+
+        obj = STORE.buckets[bucket].get(key)
+        if obj is None:
+            return self.error(404, "NoSuchKey", key)
+
+        data = obj["data"]
+        status = 200
+        headers = self.object_headers(obj)
+        byte_range = self.headers.get("Range")
+        if byte_range and byte_range.startswith("bytes="):
+            start, _, end = byte_range[len("bytes="):].partition("-")
+            start = int(start)
+            end = min(int(end), len(data) - 1) if end else len(data) - 1
+            headers["Content-Range"] = "bytes %d-%d/%d" % (start, end, len(data))
+            data = data[start:end + 1]
+            status = 206
+        self.reply(status, data, headers, content_type="application/octet-stream")
+
+    def do_HEAD(self):
+        bucket, key, _ = self.parse()
+        if bucket not in STORE.buckets:
+            return self.reply(404)
+        if key is None:
+            return self.reply(200)
+        obj = STORE.buckets[bucket].get(key)
+        if obj is None:
+            return self.reply(404)
+        headers = self.object_headers(obj)
+        self.send_response(200)
+        self.send_header("Content-Length", str(len(obj["data"])))
+        self.send_header("Content-Type", "application/octet-stream")
+        for name, value in headers.items():
+            self.send_header(name, value)
+        self.end_headers()
+
+    def do_PUT(self):
+        bucket, key, query = self.parse()
+        body = self.read_body()
+        if key is None:
+            with STORE.lock:
+                STORE.buckets.setdefault(bucket, {})
+            return self.reply(200, headers={"Location": "/" + bucket})
+        if not self.bucket_exists(bucket):
+            return
+
+        if "uploadId" in query:
+            upload = STORE.uploads.get(query["uploadId"][0])
+            if upload is None:
+                return self.error(404, "NoSuchUpload")
+            upload[3][int(query["partNumber"][0])] = body
+            return self.reply(200, headers={"ETag": '"%s"' % hashlib.md5(body).hexdigest()})
+
+        source = self.headers.get("x-amz-copy-source")
+        if source:
+            source_bucket, _, source_key = urllib.parse.unquote(source).lstrip("/").partition("/")
+            original = STORE.buckets.get(source_bucket, {}).get(source_key)
+            if original is None:
+                return self.error(404, "NoSuchKey", source_key)
+            replace = self.headers.get("x-amz-metadata-directive", "COPY").upper() == "REPLACE"
+            metadata = self.metadata_from_headers() if replace else dict(original["metadata"])
+            obj = STORE.put(bucket, key, original["data"], metadata)
+            return self.reply(
+                200,
+                "<CopyObjectResult><ETag>%s</ETag><LastModified>%s</LastModified></CopyObjectResult>"
+                % (escape(obj["etag"]), time.strftime("%Y-%m-%dT%H:%M:%S.000Z", time.gmtime(obj["modified"]))),
+            )
+
+        obj = STORE.put(bucket, key, body, self.metadata_from_headers())
+        self.reply(200, headers={"ETag": obj["etag"]})
+
+    def do_POST(self):
+        bucket, key, query = self.parse()
+        body = self.read_body()
+        if not self.bucket_exists(bucket):
+            return
+
+        if "uploads" in query:
+            upload_id = uuid.uuid4().hex
+            STORE.uploads[upload_id] = (bucket, key, self.metadata_from_headers(), {})
+            return self.reply(
+                200,
+                "<InitiateMultipartUploadResult><Bucket>%s</Bucket><Key>%s</Key><UploadId>%s</UploadId>"
+                "</InitiateMultipartUploadResult>" % (escape(bucket), escape(key), upload_id),
+            )
+
+        if "uploadId" in query:
+            upload = STORE.uploads.pop(query["uploadId"][0], None)
+            if upload is None:
+                return self.error(404, "NoSuchUpload")
+            _, _, metadata, parts = upload
+            data = b"".join(parts[number] for number in sorted(parts))
+            obj = STORE.put(bucket, key, data, metadata)
+            return self.reply(
+                200,
+                "<CompleteMultipartUploadResult><Bucket>%s</Bucket><Key>%s</Key><ETag>%s</ETag>"
+                "</CompleteMultipartUploadResult>" % (escape(bucket), escape(key), escape(obj["etag"])),
+            )
+
+        if "delete" in query:
+            import re
+            keys = [urllib.parse.unquote(k) for k in re.findall(r"<Key>(.*?)</Key>", body.decode())]
+            for k in keys:
+                STORE.delete(bucket, k)
+            deleted = "".join("<Deleted><Key>%s</Key></Deleted>" % escape(k) for k in keys)
+            return self.reply(200, "<DeleteResult>%s</DeleteResult>" % deleted)
+
+        self.error(400, "NotImplemented")
+
+    def do_DELETE(self):
+        bucket, key, query = self.parse()
+        if not self.bucket_exists(bucket):
+            return
+        if key is None:
+            if STORE.buckets[bucket]:
+                return self.error(409, "BucketNotEmpty", bucket)
+            with STORE.lock:
+                del STORE.buckets[bucket]
+        elif "uploadId" in query:
+            STORE.uploads.pop(query["uploadId"][0], None)
+        else:
+            STORE.delete(bucket, key)
+        self.reply(204)
+
+    # Bucket operations
+
+    def list_objects(self, bucket, query):
+        prefix = query.get("prefix", [""])[0]
+        with STORE.lock:
+            objects = sorted((k, o) for k, o in STORE.buckets[bucket].items() if k.startswith(prefix))
+        contents = "".join(
+            "<Contents><Key>%s</Key><LastModified>%s</LastModified><ETag>%s</ETag><Size>%d</Size>"
+            "<StorageClass>STANDARD</StorageClass></Contents>"
+            % (
+                escape(k),
+                time.strftime("%Y-%m-%dT%H:%M:%S.000Z", time.gmtime(o["modified"])),
+                escape(o["etag"]),
+                len(o["data"]),
+            )
+            for k, o in objects
+        )
+        self.reply(
+            200,
+            "<ListBucketResult><Name>%s</Name><Prefix>%s</Prefix><KeyCount>%d</KeyCount><MaxKeys>1000</MaxKeys>"
+            "<IsTruncated>false</IsTruncated>%s</ListBucketResult>"
+            % (escape(bucket), escape(prefix), len(objects), contents),
+        )
+
+    def listen(self, bucket):
+        """MinIO's ListenBucketNotification: one JSON line per event, blank lines as keep-alives"""
+        events = queue.Queue()
+        with STORE.lock:
+            STORE.listeners.append((bucket, events))
+
+        self.send_response(200)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Transfer-Encoding", "chunked")
+        self.end_headers()
+        try:
+            while True:
+                try:
+                    line = json.dumps({"Records": [events.get(timeout=1)]}) + "\n"
+                except queue.Empty:
+                    line = " \n"
+                chunk = line.encode()
+                self.wfile.write(b"%x\r\n%s\r\n" % (len(chunk), chunk))
+                self.wfile.flush()
+        except (BrokenPipeError, ConnectionResetError):
+            pass
+        finally:
+            with STORE.lock:
+                STORE.listeners.remove((bucket, events))
+            self.close_connection = True
+
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser(description="In-memory S3 server for tests")
+    parser.add_argument("--port", type=int, default=0, help="Port to listen on, 0 picks a free one")
+    parser.add_argument("--expired-token", action="append", default=[], metavar="TOKEN",
+                        help="Refuse requests with this session token as expired")
+    parser.add_argument("--tls-cert", metavar="PEM", help="Serve HTTPS with this certificate")
+    parser.add_argument("--tls-key", metavar="PEM", help="Private key of --tls-cert")
+    parser.add_argument("--client-ca", metavar="PEM", help="Require client certificates signed by this CA")
+    parser.add_argument("--hang", action="store_true", help="Accept connections but never answer")
+    parser.add_argument("--domain", help="Serve BUCKET.DOMAIN hosts as virtual-host style requests")
+    parser.add_argument("--addressing", choices=["path", "virtual"], help="Refuse requests addressed the other way")
+    parser.add_argument("--signature", choices=["v2", "v4"], help="Refuse requests signed the other way")
+    parser.add_argument("--region", help="Refuse SigV4 requests signed for another region")
+    parser.add_argument("--no-checksums", action="store_true", help="Refuse flexible checksum headers")
+    args = parser.parse_args()
+    for name in vars(COMPAT):
+        setattr(COMPAT, name, getattr(args, name))
+    EXPIRED_TOKENS.update(args.expired_token)
+    if args.hang:
+        HANG.set()
+
+    server = ThreadingHTTPServer(("127.0.0.1", args.port), Handler)
+    server.daemon_threads = True
+    scheme = "http"
+    if args.tls_cert:
+        context = ssl.SSLContext(ssl.PROTOCOL_TLS_SERVER)
+        context.load_cert_chain(args.tls_cert, args.tls_key)
+        if args.client_ca:
+            context.verify_mode = ssl.CERT_REQUIRED
+            context.load_verify_locations(args.client_ca)
+        # Failed handshakes surface as errors of the accepted connection, which the server drops
+        server.socket = context.wrap_socket(server.socket, server_side=True)
+        scheme = "https"
+    print("Fake S3 server listening on %s://127.0.0.1:%d" % (scheme, server.server_address[1]), flush=True)
+    server.serve_forever()
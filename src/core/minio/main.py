@@ -1,4 +1,5 @@
 import argparse
+import json
 import os
 from src.minio_util import MinioClient
 
@@ -8,33 +9,69 @@ if __name__ == "__main__":
 
     parser = argparse.ArgumentParser(description="MinIO Client")
     parser.add_argument("--upload", nargs=3, metavar=("FILE_PATH", "BUCKET", "KEY"), help="Upload a file to MinIO")
+    parser.add_argument("--metadata", metavar="JSON", help="User metadata (JSON object) to attach to an upload")
+    parser.add_argument("--max-bandwidth", type=int, metavar="BYTES", help="Cap uploads and downloads at BYTES per second")
+    parser.add_argument("--update-metadata", nargs=2, metavar=("BUCKET", "KEY"), help="Replace the metadata of an object with --metadata")
     parser.add_argument("--download", nargs=3, metavar=("BUCKET", "KEY", "LOCAL_PATH"), help="Download a file from MinIO")
+    parser.add_argument("--print-metadata", action="store_true", help="Print the user metadata of a downloaded object")
     parser.add_argument("--delete", nargs=2, metavar=("BUCKET", "KEY"), help="Delete a file from MinIO")
+    parser.add_argument("--head", nargs=2, metavar=("BUCKET", "KEY"), help="Print the ETag, size and metadata of an object")
+    parser.add_argument("--listen", metavar="BUCKET", help="Print bucket notifications as they arrive (MinIO only)")
     parser.add_argument("--list", metavar="BUCKET", help="List files in a MinIO bucket")
     parser.add_argument("--list-buckets", action="store_true", help="List all buckets")
     parser.add_argument("--create-bucket", metavar="BUCKET", help="Create a new bucket")
+    parser.add_argument("--probe", nargs="?", const="", metavar="BUCKET", help="Check reachability, credentials and, with a bucket, list/put/delete permissions")
     parser.add_argument("--check-bucket", metavar="BUCKET", help="Check if bucket exists")
     args = parser.parse_args()
 
+    # rusty-sync decides on the proxy, including when to go direct, so boto3 must not pick one up itself
+    if "MINIO_PROXY" in os.environ:
+        for name in ("HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY", "NO_PROXY"):
+            os.environ.pop(name, None)
+            os.environ.pop(name.lower(), None)
+
     Client = MinioClient(
         endpoint_url=os.getenv("MINIO_ENDPOINT_URL"),
         access_key=os.getenv("MINIO_ACCESS_KEY"),
-        secret_key=os.getenv("MINIO_SECRET_KEY")
+        secret_key=os.getenv("MINIO_SECRET_KEY"),
+        session_token=os.getenv("MINIO_SESSION_TOKEN"),
+        ca_file=os.getenv("MINIO_CA_FILE"),
+        client_cert=os.getenv("MINIO_CLIENT_CERT"),
+        client_key=os.getenv("MINIO_CLIENT_KEY"),
+        insecure=os.getenv("MINIO_INSECURE") == "1",
+        proxy=os.getenv("MINIO_PROXY"),
+        connect_timeout=int(os.getenv("MINIO_CONNECT_TIMEOUT", "10")),
+        read_timeout=int(os.getenv("MINIO_READ_TIMEOUT", "60")),
+        max_connections=int(os.getenv("MINIO_MAX_CONNECTIONS", "0")) or None,
+        region=os.getenv("MINIO_REGION"),
+        addressing=os.getenv("MINIO_ADDRESSING"),
+        signature_version=os.getenv("MINIO_SIGNATURE_VERSION"),
+        checksums=os.getenv("MINIO_CHECKSUMS")
     )
     Client.__test__()
 
     if args.upload:
-        Client.upload_file(args.upload[0], args.upload[1], args.upload[2])
+        metadata = json.loads(args.metadata) if args.metadata else None
+        Client.upload_file(args.upload[0], args.upload[1], args.upload[2], metadata, args.max_bandwidth)
+    if args.update_metadata:
+        metadata = json.loads(args.metadata) if args.metadata else {}
+        Client.update_metadata(args.update_metadata[0], args.update_metadata[1], metadata)
     if args.download:
-        Client.download_file(args.download[0], args.download[1], args.download[2])
+        Client.download_file(args.download[0], args.download[1], args.download[2], args.max_bandwidth, args.print_metadata)
     if args.delete:
         Client.delete_file(args.delete[0], args.delete[1])
+    if args.head:
+        Client.head_object(args.head[0], args.head[1])
+    if args.listen:
+        Client.listen_notifications(args.listen)
     if args.list:
         Client.list_files(args.list)
     if args.list_buckets:
         Client.list_buckets()
     if args.create_bucket:
         Client.create_bucket(args.create_bucket)
+    if args.probe is not None:
+        Client.probe(args.probe or None)
     if args.check_bucket:
         Client.check_bucket_exists(args.check_bucket)
     # Client.upload_file("/home/tegran-grigorian/Documents/Projects/rusty-sync/hi.mp3", "rusty-sync", "hi.mp3")
\ No newline at end of file
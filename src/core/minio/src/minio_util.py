@@ -1,45 +1,183 @@
+import json
+import os
+import sys
+import threading
+import urllib.parse
+import uuid
+import warnings
+
 import boto3
+import urllib3
+from boto3.s3.transfer import TransferConfig
+from botocore.config import Config
+
+
+# Error codes S3 servers answer with once a session token has run out
+EXPIRED_TOKEN_CODES = ("ExpiredToken", "TokenRefreshRequired", "InvalidTokenId")
+
+
+def error_code(error):
+    return getattr(error, "response", {}).get("Error", {}).get("Code")
+
+
+def report_expired_token(error):
+    # A line of its own on stderr, so rusty-sync never mistakes an object key or message for it
+    if error_code(error) in EXPIRED_TOKEN_CODES:
+        print("Error code: expired-token", file=sys.stderr)
+        return True
+    return False
+
+
+def fail(action, error):
+    # On stderr with a non-zero exit, so rusty-sync never takes a failed command for a successful one
+    report_expired_token(error)
+    print(f"{action} failed: {error}", file=sys.stderr)
+    sys.exit(1)
+
 
 class MinioClient:
-    def __init__(self, endpoint_url, access_key, secret_key):
+    def __init__(self, endpoint_url, access_key, secret_key, session_token=None,
+                 ca_file=None, client_cert=None, client_key=None, insecure=False,
+                 proxy=None, connect_timeout=10, read_timeout=60, max_connections=None,
+                 region=None, addressing=None, signature_version=None, checksums=None):
+        self.access_key = access_key
+        self.secret_key = secret_key
+        self.session_token = session_token or None
+        self.ca_file = ca_file or None
+        self.client_cert = client_cert or None
+        self.client_key = client_key or None
+        self.insecure = insecure
+        self.proxy = proxy or None
+        # botocore only speaks to HTTP proxies, SOCKS connections are made by urllib3 with PySocks
+        self.socks = bool(self.proxy) and self.proxy.startswith("socks")
+        self.connect_timeout = connect_timeout
+        self.read_timeout = read_timeout
+        self.max_connections = max_connections
+        self.region = region or "us-east-1"
+        self.signature_version = signature_version or "s3v4"
+        if insecure:
+            # rusty-sync warns once, not on every request
+            urllib3.disable_warnings(urllib3.exceptions.InsecureRequestWarning)
+
+        cert = (self.client_cert, self.client_key) if self.client_key else self.client_cert
+        options = {"max_pool_connections": max_connections} if max_connections else {}
+        # Servers that predate flexible checksums reject their headers, "when_required" leaves them out
+        # (botocore before 1.36 has neither the headers nor these options)
+        if checksums and checksums != "when_supported" and "request_checksum_calculation" in Config.OPTION_DEFAULTS:
+            options.update(request_checksum_calculation=checksums, response_checksum_validation=checksums)
+        config = Config(
+            client_cert=cert,
+            proxies={"http": self.proxy, "https": self.proxy} if self.proxy and not self.socks else None,
+            connect_timeout=connect_timeout,
+            read_timeout=read_timeout,
+            signature_version=self.signature_version,
+            s3={"addressing_style": addressing or "auto"},
+            **options
+        )
         self.s3 = boto3.client(
             "s3",
             endpoint_url=endpoint_url,
             aws_access_key_id=access_key,
             aws_secret_access_key=secret_key,
-            region_name="us-east-1"
+            aws_session_token=self.session_token,
+            region_name=self.region,
+            verify=False if insecure else self.ca_file,
+            config=config
         )
+        if self.socks:
+            session = self.s3._endpoint.http_session
+            session._manager = self.socks_manager(**session._get_pool_manager_kwargs())
+
+    def transfer_config(self, max_bandwidth=None):
+        # Bandwidth caps come from the daemon's schedule windows
+        options = {"max_bandwidth": max_bandwidth} if max_bandwidth else {}
+        if self.max_connections:
+            options["max_concurrency"] = self.max_connections
+        return TransferConfig(**options) if options else None
+
+    def timeout(self):
+        return urllib3.Timeout(connect=self.connect_timeout, read=self.read_timeout)
+
+    def http(self):
+        # For the requests boto3 has no call for, verified the same way as boto3's own
+        from botocore.httpsession import get_cert_path
+
+        if self.insecure:
+            options = {"cert_reqs": "CERT_NONE"}
+        else:
+            options = {"cert_reqs": "CERT_REQUIRED", "ca_certs": self.ca_file or os.getenv("AWS_CA_BUNDLE") or get_cert_path(True)}
+        if self.client_cert:
+            options.update(cert_file=self.client_cert, key_file=self.client_key)
+        if self.socks:
+            return self.socks_manager(**options)
+        if self.proxy:
+            return urllib3.ProxyManager(self.proxy, **options)
+        return urllib3.PoolManager(**options)
+
+    def socks_manager(self, **options):
+        # socks5h:// and socks4a:// leave resolving the endpoint's name to the proxy
+        try:
+            with warnings.catch_warnings():
+                warnings.simplefilter("ignore")
+                from urllib3.contrib.socks import SOCKSProxyManager
+        except ImportError:
+            fail("SOCKS proxy setup", "PySocks is not installed, add it with 'pip install pysocks'")
+        return SOCKSProxyManager(self.proxy, **options)
     def __test__(self):
         try:
             self.s3.list_buckets()
             print("Connection test succeeded!")
         except Exception as e:
-            print("Connection test failed:", e)
+            # Only a hint, credentials may be allowed to use a bucket without listing them all
+            # (HEAD errors have no body, an expired token only shows up here for them)
+            report_expired_token(e)
+            print("Connection test failed:", e, file=sys.stderr)
 
-    def upload_file(self, file_path, bucket, key):
+    def upload_file(self, file_path, bucket, key, metadata=None, max_bandwidth=None):
         try:
-            self.s3.upload_file(file_path, bucket, key)
+            extra_args = {"Metadata": metadata} if metadata else None
+            self.s3.upload_file(file_path, bucket, key, ExtraArgs=extra_args, Config=self.transfer_config(max_bandwidth))
             print("Upload succeeded!")
         except Exception as e:
-            print("Upload failed:", e)
+            fail("Upload", e)
 
-    def download_file(self, bucket, key, local_path):
+    def update_metadata(self, bucket, key, metadata):
+        try:
+            # Encryption parameters describe the stored bytes, which a metadata update leaves alone
+            current = self.s3.head_object(Bucket=bucket, Key=key).get("Metadata", {})
+            metadata = {**{k: v for k, v in current.items() if k.startswith("rusty-sync-encryption")}, **metadata}
+            # Copying an object onto itself is the only way S3 allows changing its metadata
+            self.s3.copy_object(
+                Bucket=bucket,
+                Key=key,
+                CopySource={"Bucket": bucket, "Key": key},
+                Metadata=metadata,
+                MetadataDirective="REPLACE"
+            )
+            print("Metadata update succeeded!")
+        except Exception as e:
+            fail("Metadata update", e)
+
+    def download_file(self, bucket, key, local_path, max_bandwidth=None, print_metadata=False):
         try:
             # Ensure directory exists
             import os
             os.makedirs(os.path.dirname(local_path), exist_ok=True)
             
-            self.s3.download_file(bucket, key, local_path)
+            self.s3.download_file(bucket, key, local_path, Config=self.transfer_config(max_bandwidth))
+            if print_metadata:
+                # Encrypted objects carry the parameters needed to decrypt them
+                print("Metadata:", json.dumps(self.s3.head_object(Bucket=bucket, Key=key).get("Metadata", {})))
             print("Download succeeded!")
         except Exception as e:
-            print("Download failed:", e)
+            fail("Download", e)
 
     def delete_file(self, bucket, key):
         try:
             self.s3.delete_object(Bucket=bucket, Key=key)
             print("Delete succeeded!")
         except Exception as e:
-            print("Delete failed:", e)
+            fail("Delete", e)
 
     def list_files(self, bucket):
         try:
@@ -47,7 +185,7 @@ class MinioClient:
             files = [obj["Key"] for obj in response.get("Contents", [])]
             print("Files in bucket:", files)
         except Exception as e:
-            print("List files failed:", e)
+            fail("List files", e)
 
     def list_files_in_prefix(self, bucket, prefix):
         try:
@@ -55,7 +193,7 @@ class MinioClient:
             files = [obj["Key"] for obj in response.get("Contents", [])]
             print(f"Files in bucket '{bucket}' with prefix '{prefix}':", files)
         except Exception as e:
-            print("List files failed:", e)
+            fail("List files", e)
 
     def upload_to_prefix(self, file_path, bucket, prefix): #hopefully a easier way to upload to a prefix
         try:
@@ -63,22 +201,72 @@ class MinioClient:
             self.s3.upload_file(file_path, bucket, key)
             print("Upload to prefix succeeded!")
         except Exception as e:
-            print("Upload to prefix failed:", e)
+            fail("Upload to prefix", e)
 
     def get_file_timestamp(self, bucket, key):
         try:
             response = self.s3.head_object(Bucket=bucket, Key=key)
             return response["LastModified"]
         except Exception as e:
-            print("Get file timestamp failed:", e)
-            return None
+            fail("Get file timestamp", e)
     def get_file_size(self, bucket, key):
         try:
             response = self.s3.head_object(Bucket=bucket, Key=key)
             return response["ContentLength"]
         except Exception as e:
-            print("Get file size failed:", e)
-            return None
+            fail("Get file size", e)
+
+    def head_object(self, bucket, key):
+        try:
+            response = self.s3.head_object(Bucket=bucket, Key=key)
+            print("Head:", json.dumps({"etag": response["ETag"], "size": response["ContentLength"], "metadata": response.get("Metadata", {})}))
+        except Exception as e:
+            if getattr(e, "response", {}).get("Error", {}).get("Code") in ("404", "NoSuchKey", "NotFound"):
+                print("Head missing")
+            else:
+                fail("Head", e)
+
+    def listen_notifications(self, bucket):
+        # MinIO's ListenBucketNotification extension, which boto3 has no call for
+        try:
+            from botocore.auth import HmacV1Auth, S3SigV4Auth
+            from botocore.awsrequest import AWSRequest
+            from botocore.credentials import Credentials
+
+            query = urllib.parse.urlencode(
+                [("events", "s3:ObjectCreated:*"), ("events", "s3:ObjectRemoved:*"), ("prefix", ""), ("suffix", "")]
+            )
+            url = f"{self.s3.meta.endpoint_url}/{urllib.parse.quote(bucket)}?{query}"
+            request = AWSRequest(method="GET", url=url)
+            credentials = Credentials(self.access_key, self.secret_key, self.session_token)
+            if self.signature_version == "s3":
+                HmacV1Auth(credentials).add_auth(request)
+            else:
+                S3SigV4Auth(credentials, "s3", self.region).add_auth(request)
+
+            # MinIO sends keep-alives far more often than the read timeout, silence means the server hangs
+            response = self.http().request(
+                "GET", url, headers=dict(request.headers), preload_content=False, timeout=self.timeout()
+            )
+            if response.status != 200:
+                print("Listen failed:", response.status, response.data.decode(errors="replace"))
+                sys.exit(1)
+
+            # Stop with the process that started us, which holds the other end of stdin
+            threading.Thread(target=lambda: (sys.stdin.read(), os._exit(0)), daemon=True).start()
+
+            print("Listening for bucket notifications", flush=True)
+            for line in response:
+                # Blank lines are keep-alives
+                if not line.strip():
+                    continue
+                for record in json.loads(line).get("Records", []):
+                    key = urllib.parse.unquote_plus(record["s3"]["object"]["key"])
+                    print("Event:", record["eventName"], key, flush=True)
+            print("Listen failed: connection closed")
+        except Exception as e:
+            print("Listen failed:", e)
+        sys.exit(1)
 
     def create_bucket(self, bucket_name):
         try:
@@ -88,7 +276,7 @@ class MinioClient:
             if "BucketAlreadyExists" in str(e) or "BucketAlreadyOwnedByYou" in str(e):
                 print(f"Bucket '{bucket_name}' already exists.")
             else:
-                print(f"Create bucket failed: {e}")
+                fail("Create bucket", e)
 
     def check_bucket_exists(self, bucket_name):
         try:
@@ -96,9 +284,55 @@ class MinioClient:
             print(f"Bucket '{bucket_name}' exists.")
             return True
         except Exception as e:
-            print(f"Bucket '{bucket_name}' does not exist: {e}")
+            if getattr(e, "response", {}).get("Error", {}).get("Code") not in ("404", "NoSuchBucket", "NotFound"):
+                fail("Check bucket", e)
+            print(f"Bucket '{bucket_name}' does not exist.")
             return False
 
+    def probe(self, bucket=None):
+        # One "Probe:" line per check, stopping at the first that fails since the rest depend on it
+        def report(check, ok, detail, **extra):
+            print("Probe:", json.dumps({"check": check, "ok": ok, "detail": detail, **extra}), flush=True)
+            return ok
+
+        def failed(check, error):
+            report(check, False, str(error))
+            if report_expired_token(error):
+                sys.exit(1)  # rusty-sync refreshes the credentials and probes again
+            return False
+
+        try:
+            # Unsigned, so an unreachable server isn't mistaken for rejected credentials
+            response = self.http().request(
+                "GET", self.s3.meta.endpoint_url, retries=False, timeout=self.timeout()
+            )
+            date = response.headers.get("Date")
+            report("reachable", True, f"HTTP {response.status}", date=date)
+        except Exception as e:
+            return report("reachable", False, str(e))
+
+        try:
+            buckets = self.s3.list_buckets().get("Buckets", [])
+            report("authentication", True, f"{len(buckets)} bucket(s) visible")
+        except Exception as e:
+            return failed("authentication", e)
+
+        if not bucket:
+            return True
+        key = f".rusty-sync/probe-{uuid.uuid4()}"
+        steps = [
+            ("list", lambda: self.s3.list_objects_v2(Bucket=bucket, MaxKeys=1)),
+            ("put", lambda: self.s3.put_object(Bucket=bucket, Key=key, Body=b"rusty-sync probe")),
+            ("delete", lambda: self.s3.delete_object(Bucket=bucket, Key=key)),
+        ]
+        for check, step in steps:
+            try:
+                step()
+                report(check, True, key if check != "list" else bucket)
+            except Exception as e:
+                return failed(check, e)
+        return True
+
     def list_buckets(self):
         try:
             response = self.s3.list_buckets()
@@ -106,5 +340,4 @@ class MinioClient:
             print("Available buckets:", buckets)
             return buckets
         except Exception as e:
-            print("List buckets failed:", e)
-            return [] 
\ No newline at end of file
+            fail("List buckets", e)
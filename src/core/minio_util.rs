@@ -1,7 +1,18 @@
 // this code reads into the src/core/minio python suite and runs commands into it
-use std::process::Command; // run python script
-use std::path::PathBuf;
-use crate::config::config_manager::RustySyncConfig;
+use std::process::{Child, Command, Output, Stdio}; // run python script
+use std::path::{Path, PathBuf};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::io::Read;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use crate::config::config_manager::{MinioEnv, RustySyncConfig};
+use crate::config::credentials::ResolvedCredentials;
+use crate::core::encryption::{
+    ENCRYPTION_METADATA, ENCRYPTION_PARAMS_KEY, EncryptionKey, SALT_METADATA, TempFile, stored_in_plaintext,
+};
 
 /// Find the project root directory by looking for Cargo.toml
 fn find_project_root() -> Result<PathBuf, String> {
@@ -22,11 +33,105 @@ fn find_project_root() -> Result<PathBuf, String> {
     }
 }
 
-/// Set up environment variables from config before running Python commands
-fn setup_minio_env() -> Result<(), String> {
+/// Line the bridge prints on stderr when the server rejected an expired session token
+const EXPIRED_TOKEN_ERROR: &str = "Error code: expired-token";
+
+/// Bridge commands that run as long as data keeps arriving within the read timeout, without a deadline
+const TRANSFER_COMMANDS: [&str; 2] = ["--upload", "--download"];
+
+/// Environment for Python commands, passed to the child process only
+/// (setting our own environment is not thread-safe once the daemon runs several transfers)
+fn setup_minio_env() -> Result<MinioEnv, String> {
     let config = RustySyncConfig::load()?;
-    config.export_to_env()?;
-    Ok(())
+    config.minio_env()
+}
+
+/// Run main.py with the arguments `configure` adds, under the resolved credentials
+/// When the server reports an expired session token the credentials are refreshed and the command runs once more
+fn run_minio(configure: impl Fn(&mut Command)) -> Result<Output, String> {
+    let (minio_dir, python_exe, main_py) = find_minio_scripts()?;
+    let mut refreshed = false;
+
+    loop {
+        let env = setup_minio_env()?;
+        let mut command = Command::new(&python_exe);
+        command.envs(env.vars).current_dir(&minio_dir).arg(&main_py);
+        configure(&mut command);
+
+        // A server that accepts connections but never answers would otherwise hold the command forever
+        let transfer = command.get_args().any(|arg| TRANSFER_COMMANDS.iter().any(|name| arg == *name));
+        let output = output_within(command, (!transfer).then(|| env.network.command_timeout()))?;
+
+        if !is_expired_token(&output) {
+            return Ok(output);
+        }
+        if refreshed || !ResolvedCredentials::expire_cached() {
+            return Err(
+                "Session credentials have expired. Renew them, or configure a credential process to refresh them".to_string(),
+            );
+        }
+        eprintln!("Session credentials expired, refreshing them");
+        refreshed = true;
+    }
+}
+
+/// Whether a command failed because the session token ran out
+/// Only the bridge's own error line counts, stdout carries object keys and metadata that could contain anything
+fn is_expired_token(output: &Output) -> bool {
+    !output.status.success()
+        && String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .any(|line| line.trim() == EXPIRED_TOKEN_ERROR)
+}
+
+/// Like `Command::output`, but the command is killed once `timeout` has passed
+fn output_within(mut command: Command, timeout: Option<Duration>) -> Result<Output, String> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute python script: {}", e))?;
+    let Some(timeout) = timeout else {
+        return child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to execute python script: {}", e));
+    };
+
+    // Drained meanwhile, a child blocked on a full pipe never exits
+    let stdout = child.stdout.take().map(read_in_background);
+    let stderr = child.stderr.take().map(read_in_background);
+    let started = Instant::now();
+    let mut pause = Duration::from_millis(5);
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| format!("Failed to execute python script: {}", e))?
+        {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!(
+                "No answer from the server within {}s. Check it is up, or raise its timeouts with 'rusty-sync config network'",
+                timeout.as_secs()
+            ));
+        }
+        thread::sleep(pause);
+        pause = (pause * 2).min(Duration::from_millis(100));
+    };
+
+    let collect = |reader: Option<JoinHandle<Vec<u8>>>| reader.and_then(|reader| reader.join().ok()).unwrap_or_default();
+    Ok(Output { status, stdout: collect(stdout), stderr: collect(stderr) })
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
 }
 
 /// Find MinIO Python scripts - check system installation first, then development location
@@ -52,7 +157,7 @@ fn find_minio_scripts() -> Result<(PathBuf, PathBuf, PathBuf), String> {
     let system_main_py = system_minio_dir.join("main.py");
     
     if system_minio_dir.exists() && system_main_py.exists() {
-        return Ok((system_minio_dir, system_python_exe, system_main_py));
+        return Ok((system_minio_dir, with_fallback_python(system_python_exe), system_main_py));
     }
     
     // Development path (fallback for source builds)
@@ -68,7 +173,7 @@ fn find_minio_scripts() -> Result<(PathBuf, PathBuf, PathBuf), String> {
     let dev_main_py = dev_minio_dir.join("main.py");
     
     if dev_minio_dir.exists() && dev_main_py.exists() {
-        return Ok((dev_minio_dir, dev_python_exe, dev_main_py));
+        return Ok((dev_minio_dir, with_fallback_python(dev_python_exe), dev_main_py));
     }
     
     #[cfg(windows)]
@@ -80,25 +185,133 @@ fn find_minio_scripts() -> Result<(PathBuf, PathBuf, PathBuf), String> {
     Err(error_msg.to_string())
 }
 
+/// Use the Python on PATH when the bundled virtualenv hasn't been created (boto3 must be installed there)
+fn with_fallback_python(venv_python: PathBuf) -> PathBuf {
+    if venv_python.exists() {
+        return venv_python;
+    }
+
+    #[cfg(windows)]
+    let system_python = "python";
+
+    #[cfg(unix)]
+    let system_python = "python3";
+
+    PathBuf::from(system_python)
+}
+
+/// Limits applied to the transfers a thread starts, see `MinioUtil::with_limits`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransferLimits {
+    pub max_bandwidth: Option<u64>, // Bytes per second for each upload or download
+    pub deadline: Option<Instant>,  // No new transfers start after this
+}
+
+thread_local! {
+    static TRANSFER_LIMITS: Cell<TransferLimits> = Cell::new(TransferLimits::default());
+}
+
+/// Refuse to start a transfer once the thread's deadline has passed
+fn check_transfer_deadline() -> Result<(), String> {
+    match TRANSFER_LIMITS.get().deadline {
+        Some(deadline) if Instant::now() >= deadline => Err("Transfer window has ended".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Extra arguments for uploads and downloads under the thread's limits
+fn transfer_args() -> Vec<String> {
+    match TRANSFER_LIMITS.get().max_bandwidth {
+        Some(bytes) => vec!["--max-bandwidth".to_string(), bytes.to_string()],
+        None => Vec::new(),
+    }
+}
+
+thread_local! {
+    static WITHOUT_ENCRYPTION: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Keys of the buckets this process has looked up, None for buckets that aren't encrypted
+static BUCKET_KEYS: Mutex<BTreeMap<String, Option<Arc<EncryptionKey>>>> = Mutex::new(BTreeMap::new());
+
+/// Outcome of one step of `MinioUtil::probe`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProbeCheck {
+    pub check: String, // reachable, authentication, list, put or delete
+    pub ok: bool,
+    pub detail: String,
+    #[serde(default)]
+    pub date: Option<String>, // Date header of the server, reported by the reachability check
+}
+
+/// Python interpreter and scripts the bridge runs, see `MinioUtil::python_setup`
+#[derive(Debug, Clone)]
+pub struct PythonSetup {
+    pub python: PathBuf,
+    pub main_py: PathBuf,
+    pub boto3_version: Result<String, String>, // Error if boto3 can't be imported by that interpreter
+}
+
 pub struct MinioUtil {}
 
 impl MinioUtil {
+    /// Run `f` with `limits` applied to every transfer it starts on this thread
+    pub fn with_limits<T>(limits: TransferLimits, f: impl FnOnce() -> T) -> T {
+        let previous = TRANSFER_LIMITS.replace(limits);
+        let result = f();
+        TRANSFER_LIMITS.set(previous);
+        result
+    }
+
+    /// Run `f` with objects transferred as stored, without encrypting contents or names
+    /// Used to convert objects uploaded before a bucket was encrypted
+    pub fn without_encryption<T>(f: impl FnOnce() -> T) -> T {
+        let previous = WITHOUT_ENCRYPTION.replace(true);
+        let result = f();
+        WITHOUT_ENCRYPTION.set(previous);
+        result
+    }
+
     // example python command python main.py --upload /home/tegran-grigorian/Documents/Projects/rusty-sync/hi.mp3 rusty-sync hi.mp3
 
     pub fn upload_file(file_path: &str, bucket: &str, object_name: &str) -> Result<(), String> {
-        setup_minio_env()?; // Setup config before running Python
-        
-        let (minio_dir, python_exe, main_py) = find_minio_scripts()?;
-        
-        let output = Command::new(&python_exe)
-            .current_dir(&minio_dir) // Set working directory
-            .arg(&main_py)
-            .arg("--upload")
-            .arg(file_path)
-            .arg(bucket)
-            .arg(object_name)
-            .output()
-            .map_err(|e| format!("Failed to execute python script: {}", e))?;
+        Self::upload_file_with_metadata(file_path, bucket, object_name, &BTreeMap::new())
+    }
+
+    /// Upload a file with user metadata attached to the object
+    pub fn upload_file_with_metadata(
+        file_path: &str,
+        bucket: &str,
+        object_name: &str,
+        metadata: &BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        check_transfer_deadline()?;
+        // Encrypted buckets get a sealed copy, described by nothing but its encryption parameters
+        // (link targets, modes and xattrs travel in the encrypted manifest only)
+        let key = Self::object_key(bucket, object_name)?;
+        let (sealed, metadata) = match &key {
+            Some(key) => {
+                let (sealed, encryption_metadata) = key.seal_file(Path::new(file_path), object_name)?;
+                (Some(sealed), encryption_metadata)
+            }
+            None => (None, metadata.clone()),
+        };
+        let remote_name = key.map_or(object_name.to_string(), |key| key.object_name(object_name));
+        let upload_path = sealed.as_ref().map_or(Path::new(file_path), |sealed| sealed.path.as_path());
+
+        let metadata_json = serde_json::to_string(&metadata)
+            .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+
+        let output = run_minio(|command| {
+            command
+                .arg("--upload")
+                .arg(upload_path)
+                .arg(bucket)
+                .arg(&remote_name)
+                .arg("--metadata")
+                .arg(&metadata_json)
+                .args(transfer_args());
+        })?;
 
         if output.status.success() {
             println!("Successfully uploaded {} to bucket {}", object_name, bucket);
@@ -111,19 +324,66 @@ impl MinioUtil {
         }
     }
 
+    /// Replace the user metadata of an existing object without re-uploading it
+    pub fn update_object_metadata(
+        bucket: &str,
+        object_name: &str,
+        metadata: &BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        check_transfer_deadline()?;
+        if Self::object_key(bucket, object_name)?.is_some() {
+            return Ok(()); // Encrypted objects carry no file metadata, the manifest has it
+        }
+        Self::write_metadata(bucket, object_name, object_name, metadata)
+    }
+
+    /// Replace the encryption metadata of an object in an encrypted bucket, used to re-wrap its data key
+    pub fn replace_encryption_metadata(
+        bucket: &str,
+        object_name: &str,
+        metadata: &BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        check_transfer_deadline()?;
+        let remote_name = Self::remote_name(bucket, object_name)?;
+        Self::write_metadata(bucket, object_name, &remote_name, metadata)
+    }
+
+    fn write_metadata(
+        bucket: &str,
+        object_name: &str,
+        remote_name: &str,
+        metadata: &BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        let metadata_json = serde_json::to_string(metadata)
+            .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+
+        let output = run_minio(|command| {
+            command
+                .arg("--update-metadata")
+                .arg(bucket)
+                .arg(remote_name)
+                .arg("--metadata")
+                .arg(&metadata_json);
+        })?;
+
+        if output.status.success() {
+            println!("Updated metadata of {} in bucket {}", object_name, bucket);
+            Ok(())
+        } else {
+            Err(format!(
+                "Python script error: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ))
+        }
+    }
+
     /// Create a bucket if it doesn't exist
     pub fn create_bucket(bucket: &str) -> Result<(), String> {
-        setup_minio_env()?; // Setup config before running Python
-        
-        let (minio_dir, python_exe, main_py) = find_minio_scripts()?;
-        
-        let output = Command::new(&python_exe)
-            .current_dir(&minio_dir)
-            .arg(&main_py)
-            .arg("--create-bucket")
-            .arg(bucket)
-            .output()
-            .map_err(|e| format!("Failed to execute python script: {}", e))?;
+        let output = run_minio(|command| {
+            command
+                .arg("--create-bucket")
+                .arg(bucket);
+        })?;
 
         if output.status.success() {
             println!("Bucket '{}' is ready", bucket);
@@ -138,49 +398,158 @@ impl MinioUtil {
 
     /// Check if bucket exists
     pub fn check_bucket_exists(bucket: &str) -> Result<bool, String> {
-        setup_minio_env()?; // Setup config before running Python
-        
-        let (minio_dir, python_exe, main_py) = find_minio_scripts()?;
-        
-        let output = Command::new(&python_exe)
-            .current_dir(&minio_dir)
-            .arg(&main_py)
-            .arg("--check-bucket")
-            .arg(bucket)
-            .output()
-            .map_err(|e| format!("Failed to execute python script: {}", e))?;
+        let output = run_minio(|command| {
+            command
+                .arg("--check-bucket")
+                .arg(bucket);
+        })?;
 
         if output.status.success() {
             let stdout = String::from_utf8_lossy(&output.stdout);
-            Ok(stdout.contains("exists"))
+            Ok(stdout.contains("exists."))
         } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            if stderr.contains("does not exist") {
-                Ok(false)
-            } else {
-                Err(format!("Python script error: {}", stderr))
-            }
+            Err(format!(
+                "Python script error: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ))
         }
     }
 
     /// Download a file from MinIO
     pub fn download_file(bucket: &str, object_name: &str, local_path: &str) -> Result<(), String> {
-        setup_minio_env()?; // Setup config before running Python
-        
-        let (minio_dir, python_exe, main_py) = find_minio_scripts()?;
-        
-        let output = Command::new(&python_exe)
-            .current_dir(&minio_dir)
-            .arg(&main_py)
-            .arg("--download")
-            .arg(bucket)
-            .arg(object_name)
-            .arg(local_path)
-            .output()
-            .map_err(|e| format!("Failed to execute python script: {}", e))?;
+        check_transfer_deadline()?;
+        // Encrypted objects are downloaded to a private temp file and decrypted into the target
+        let key = Self::object_key(bucket, object_name)?;
+        let sealed = key.as_ref().map(|_| TempFile::new()).transpose()?;
+        let download_path = sealed.as_ref().map_or(Path::new(local_path), |sealed| sealed.path.as_path());
+
+        let remote_name = key.as_ref().map_or(object_name.to_string(), |key| key.object_name(object_name));
+        let output = run_minio(|command| {
+            command
+                .arg("--download")
+                .arg(bucket)
+                .arg(&remote_name)
+                .arg(download_path)
+                .args(transfer_args());
+            if key.is_some() {
+                command.arg("--print-metadata");
+            }
+        })?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Python script error: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        if let (Some(key), Some(sealed)) = (key, sealed) {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let metadata: BTreeMap<String, String> = stdout
+                .lines()
+                .find_map(|line| line.strip_prefix("Metadata: "))
+                .map(serde_json::from_str)
+                .transpose()
+                .map_err(|e| format!("Invalid metadata response: {}", e))?
+                .ok_or_else(|| format!("Download of {} failed: {}", object_name, stdout.trim()))?;
+
+            if let Some(parent) = Path::new(local_path).parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
+            }
+            if metadata.contains_key(ENCRYPTION_METADATA) {
+                key.open_file(&metadata, object_name, &sealed.path, Path::new(local_path))?;
+            } else {
+                // Uploaded before the bucket was encrypted
+                std::fs::copy(&sealed.path, local_path)
+                    .map_err(|e| format!("Failed to write {}: {}", local_path, e))?;
+            }
+        }
+
+        println!("Successfully downloaded {} from bucket {}", object_name, bucket);
+        Ok(())
+    }
+
+    /// Key to encrypt an object with, None if the bucket isn't encrypted
+    /// Errors for encrypted buckets that haven't been unlocked on this machine
+    fn object_key(bucket: &str, object_name: &str) -> Result<Option<Arc<EncryptionKey>>, String> {
+        if stored_in_plaintext(object_name) {
+            return Ok(None); // Needed to derive the key, so never encrypted itself
+        }
+        Self::bucket_key(bucket)
+    }
+
+    /// Key of an encrypted bucket, see `object_key`
+    fn bucket_key(bucket: &str) -> Result<Option<Arc<EncryptionKey>>, String> {
+        if WITHOUT_ENCRYPTION.get() {
+            return Ok(None);
+        }
+        if let Some(key) = BUCKET_KEYS.lock().unwrap().get(bucket) {
+            return Ok(key.clone());
+        }
+
+        let key = match EncryptionKey::load(bucket)? {
+            Some(key) => {
+                // A rekey elsewhere leaves this machine with a key nothing should be sealed with anymore
+                let current = Self::object_metadata(bucket, ENCRYPTION_PARAMS_KEY)?
+                    .and_then(|mut metadata| metadata.remove(SALT_METADATA));
+                if let Some(salt) = current
+                    && salt != key.salt()
+                    && key.retired.iter().all(|retired| retired.salt != salt)
+                {
+                    return Err(format!(
+                        "The key of bucket '{}' was changed. Unlock it again with 'rusty-sync encryption unlock'",
+                        bucket
+                    ));
+                }
+                Some(Arc::new(key))
+            }
+            None if Self::object_etag(bucket, ENCRYPTION_PARAMS_KEY)?.is_some() => {
+                return Err(format!(
+                    "Bucket '{}' is encrypted. Unlock it with 'rusty-sync encryption unlock'",
+                    bucket
+                ));
+            }
+            None => None,
+        };
+        BUCKET_KEYS.lock().unwrap().insert(bucket.to_string(), key.clone());
+        Ok(key)
+    }
+
+    /// Object key a relative path is stored under, encrypted in buckets with encrypted names
+    fn remote_name(bucket: &str, object_name: &str) -> Result<String, String> {
+        Ok(match Self::object_key(bucket, object_name)? {
+            Some(key) => key.object_name(object_name),
+            None => object_name.to_string(),
+        })
+    }
+
+    /// Drop the cached key lookup of a bucket after its key changed
+    pub fn forget_bucket_key(bucket: &str) {
+        BUCKET_KEYS.lock().unwrap().remove(bucket);
+    }
+
+    /// Look a bucket's key up again, checking it against the bucket's current parameters
+    /// Watches and the daemon call this before every push, so a rekey or an encryption enabled elsewhere
+    /// stops their uploads instead of sealing with a stale key or uploading in plaintext
+    pub fn refresh_bucket_key(bucket: &str) -> Result<(), String> {
+        Self::forget_bucket_key(bucket);
+        Self::bucket_key(bucket).map(|_| ())
+    }
+
+    /// Delete a file from MinIO
+    pub fn delete_file(bucket: &str, object_name: &str) -> Result<(), String> {
+        check_transfer_deadline()?;
+        let remote_name = Self::remote_name(bucket, object_name)?;
+        let output = run_minio(|command| {
+            command
+                .arg("--delete")
+                .arg(bucket)
+                .arg(&remote_name);
+        })?;
 
         if output.status.success() {
-            println!("Successfully downloaded {} from bucket {}", object_name, bucket);
+            println!("Successfully deleted {} from bucket {}", object_name, bucket);
             Ok(())
         } else {
             Err(format!(
@@ -190,18 +559,122 @@ impl MinioUtil {
         }
     }
 
-    /// List all buckets available on the MinIO server
-    pub fn list_buckets() -> Result<Vec<String>, String> {
-        setup_minio_env()?; // Setup config before running Python
-        
+    /// ETag of an object, None if it doesn't exist
+    pub fn object_etag(bucket: &str, object_name: &str) -> Result<Option<String>, String> {
+        Ok(Self::head_object(bucket, object_name)?.and_then(|head| head["etag"].as_str().map(str::to_string)))
+    }
+
+    /// User metadata of an object, None if it doesn't exist
+    pub fn object_metadata(bucket: &str, object_name: &str) -> Result<Option<BTreeMap<String, String>>, String> {
+        Self::head_object(bucket, object_name)?
+            .map(|head| serde_json::from_value(head["metadata"].clone()))
+            .transpose()
+            .map_err(|e| format!("Invalid head response: {}", e))
+    }
+
+    /// ETag, size and metadata of an object, None if it doesn't exist
+    fn head_object(bucket: &str, object_name: &str) -> Result<Option<serde_json::Value>, String> {
+        let remote_name = Self::remote_name(bucket, object_name)?;
+        let output = run_minio(|command| {
+            command
+                .arg("--head")
+                .arg(bucket)
+                .arg(&remote_name);
+        })?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Python script error: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for line in stdout.lines() {
+            if let Some(json) = line.strip_prefix("Head: ") {
+                return serde_json::from_str(json)
+                    .map(Some)
+                    .map_err(|e| format!("Invalid head response: {}", e));
+            }
+            if line.starts_with("Head missing") {
+                return Ok(None);
+            }
+        }
+        Err(format!("Unexpected head response: {}", stdout.trim()))
+    }
+
+    /// Start listening for bucket notifications (a MinIO extension)
+    /// The child prints "Event: <name> <key>" per event and "Listen failed: ..." when it stops
+    pub fn listen_bucket_notifications(bucket: &str) -> Result<Child, String> {
+        let env = setup_minio_env()?; // Setup config before running Python
+
         let (minio_dir, python_exe, main_py) = find_minio_scripts()?;
-        
-        let output = Command::new(&python_exe)
+
+        Command::new(&python_exe)
+            .envs(env.vars)
             .current_dir(&minio_dir)
             .arg(&main_py)
-            .arg("--list-buckets")
-            .output()
-            .map_err(|e| format!("Failed to execute python script: {}", e))?;
+            .arg("--listen")
+            .arg(bucket)
+            .stdin(Stdio::piped()) // Closes when we exit, which stops the listener
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to execute python script: {}", e))
+    }
+
+    /// Check the configured server step by step: reachability, credentials and,
+    /// with a bucket, listing it and writing and deleting a probe object
+    /// Checks stop at the first failure, which is the last one returned
+    pub fn probe(bucket: Option<&str>) -> Result<Vec<ProbeCheck>, String> {
+        let output = run_minio(|command| {
+            command.arg("--probe");
+            if let Some(bucket) = bucket {
+                command.arg(bucket);
+            }
+        })?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Python script error: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let checks = stdout
+            .lines()
+            .filter_map(|line| line.strip_prefix("Probe: "))
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<ProbeCheck>, _>>()
+            .map_err(|e| format!("Invalid probe response: {}", e))?;
+        if checks.is_empty() {
+            return Err(format!("Unexpected probe response: {}", stdout.trim()));
+        }
+        Ok(checks)
+    }
+
+    /// The interpreter and scripts the bridge would run, and whether boto3 imports there
+    pub fn python_setup() -> Result<PythonSetup, String> {
+        let (minio_dir, python, main_py) = find_minio_scripts()?;
+        let output = Command::new(&python)
+            .current_dir(&minio_dir)
+            .arg("-c")
+            .arg("import boto3; print(boto3.__version__)")
+            .output();
+        let boto3_version = match output {
+            Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+            Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().lines().last().unwrap_or("").to_string()),
+            Err(e) => Err(format!("Failed to run {}: {}", python.display(), e)),
+        };
+        Ok(PythonSetup { python, main_py, boto3_version })
+    }
+
+    /// List all buckets available on the MinIO server
+    pub fn list_buckets() -> Result<Vec<String>, String> {
+        let output = run_minio(|command| {
+            command
+                .arg("--list-buckets");
+        })?;
 
         if output.status.success() {
             let stdout = String::from_utf8_lossy(&output.stdout);
@@ -239,17 +712,11 @@ impl MinioUtil {
 
     /// List all files in a bucket
     pub fn list_files_in_bucket(bucket: &str) -> Result<Vec<String>, String> {
-        setup_minio_env()?; // Setup config before running Python
-        
-        let (minio_dir, python_exe, main_py) = find_minio_scripts()?;
-        
-        let output = Command::new(&python_exe)
-            .current_dir(&minio_dir)
-            .arg(&main_py)
-            .arg("--list")
-            .arg(bucket)
-            .output()
-            .map_err(|e| format!("Failed to execute python script: {}", e))?;
+        let output = run_minio(|command| {
+            command
+                .arg("--list")
+                .arg(bucket);
+        })?;
 
         if output.status.success() {
             let stdout = String::from_utf8_lossy(&output.stdout);
@@ -273,8 +740,27 @@ impl MinioUtil {
                 .flatten()
                 .filter(|file| !file.is_empty())
                 .collect();
-            
-            Ok(files)
+
+            // Translate encrypted object names back to relative paths
+            let Some(key) = Self::bucket_key(bucket)?.filter(|key| key.names.is_some()) else {
+                return Ok(files);
+            };
+            let total = files.len();
+            let paths: Vec<String> = files
+                .into_iter()
+                .filter_map(|file| match stored_in_plaintext(&file) {
+                    true => Some(file),
+                    false => key.path_of(&file),
+                })
+                .collect();
+            if paths.len() < total {
+                eprintln!(
+                    "Warning: Ignoring {} object(s) in '{}' whose names aren't encrypted with its key",
+                    total - paths.len(),
+                    bucket
+                );
+            }
+            Ok(paths)
         } else {
             Err(format!(
                 "Python script error: {}",
@@ -300,3 +786,35 @@ impl MinioTests {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_within_kills_hung_command() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo started; sleep 30"]);
+        let started = Instant::now();
+        assert!(output_within(command, Some(Duration::from_millis(300))).is_err());
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo done"]);
+        let output = output_within(command, Some(Duration::from_secs(10))).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "done");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expired_token_only_from_the_error_line() {
+        let run = |script: &str| Command::new("sh").args(["-c", script]).output().unwrap();
+
+        assert!(is_expired_token(&run("echo 'Error code: expired-token' >&2; exit 1")));
+        // Listings that merely mention the codes are not failures
+        assert!(!is_expired_token(&run("echo 'ExpiredToken.txt'; echo 'Error code: expired-token'")));
+        assert!(!is_expired_token(&run("echo 'Error code: expired-token' >&2")));
+        assert!(!is_expired_token(&run("echo 'Download failed: ExpiredToken' >&2; exit 1")));
+    }
+}
@@ -1 +1,2 @@
 pub mod minio_util;
+pub mod encryption;
@@ -1,6 +1,7 @@
 pub mod test;
 use rusty_sync::cli::interface_init::InitInterface;
 
+#[allow(clippy::needless_return)]
 fn main() {
     // Check if we're running as a CLI tool
     let args: std::vec::Vec<String> = std::env::args().collect();
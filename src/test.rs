@@ -51,6 +51,7 @@ pub fn test_tree_gen() {
     }
 }
 
+#[allow(clippy::useless_format)]
 pub fn test_tree_gen_to_json(save_in_target: bool) {
     // Use test directory for cross-platform testing  
     let folder_path = "test";
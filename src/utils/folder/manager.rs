@@ -1,5 +1,6 @@
 // this module will manage folders, create, delete and list folders
 
+use crate::utils::walker::{DirWalker, WalkOptions};
 use std::fs;
 use std::path::Path;
 use std::io;
@@ -44,16 +45,18 @@ impl FolderManager {
     }
 
     pub fn traverse_folder(path: &str) -> io::Result<Vec<String>> {
-        let mut entries = Vec::new();
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                entries.push(path.to_string_lossy().to_string());
-                entries.extend(Self::traverse_folder(&path.to_string_lossy())?);
-            } else {
-                entries.push(path.to_string_lossy().to_string());
-            }
+        // Fail only if the folder itself can't be read, report anything below it and keep going
+        fs::read_dir(path)?;
+
+        let mut walker = DirWalker::new(WalkOptions::default());
+        let entries = walker
+            .walk(Path::new(path))
+            .into_iter()
+            .map(|entry| entry.path.to_string_lossy().to_string())
+            .collect();
+
+        for error in walker.errors() {
+            eprintln!("Error reading {}", error);
         }
         Ok(entries)
     }
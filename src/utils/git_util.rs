@@ -1,16 +1,212 @@
 // this module detect for a .git directory and ignores it so we dont backup redunant data
 // it will also check if the current directory is a git repository
 
-use std::path::{Path, PathBuf};
+use crate::utils::path_safety::key_to_local;
+use crate::utils::tree_serializer::{git_bundle_key, FileNode};
+use crate::utils::walker::{DirWalker, WalkError, WalkOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// How an embedded git repository inside a sync folder is handled
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum GitRepoMode {
+    /// Collapse the repo to a single node with its remote and no content
+    #[default]
+    Skip,
+    /// Record the remote, current branch and HEAD commit
+    RemoteOnly,
+    /// Upload a `git bundle` containing all refs
+    Bundle,
+    /// Sync the working tree, honoring the repo's `.gitignore`
+    Worktree,
+}
+
+impl GitRepoMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GitRepoMode::Skip => "skip",
+            GitRepoMode::RemoteOnly => "remote-only",
+            GitRepoMode::Bundle => "bundle",
+            GitRepoMode::Worktree => "worktree",
+        }
+    }
+}
+
+impl fmt::Display for GitRepoMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for GitRepoMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(GitRepoMode::Skip),
+            "remote-only" => Ok(GitRepoMode::RemoteOnly),
+            "bundle" => Ok(GitRepoMode::Bundle),
+            "worktree" => Ok(GitRepoMode::Worktree),
+            _ => Err(format!(
+                "Unknown git mode '{}'. Use skip, remote-only, bundle or worktree",
+                s
+            )),
+        }
+    }
+}
+
+/// On-disk layout of a git repository
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum GitRepoKind {
+    /// Regular checkout with a `.git` directory
+    #[default]
+    Standard,
+    /// Linked worktree, `.git` is a file pointing into `<main>/.git/worktrees/`
+    LinkedWorktree,
+    /// Submodule checkout, `.git` is a file pointing into `<super>/.git/modules/`
+    Submodule,
+    /// Bare repository without a working tree
+    Bare,
+}
+
+impl GitRepoKind {
+    /// The mode that actually applies to a repo of this kind when `requested` is configured
+    pub fn effective_mode(&self, requested: GitRepoMode) -> GitRepoMode {
+        match (self, requested) {
+            // A bare repo has no working tree to sync, its content only fits in a bundle
+            (GitRepoKind::Bare, GitRepoMode::Worktree) => GitRepoMode::Bundle,
+            _ => requested,
+        }
+    }
+}
+
+impl fmt::Display for GitRepoKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GitRepoKind::Standard => "standard",
+            GitRepoKind::LinkedWorktree => "worktree",
+            GitRepoKind::Submodule => "submodule",
+            GitRepoKind::Bare => "bare",
+        })
+    }
+}
+
+/// Git metadata recorded on a repository node in the file tree
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GitRepoInfo {
+    pub mode: GitRepoMode,      // Handling mode that applied to this repo
+    #[serde(default)]
+    pub kind: GitRepoKind, // Standard checkout, linked worktree, submodule or bare
+    #[serde(default)]
+    pub git_dir: Option<String>, // Resolved git directory
+    pub branch: Option<String>, // Current branch (None when HEAD is detached)
+    pub head: Option<String>,   // HEAD commit id
+    #[serde(default)]
+    pub remotes: BTreeMap<String, String>, // Remote name -> URL
+    #[serde(default)]
+    pub dirty: Option<bool>, // Uncommitted or untracked changes in the working tree
+    #[serde(default)]
+    pub ahead: Option<u32>, // Commits not yet on the upstream branch
+    #[serde(default)]
+    pub behind: Option<u32>, // Upstream commits not yet merged locally
+    #[serde(default)]
+    pub unpushed: Option<u32>, // Commits on local branches that no remote has
+}
+
+impl GitRepoInfo {
+    /// Check if this repo has commits that exist only on this machine and are not in a bundle
+    pub fn has_local_only_commits(&self) -> bool {
+        self.mode != GitRepoMode::Bundle && self.unpushed.unwrap_or(0) > 0
+    }
+}
+
+/// Run a git command inside a repository and return trimmed stdout on success
+fn git_output(repo_path: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
 
 pub struct GitDetector;
 
 impl GitDetector {
     // Check if the given path is a git repository, input if a folder path
     pub fn is_git_repository(path: &Path) -> bool {
+        Self::resolve_git_dir(path).is_some()
+    }
+
+    /// Find the real git directory for a folder: `.git` itself, the target of a `gitdir:` file, or the folder for bare repos
+    pub fn resolve_git_dir(path: &Path) -> Option<PathBuf> {
         let git_path = path.join(".git");
-        git_path.exists() && git_path.is_dir()
+
+        if git_path.is_dir() {
+            return Some(git_path);
+        }
+
+        if git_path.is_file() {
+            // Linked worktrees and submodules use a file containing "gitdir: <path>"
+            let content = fs::read_to_string(&git_path).ok()?;
+            let target = content.lines().next()?.strip_prefix("gitdir:")?.trim();
+            let git_dir = path.join(target); // join keeps absolute targets as they are
+            return git_dir.is_dir().then_some(git_dir);
+        }
+
+        if Self::is_bare_repository(path) {
+            return Some(path.to_path_buf());
+        }
+
+        None
+    }
+
+    /// Check if a folder is a bare repository (HEAD, objects and refs at the top level)
+    pub fn is_bare_repository(path: &Path) -> bool {
+        path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+    }
+
+    /// Work out which layout a repository uses, None if it is not a repository
+    pub fn get_repo_kind(path: &Path) -> Option<GitRepoKind> {
+        let git_dir = Self::resolve_git_dir(path)?;
+
+        if git_dir == path {
+            return Some(GitRepoKind::Bare);
+        }
+
+        if path.join(".git").is_file() {
+            let parent_name = git_dir
+                .parent()
+                .and_then(|parent| parent.file_name())
+                .map(|name| name.to_string_lossy().to_string());
+
+            // Worktrees live in .git/worktrees/<name>
+            if parent_name.as_deref() == Some("worktrees") {
+                return Some(GitRepoKind::LinkedWorktree);
+            }
+            // Only git knows whether a superproject tracks this one, a git dir path can be anywhere
+            if git_output(path, &["rev-parse", "--show-superproject-working-tree"]).is_some_and(|superproject| !superproject.is_empty()) {
+                return Some(GitRepoKind::Submodule);
+            }
+        }
+
+        Some(GitRepoKind::Standard)
     }
     pub fn print_directory(path: &Path) {
         if path.exists() {
@@ -20,43 +216,341 @@ impl GitDetector {
         }
     }
 
+    /// Get remote URLs, "origin" first, as resolved by git itself
     pub fn get_git_remotes(repo_path: &Path) -> Vec<String> {
-        let git_config_path = repo_path.join(".git").join("config");
-        if !git_config_path.exists() {
-            return Vec::new();
-        }
-
-        let content = fs::read_to_string(git_config_path).unwrap_or_else(|_| String::new());
-        let mut urls = Vec::new();
-        let mut in_remote_section = false;
-
-        for line in content.lines() {
-            let line = line.trim();
-            if line.starts_with("[remote") {
-                in_remote_section = true;
-            } else if line.starts_with('[') {
-                in_remote_section = false;
-            } else if in_remote_section && line.starts_with("url =") {
-                if let Some(url) = line.split('=').nth(1) {
-                    urls.push(url.trim().to_string());
+        let mut remotes = Self::get_remote_map(repo_path);
+        let mut urls: Vec<String> = remotes.remove("origin").into_iter().collect();
+        urls.extend(remotes.into_values());
+        urls
+    }
+
+    /// Map of remote name to URL, honoring include files and other config git reads
+    pub fn get_remote_map(repo_path: &Path) -> BTreeMap<String, String> {
+        let output = git_output(repo_path, &["config", "--get-regexp", r"^remote\..*\.url$"])
+            .unwrap_or_default();
+
+        output
+            .lines()
+            .filter_map(|line| {
+                let (key, url) = line.split_once(' ')?;
+                let name = key.strip_prefix("remote.")?.strip_suffix(".url")?;
+                Some((name.to_string(), url.trim().to_string()))
+            })
+            .collect()
+    }
+
+    /// Check if the working tree has uncommitted or untracked changes
+    pub fn is_dirty(repo_path: &Path) -> Option<bool> {
+        git_output(repo_path, &["status", "--porcelain"]).map(|status| !status.is_empty())
+    }
+
+    /// Count commits (ahead, behind) relative to the branch's upstream, None without one
+    pub fn get_ahead_behind(repo_path: &Path) -> Option<(u32, u32)> {
+        let counts = git_output(
+            repo_path,
+            &["rev-list", "--left-right", "--count", "HEAD...@{upstream}"],
+        )?;
+        let mut parts = counts.split_whitespace().map(|n| n.parse::<u32>().ok());
+        Some((parts.next()??, parts.next()??))
+    }
+
+    /// Count commits on local branches that are not on any remote, i.e. exist only on this machine
+    pub fn count_unpushed_commits(repo_path: &Path) -> Option<u32> {
+        git_output(repo_path, &["rev-list", "--count", "--branches", "--not", "--remotes"])?
+            .parse()
+            .ok()
+    }
+
+    /// Get the currently checked out branch, None for a detached HEAD
+    pub fn get_current_branch(repo_path: &Path) -> Option<String> {
+        git_output(repo_path, &["symbolic-ref", "--quiet", "--short", "HEAD"])
+            .filter(|branch| !branch.is_empty())
+    }
+
+    /// Get the commit id HEAD points to, None for a repo without commits
+    pub fn get_head_commit(repo_path: &Path) -> Option<String> {
+        git_output(repo_path, &["rev-parse", "--verify", "--quiet", "HEAD"])
+            .filter(|head| !head.is_empty())
+    }
+
+    /// Collect the metadata recorded for a repository handled with the given mode
+    pub fn get_repo_info(repo_path: &Path, requested_mode: GitRepoMode) -> GitRepoInfo {
+        let kind = Self::get_repo_kind(repo_path).unwrap_or_default();
+        let mode = kind.effective_mode(requested_mode);
+
+        let (ahead, behind) = match Self::get_ahead_behind(repo_path) {
+            Some((ahead, behind)) => (Some(ahead), Some(behind)),
+            None => (None, None),
+        };
+
+        // Remotes and unpushed work are always recorded so status can warn about them
+        let mut info = GitRepoInfo {
+            mode,
+            kind,
+            git_dir: Self::resolve_git_dir(repo_path).map(|dir| dir.to_string_lossy().to_string()),
+            remotes: Self::get_remote_map(repo_path),
+            dirty: Self::is_dirty(repo_path),
+            ahead,
+            behind,
+            unpushed: Self::count_unpushed_commits(repo_path),
+            ..Default::default()
+        };
+
+        // Skip keeps the old behaviour of not recording checkout state
+        if mode != GitRepoMode::Skip {
+            info.branch = Self::get_current_branch(repo_path);
+            info.head = Self::get_head_commit(repo_path);
+        }
+
+        info
+    }
+
+    /// Fingerprint of every ref in the repo, changes whenever a commit or branch changes
+    pub fn get_refs_fingerprint(repo_path: &Path) -> Option<String> {
+        let refs = git_output(repo_path, &["show-ref", "--head"])?;
+
+        // Let git hash the listing so the fingerprint is stable across machines
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(repo_path)
+            .args(["hash-object", "--stdin"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        child.stdin.take()?.write_all(refs.as_bytes()).ok()?;
+        let output = child.wait_with_output().ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Write a bundle of all refs in the repository to `bundle_path`
+    pub fn create_bundle(repo_path: &Path, bundle_path: &Path) -> Result<(), String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(repo_path)
+            .args(["bundle", "create"])
+            .arg(bundle_path)
+            .arg("--all")
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "git bundle failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    /// List the working tree files git would not ignore (tracked plus untracked, minus `.gitignore` matches)
+    pub fn list_worktree_files(repo_path: &Path) -> Option<HashSet<PathBuf>> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(repo_path)
+            .args(["ls-files", "--cached", "--others", "--exclude-standard", "-z"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        let files = String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| repo_path.join(entry))
+            .collect();
+        Some(files)
+    }
+}
+/// Re-creates git repositories recorded in a remote tree on another machine
+pub struct GitRestorer;
+
+impl GitRestorer {
+    /// Clone every repository recorded in `tree` that is missing under `local_root`
+    /// Returns the relative path of each repo together with the outcome
+    pub fn restore_repositories(
+        tree: &FileNode,
+        local_root: &Path,
+    ) -> Vec<(String, Result<bool, String>)> {
+        tree.get_git_repositories()
+            .into_iter()
+            .map(|repo| {
+                let result = Self::restore_repository(repo, local_root);
+                (repo.relative_path.clone(), result)
+            })
+            .collect()
+    }
+
+    /// Restore a single repository node, Ok(false) means it was left alone
+    pub fn restore_repository(repo: &FileNode, local_root: &Path) -> Result<bool, String> {
+        let target = if repo.relative_path == "." {
+            local_root.to_path_buf()
+        } else {
+            local_root.join(key_to_local(&repo.relative_path))
+        };
+
+        // Repos that already exist are never touched
+        if GitDetector::is_git_repository(&target) {
+            return Ok(false);
+        }
+
+        // Only the sync root may hold other files, git refuses to clone into a non-empty folder
+        let is_empty = fs::read_dir(&target)
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(true);
+        if !is_empty && repo.relative_path != "." {
+            return Err(format!(
+                "'{}' already exists and is not a git repository",
+                target.display()
+            ));
+        }
+
+        // Prefer a downloaded bundle since it also carries commits that were never pushed
+        let bundle_path = local_root.join(key_to_local(&git_bundle_key(&repo.relative_path)));
+        let source = if bundle_path.exists() {
+            bundle_path.to_string_lossy().to_string()
+        } else {
+            repo.git_remote
+                .clone()
+                .ok_or_else(|| "No remote or bundle recorded for this repository".to_string())?
+        };
+
+        let git_info = repo.git_info.as_ref();
+
+        // Bare repos are restored bare, there is no branch to check out
+        if git_info.is_some_and(|git| git.kind == GitRepoKind::Bare) {
+            return Self::clone_bare_repository(&source, &target).map(|_| true);
+        }
+
+        Self::clone_repository(
+            &source,
+            &target,
+            git_info.and_then(|git| git.branch.as_deref()),
+            git_info.and_then(|git| git.head.as_deref()),
+        )?;
+
+        // A repo restored from a bundle should still point at its real remote
+        if bundle_path.exists()
+            && let Some(remote) = &repo.git_remote
+        {
+            run_git(&target, &["remote", "set-url", "origin", remote])?;
+        }
+
+        Ok(true)
+    }
+
+    /// Clone `source` into `target` as a bare repository
+    pub fn clone_bare_repository(source: &str, target: &Path) -> Result<(), String> {
+        let output = Command::new("git")
+            .args(["clone", "--quiet", "--bare", source])
+            .arg(target)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "git clone failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    /// Clone `source` into `target` and check out the recorded branch, or HEAD when detached
+    pub fn clone_repository(
+        source: &str,
+        target: &Path,
+        branch: Option<&str>,
+        head: Option<&str>,
+    ) -> Result<(), String> {
+        fs::create_dir_all(target)
+            .map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+
+        if fs::read_dir(target).map(|mut e| e.next().is_some()).unwrap_or(false) {
+            // The sync root already holds files, so initialise in place instead of cloning
+            run_git(target, &["init", "--quiet"])?;
+            run_git(target, &["remote", "add", "origin", source])?;
+            run_git(target, &["fetch", "--quiet", "origin"])?;
+        } else {
+            let output = Command::new("git")
+                .args(["clone", "--quiet", source])
+                .arg(target)
+                .stdin(Stdio::null())
+                .output()
+                .map_err(|e| format!("Failed to run git: {}", e))?;
+
+            if !output.status.success() {
+                return Err(format!(
+                    "git clone failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+        }
+
+        match (branch, head) {
+            (Some(branch), _) => {
+                // The branch may only exist on the remote, let git create the tracking branch
+                if GitDetector::get_current_branch(target).as_deref() != Some(branch) {
+                    run_git(target, &["checkout", "--quiet", branch])?;
                 }
             }
+            (None, Some(head)) => run_git(target, &["checkout", "--quiet", "--detach", head])?,
+            (None, None) => {}
         }
-        urls
+
+        Ok(())
     }
 }
+
+/// Run a git command inside a repository, turning a failure into an error message
+fn run_git(repo_path: &Path, args: &[&str]) -> Result<(), String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
 // a struct needs to be created that will climb a directroy for all the .git directories and return a vector of paths
 // these paths will be ignored including their contents
 // a high directory like documents is given which is then scanned through for all the .gits
 
+#[derive(Default)]
 pub struct GitIgnore {
     pub ignored_paths: Vec<PathBuf>,
+    pub errors: Vec<WalkError>, // Folders that could not be read while searching
 }
 
 impl GitIgnore {
     pub fn new() -> Self {
         GitIgnore {
             ignored_paths: Vec::new(),
+            errors: Vec::new(),
         }
     }
 
@@ -66,37 +560,266 @@ impl GitIgnore {
             return;
         }
 
+        let mut walker = DirWalker::new(WalkOptions::default());
+        self.search(&mut walker, base_path, 0);
+        self.errors.extend(walker.take_errors());
+    }
+
+    fn search(&mut self, walker: &mut DirWalker, path: &Path, depth: usize) {
         // If this directory is already ignored, skip it
-        if self.ignored_paths.contains(&base_path.to_path_buf()) {
+        if self.ignored_paths.contains(&path.to_path_buf()) {
             return;
         }
 
         // Check if the current directory is a git repository
-        if GitDetector::is_git_repository(base_path) {
-            self.ignored_paths.push(base_path.to_path_buf());
+        if GitDetector::is_git_repository(path) {
+            self.ignored_paths.push(path.to_path_buf());
             // Do not recurse into this directory
             return;
         }
 
-        for entry in fs::read_dir(base_path).unwrap_or_else(|_| panic!("Failed to read directory: {}", base_path.display())) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.is_dir() {
-                    // Recursively search in subdirectories
-                    self.find_git_directories(&path);
-                }
-            } else {
-                eprintln!("Failed to read entry in directory: {}", base_path.display());
+        let _ = walker.descend(path, depth, |walker, entries| {
+            for entry in entries.into_iter().filter(|entry| entry.is_dir) {
+                // Recursively search in subdirectories
+                self.search(walker, &entry.path, depth + 1);
             }
-        }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::folder_settings::FolderSettings;
+    use crate::utils::tree_serializer::ReadFileTree;
+    use crate::utils::temp_dir;
+
+    fn git(repo: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_restore_repository_from_file_remote() {
+        let base = temp_dir("restore");
+
+        // Upstream repo with a second branch that the sync folder has checked out
+        let upstream = base.join("upstream");
+        fs::create_dir_all(&upstream).unwrap();
+        git(&upstream, &["init", "--quiet", "-b", "main"]);
+        fs::write(upstream.join("readme.txt"), "hello").unwrap();
+        git(&upstream, &["add", "."]);
+        git(&upstream, &["commit", "--quiet", "-m", "initial"]);
+        git(&upstream, &["branch", "feature"]);
+
+        let source = base.join("source");
+        let repo = source.join("projects/app");
+        fs::create_dir_all(&repo).unwrap();
+        let remote = format!("file://{}", upstream.display());
+        git(&source, &["clone", "--quiet", &remote, "projects/app"]);
+        git(&repo, &["checkout", "--quiet", "feature"]);
+
+        let settings = FolderSettings {
+            git_mode: GitRepoMode::RemoteOnly,
+            ..Default::default()
+        };
+        let tree =
+            ReadFileTree::generate_tree_with_settings(&source.to_string_lossy(), "test", &settings)
+                .unwrap();
+
+        // Restoring on a fresh machine clones and checks out the recorded branch
+        let target = base.join("target");
+        fs::create_dir_all(&target).unwrap();
+        let results = GitRestorer::restore_repositories(&tree, &target);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "projects/app");
+        assert_eq!(results[0].1, Ok(true));
+
+        let restored = target.join("projects/app");
+        assert!(restored.join("readme.txt").exists());
+        assert_eq!(
+            GitDetector::get_current_branch(&restored).as_deref(),
+            Some("feature")
+        );
+
+        // A second restore leaves the existing repository alone
+        let results = GitRestorer::restore_repositories(&tree, &target);
+        assert_eq!(results[0].1, Ok(false));
+
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_repo_info_reads_includes_and_unpushed_commits() {
+        let base = temp_dir("info");
+        let repo = base.join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "--quiet", "-b", "main"]);
+
+        // Remotes defined in an included file are still found
+        fs::write(
+            base.join("remotes.inc"),
+            "[remote \"backup\"]\n\turl = https://example.com/backup.git\n",
+        )
+        .unwrap();
+        git(&repo, &["config", "include.path", &base.join("remotes.inc").to_string_lossy()]);
+        git(&repo, &["remote", "add", "origin", "https://example.com/origin.git"]);
+
+        fs::write(repo.join("a.txt"), "a").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "--quiet", "-m", "local only"]);
+        fs::write(repo.join("b.txt"), "b").unwrap();
+
+        let info = GitDetector::get_repo_info(&repo, GitRepoMode::RemoteOnly);
+        assert_eq!(info.remotes.len(), 2);
+        assert_eq!(
+            info.remotes.get("backup").map(String::as_str),
+            Some("https://example.com/backup.git")
+        );
+        assert_eq!(info.branch.as_deref(), Some("main"));
+        assert_eq!(info.dirty, Some(true));
+        assert_eq!(info.unpushed, Some(1));
+        assert!(info.has_local_only_commits());
+        assert_eq!(
+            GitDetector::get_git_remotes(&repo).first().map(String::as_str),
+            Some("https://example.com/origin.git")
+        );
+
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_detects_worktrees_submodules_and_bare_repos() {
+        let base = temp_dir("kinds");
+        let sync_root = base.join("sync");
+        let main = sync_root.join("main");
+        fs::create_dir_all(&main).unwrap();
+        git(&main, &["init", "--quiet", "-b", "main"]);
+        fs::write(main.join("a.txt"), "a").unwrap();
+        git(&main, &["add", "."]);
+        git(&main, &["commit", "--quiet", "-m", "initial"]);
+
+        git(&main, &["worktree", "add", "--quiet", "../linked", "-b", "other"]);
+        git(&sync_root, &["clone", "--quiet", "--bare", "main", "bare.git"]);
+        let library = base.join("library");
+        git(&base, &["clone", "--quiet", "--bare", "sync/main", "library"]);
+        let library = library.to_string_lossy();
+        let submodule_args = ["-c", "protocol.file.allow=always", "submodule", "add", "--quiet"];
+        git(&main, &[&submodule_args[..], &[library.as_ref(), "lib"]].concat());
+
+        assert_eq!(GitDetector::get_repo_kind(&main), Some(GitRepoKind::Standard));
+        assert_eq!(
+            GitDetector::get_repo_kind(&sync_root.join("linked")),
+            Some(GitRepoKind::LinkedWorktree)
+        );
+        assert_eq!(GitDetector::get_repo_kind(&main.join("lib")), Some(GitRepoKind::Submodule));
+        assert_eq!(
+            GitDetector::get_repo_kind(&sync_root.join("bare.git")),
+            Some(GitRepoKind::Bare)
+        );
+        assert_eq!(GitDetector::get_repo_kind(&base), None);
+
+        // A git dir kept in some "modules" folder doesn't make a repository a submodule
+        let separate = sync_root.join("separate");
+        fs::create_dir_all(&separate).unwrap();
+        let git_dir = base.join("modules/separate.git");
+        fs::create_dir_all(base.join("modules")).unwrap();
+        git(&separate, &["init", "--quiet", "--separate-git-dir", &git_dir.to_string_lossy()]);
+        assert_eq!(GitDetector::get_repo_kind(&separate), Some(GitRepoKind::Standard));
+
+        // Every layout becomes a repo node instead of a plain folder, bare repos fall back to bundles
+        let settings = FolderSettings {
+            git_mode: GitRepoMode::Worktree,
+            ..Default::default()
+        };
+        let tree = ReadFileTree::generate_tree_with_settings(
+            &sync_root.to_string_lossy(),
+            "test",
+            &settings,
+        )
+        .unwrap();
+        let repos: Vec<(String, GitRepoKind, GitRepoMode)> = tree
+            .get_git_repositories()
+            .into_iter()
+            .map(|repo| {
+                let git = repo.git_info.as_ref().unwrap();
+                (repo.relative_path.clone(), git.kind, git.mode)
+            })
+            .collect();
+        assert!(repos.contains(&("linked".to_string(), GitRepoKind::LinkedWorktree, GitRepoMode::Worktree)));
+        assert!(repos.contains(&("main/lib".to_string(), GitRepoKind::Submodule, GitRepoMode::Worktree)));
+        assert!(repos.contains(&("bare.git".to_string(), GitRepoKind::Bare, GitRepoMode::Bundle)));
+        assert!(repos.contains(&("separate".to_string(), GitRepoKind::Standard, GitRepoMode::Worktree)));
+        let linked_git_file = sync_root.join("linked/.git");
+        assert!(tree.find_by_path(&linked_git_file.to_string_lossy()).is_none());
+
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_bundle_and_worktree_modes() {
+        let base = temp_dir("modes");
+        let repo = base.join("projects/app");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "--quiet", "-b", "main"]);
+        fs::write(repo.join(".gitignore"), "build/\n").unwrap();
+        fs::write(repo.join("main.rs"), "fn main() {}").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "--quiet", "-m", "initial"]);
+        fs::create_dir_all(repo.join("build")).unwrap();
+        fs::write(repo.join("build/out.o"), "binary").unwrap();
+
+        let root = base.to_string_lossy().to_string();
+        let scan = |git_mode| {
+            let settings = FolderSettings {
+                git_mode,
+                ..Default::default()
+            };
+            let tree = ReadFileTree::generate_tree_with_settings(&root, "test", &settings).unwrap();
+            (tree, settings)
+        };
+
+        // Bundles live under the metadata dir, keyed by the repo's path
+        assert_eq!(git_bundle_key("projects/app"), ".rusty-sync/bundles/projects/app/repo.bundle");
+        assert_eq!(git_bundle_key("."), ".rusty-sync/bundles/repo.bundle");
+
+        // A bundle-mode repo is a single node that needs uploading until its refs are synced
+        let (mut tree, settings) = scan(GitRepoMode::Bundle);
+        let node = tree.find_by_relative_path("projects/app").unwrap();
+        let fingerprint = node.hash.clone();
+        assert!(fingerprint.is_some());
+        assert_eq!(fingerprint, GitDetector::get_refs_fingerprint(&repo));
+        assert!(node.needs_sync());
+        assert!(tree.find_by_relative_path("projects/app/main.rs").is_none());
+        tree.find_by_relative_path_mut("projects/app").unwrap().last_synced = Some(1);
+
+        // Working files don't move the fingerprint, a new branch or commit does
+        fs::write(repo.join("main.rs"), "fn main() { println!(); }").unwrap();
+        assert_eq!(GitDetector::get_refs_fingerprint(&repo), fingerprint);
+        git(&repo, &["branch", "feature"]);
+        let branched = GitDetector::get_refs_fingerprint(&repo);
+        assert_ne!(branched, fingerprint);
+
+        ReadFileTree::rescan_paths(&mut tree, &root, &settings, &[repo.join(".git/refs/heads/feature")]);
+        let node = tree.find_by_relative_path("projects/app").unwrap();
+        assert_eq!(node.hash, branched);
+        assert!(node.needs_sync());
+
+        // Worktree mode syncs the files git tracks and leaves the ignored ones out
+        let (tree, _) = scan(GitRepoMode::Worktree);
+        let node = tree.find_by_relative_path("projects/app").unwrap();
+        assert!(!node.needs_sync());
+        assert!(tree.find_by_relative_path("projects/app/main.rs").is_some());
+        assert!(tree.find_by_relative_path("projects/app/build/out.o").is_none());
+        assert!(tree.find_by_relative_path("projects/app/.git").is_none());
+
+        let _ = fs::remove_dir_all(&base);
     }
-        //         if path.is_dir() {
-        //             if GitDetector::is_git_repository(&path) {
-        //                 self.ignored_paths.push(path.clone());
-        //             }
-        //             // Recursively search in subdirectories
-        //             self.find_git_directories(&path);
-        //         }
-        //     }
-        // }
 }
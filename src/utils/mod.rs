@@ -2,3 +2,18 @@ pub mod file;
 pub mod folder;
 pub mod tree_serializer;
 pub mod git_util;
+pub mod path_safety;
+pub mod remote_watcher;
+pub mod schedule;
+pub mod walker;
+pub mod watcher;
+pub mod xattr_util;
+
+/// Creates an empty scratch directory for a test, unique to this process.
+#[cfg(test)]
+pub(crate) fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("rusty-sync-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
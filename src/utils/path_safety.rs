@@ -0,0 +1,295 @@
+// checks file names for things that break on other platforms and maps object keys to local names
+// keys are the portable form, local names are escaped where the platform can't represent a key
+
+use crate::utils::tree_serializer::FileNode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+
+/// Names Windows reserves for devices, with or without an extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters Windows does not allow in file names
+const INVALID_CHARS: [char; 8] = ['<', '>', ':', '"', '\\', '|', '?', '*'];
+
+/// Whether this platform needs keys escaped before they become local file names
+const NEEDS_ESCAPING: bool = cfg!(windows);
+
+/// How object keys are Unicode-normalized
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyNormalization {
+    /// Compose to NFC, so names typed on macOS (NFD) and elsewhere map to one key
+    #[default]
+    Nfc,
+    /// Use names byte for byte as the filesystem reports them
+    None,
+}
+
+impl KeyNormalization {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyNormalization::Nfc => "nfc",
+            KeyNormalization::None => "none",
+        }
+    }
+
+    /// Apply the policy to a key
+    pub fn apply(&self, key: &str) -> String {
+        match self {
+            KeyNormalization::Nfc => key.nfc().collect(),
+            KeyNormalization::None => key.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for KeyNormalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for KeyNormalization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nfc" => Ok(KeyNormalization::Nfc),
+            "none" => Ok(KeyNormalization::None),
+            _ => Err(format!("Unknown key normalization '{}'. Use nfc or none", s)),
+        }
+    }
+}
+
+/// Why a name is not portable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameProblem {
+    CaseCollision(String),          // Differs from a sibling only in case
+    NormalizationCollision(String), // Same as a sibling once Unicode-normalized
+    ReservedName,                   // CON, NUL, COM1 and friends
+    InvalidCharacter(char),         // A character Windows refuses
+    TrailingDotOrSpace,             // Silently stripped by Windows
+}
+
+/// A file or folder whose name won't survive every platform
+#[derive(Debug, Clone)]
+pub struct NameIssue {
+    pub path: String,
+    pub problem: NameProblem,
+}
+
+impl fmt::Display for NameIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.problem {
+            NameProblem::CaseCollision(other) => {
+                write!(f, "{}: collides with '{}' on case-insensitive filesystems", self.path, other)
+            }
+            NameProblem::NormalizationCollision(other) => {
+                write!(f, "{}: same name as '{}' in a different Unicode normalization", self.path, other)
+            }
+            NameProblem::ReservedName => write!(f, "{}: reserved device name on Windows", self.path),
+            NameProblem::InvalidCharacter(c) => {
+                write!(f, "{}: contains '{}', which Windows does not allow", self.path, c.escape_default())
+            }
+            NameProblem::TrailingDotOrSpace => {
+                write!(f, "{}: ends in a dot or space, which Windows strips", self.path)
+            }
+        }
+    }
+}
+
+/// Check a single name for characters or forms that don't work everywhere
+pub fn check_name(name: &str) -> Option<NameProblem> {
+    if let Some(c) = name.chars().find(|c| INVALID_CHARS.contains(c) || c.is_control()) {
+        return Some(NameProblem::InvalidCharacter(c));
+    }
+
+    if is_reserved_name(name) {
+        return Some(NameProblem::ReservedName);
+    }
+
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Some(NameProblem::TrailingDotOrSpace);
+    }
+
+    None
+}
+
+fn is_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved))
+}
+
+/// Flag every name in the tree that would break or collide on another platform
+pub fn validate_tree(tree: &FileNode) -> Vec<NameIssue> {
+    let mut issues = Vec::new();
+    validate_children(tree, &mut issues);
+    issues
+}
+
+fn validate_children(node: &FileNode, issues: &mut Vec<NameIssue>) {
+    let Some(children) = &node.children else {
+        return;
+    };
+
+    let mut by_case: HashMap<String, &FileNode> = HashMap::new();
+    let mut by_form: HashMap<String, &FileNode> = HashMap::new();
+
+    for child in children {
+        if let Some(problem) = check_name(&child.name) {
+            issues.push(NameIssue {
+                path: child.relative_path.clone(),
+                problem,
+            });
+        }
+
+        let form: String = child.name.nfc().collect();
+        if let Some(other) = by_form.get(&form) {
+            issues.push(NameIssue {
+                path: child.relative_path.clone(),
+                problem: NameProblem::NormalizationCollision(other.name.clone()),
+            });
+        } else if let Some(other) = by_case.get(&form.to_lowercase()) {
+            issues.push(NameIssue {
+                path: child.relative_path.clone(),
+                problem: NameProblem::CaseCollision(other.name.clone()),
+            });
+        }
+
+        by_case.entry(form.to_lowercase()).or_insert(child);
+        by_form.entry(form).or_insert(child);
+
+        validate_children(child, issues);
+    }
+}
+
+/// Escape a key component into a name every platform can store
+/// Invalid characters, a trailing dot or space and a '%' that looks like an escape become %XX,
+/// reserved names get their first letter escaped
+pub fn escape_component(name: &str) -> String {
+    if check_name(name).is_none() && !has_escape_sequence(name) {
+        return name.to_string();
+    }
+
+    let chars: Vec<char> = name.chars().collect();
+    let mut escaped = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        let needs_escape = match c {
+            '%' => is_escape_sequence(&chars[i..]),
+            '.' | ' ' => i == chars.len() - 1,
+            _ => INVALID_CHARS.contains(c) || c.is_control(),
+        } || (i == 0 && is_reserved_name(name));
+
+        if needs_escape {
+            let mut buffer = [0; 4];
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            escaped.push(*c);
+        }
+    }
+    escaped
+}
+
+/// A '%' followed by two hex digits, which unescaping would decode
+fn is_escape_sequence(chars: &[char]) -> bool {
+    chars.len() >= 3 && chars[0] == '%' && chars[1].is_ascii_hexdigit() && chars[2].is_ascii_hexdigit()
+}
+
+fn has_escape_sequence(name: &str) -> bool {
+    let chars: Vec<char> = name.chars().collect();
+    (0..chars.len()).any(|i| is_escape_sequence(&chars[i..]))
+}
+
+/// Reverse `escape_component`, leaving anything that isn't a valid %XX sequence alone
+pub fn unescape_component(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = name.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).unwrap_or_else(|_| name.to_string())
+}
+
+/// Local path (relative to the sync root) an object key is stored under on this platform
+pub fn key_to_local(key: &str) -> PathBuf {
+    if !NEEDS_ESCAPING {
+        return PathBuf::from(key);
+    }
+    key.split('/').map(escape_component).collect()
+}
+
+/// Key component for a local file name on this platform
+pub fn local_to_key_component(name: &str) -> String {
+    if NEEDS_ESCAPING {
+        unescape_component(name)
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escaping_round_trips_unportable_names() {
+        for name in ["a:b", "CON", "nul.txt", "trailing.", "100%", "a%3Ab", "ok.txt", "com1.tar.gz"] {
+            let escaped = escape_component(name);
+            assert!(check_name(&escaped).is_none(), "{} -> {}", name, escaped);
+            assert_eq!(unescape_component(&escaped), name);
+        }
+        assert_eq!(escape_component("ok.txt"), "ok.txt");
+        assert_eq!(escape_component("100%.txt"), "100%.txt");
+        assert_eq!(escape_component("a:b"), "a%3Ab");
+        assert_eq!(escape_component("CON"), "%43ON");
+    }
+
+    #[test]
+    fn test_validate_tree_flags_collisions() {
+        let node = |name: &str| FileNode {
+            name: name.to_string(),
+            r#type: "file".to_string(),
+            path: name.to_string(),
+            relative_path: name.to_string(),
+            ..Default::default()
+        };
+        let mut root = node(".");
+        root.r#type = "folder".to_string();
+        root.children = Some(vec![
+            node("Readme.md"),
+            node("README.md"),
+            node("caf\u{e9}"),
+            node("cafe\u{301}"),
+            node("aux.c"),
+        ]);
+
+        let problems: Vec<NameProblem> = validate_tree(&root).into_iter().map(|i| i.problem).collect();
+        assert_eq!(
+            problems,
+            vec![
+                NameProblem::CaseCollision("Readme.md".to_string()),
+                NameProblem::NormalizationCollision("caf\u{e9}".to_string()),
+                NameProblem::ReservedName,
+            ]
+        );
+    }
+}
@@ -0,0 +1,207 @@
+// notices changes other machines push to a bucket
+// polls the ETag of the remote manifest, optionally woken early by MinIO bucket notifications
+
+use crate::core::minio_util::MinioUtil;
+use crate::utils::tree_serializer::{FileNode, MANIFEST_KEY, is_internal_path};
+use std::io::{BufRead, BufReader};
+use std::process::Child;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// Paths that differ between two versions of the remote manifest
+#[derive(Debug, Default, PartialEq)]
+pub struct RemoteChanges {
+    pub changed: Vec<String>, // Added or modified files and links
+    pub removed: Vec<String>, // Files and links another machine deleted (covered by a tombstone)
+}
+
+impl RemoteChanges {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Compare the manifest we last pulled or pushed with the current one
+pub fn diff_manifests(previous: Option<&FileNode>, current: &FileNode) -> RemoteChanges {
+    let mut changes = RemoteChanges::default();
+
+    for node in current.get_all_objects() {
+        if is_internal_path(&node.relative_path) {
+            continue;
+        }
+        let old = previous.and_then(|tree| tree.find_by_relative_path(&node.relative_path));
+        if old.is_none_or(|old| !same_object(old, node)) {
+            changes.changed.push(node.relative_path.clone());
+        }
+    }
+
+    if let Some(previous) = previous {
+        for node in previous.get_all_objects() {
+            if !is_internal_path(&node.relative_path)
+                && current.find_by_relative_path(&node.relative_path).is_none()
+                && current.is_deleted(&node.relative_path)
+            {
+                changes.removed.push(node.relative_path.clone());
+            }
+        }
+    }
+
+    changes
+}
+
+/// Two manifest entries describe the same object version (sync bookkeeping aside)
+fn same_object(a: &FileNode, b: &FileNode) -> bool {
+    a.r#type == b.r#type
+        && a.size == b.size
+        && a.modified == b.modified
+        && a.link_target == b.link_target
+        && a.mode == b.mode
+        && a.xattrs == b.xattrs
+}
+
+pub struct RemoteWatcher {
+    bucket: String,
+    interval: Duration,
+    handled_etag: Option<String>, // Manifest version that was last pulled
+    events: Option<Receiver<String>>,
+    listener: Option<Child>,
+}
+
+impl RemoteWatcher {
+    /// Poll `bucket` every `interval`, and with `notify` also wake up on bucket notifications
+    pub fn new(bucket: &str, interval: Duration, notify: bool) -> Result<Self, String> {
+        let mut watcher = RemoteWatcher {
+            bucket: bucket.to_string(),
+            interval,
+            handled_etag: None,
+            events: None,
+            listener: None,
+        };
+
+        if notify {
+            let mut listener = MinioUtil::listen_bucket_notifications(bucket)?;
+            let stdout = listener.stdout.take().ok_or("Notification listener has no output")?;
+            let (sender, events) = mpsc::channel();
+
+            thread::spawn(move || {
+                for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                    if let Some(event) = line.strip_prefix("Event: ") {
+                        if sender.send(event.to_string()).is_err() {
+                            return;
+                        }
+                    } else if line.starts_with("Listen failed") {
+                        eprintln!("Warning: Bucket notifications unavailable, polling only ({})", line);
+                    }
+                }
+            });
+
+            watcher.events = Some(events);
+            watcher.listener = Some(listener);
+        }
+
+        Ok(watcher)
+    }
+
+    /// Block until the remote manifest differs from the last handled version, returns its ETag
+    /// Transient errors are reported and retried on the next poll
+    pub fn wait_for_change(&mut self) -> String {
+        loop {
+            match MinioUtil::object_etag(&self.bucket, MANIFEST_KEY) {
+                Ok(Some(etag)) if self.handled_etag.as_ref() != Some(&etag) => return etag,
+                Ok(_) => {} // Unchanged, or nothing pushed yet
+                Err(e) => eprintln!("Warning: Could not check '{}' for changes: {}", self.bucket, e),
+            }
+            self.sleep();
+        }
+    }
+
+    /// Record that the manifest version with this ETag has been pulled
+    pub fn mark_handled(&mut self, etag: String) {
+        self.handled_etag = Some(etag);
+    }
+
+    /// Wait one poll interval, cut short by the first bucket notification
+    fn sleep(&mut self) {
+        let Some(events) = &self.events else {
+            thread::sleep(self.interval);
+            return;
+        };
+
+        match events.recv_timeout(self.interval) {
+            // Other objects of the same push are still arriving, let them land before looking
+            Ok(_) => while events.recv_timeout(Duration::from_millis(500)).is_ok() {},
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => self.events = None,
+        }
+    }
+}
+
+impl Drop for RemoteWatcher {
+    fn drop(&mut self) {
+        if let Some(listener) = &mut self.listener {
+            let _ = listener.kill();
+            let _ = listener.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tree_serializer::Tombstone;
+
+    fn file(name: &str, modified: u64) -> FileNode {
+        FileNode {
+            name: name.to_string(),
+            r#type: "file".to_string(),
+            path: name.to_string(),
+            relative_path: name.to_string(),
+            size: Some(4),
+            modified: Some(modified),
+            last_synced: Some(modified),
+            ..Default::default()
+        }
+    }
+
+    fn folder(children: Vec<FileNode>) -> FileNode {
+        let mut root = file(".", 0);
+        root.r#type = "folder".to_string();
+        root.children = Some(children);
+        root
+    }
+
+    #[test]
+    fn test_diff_manifests() {
+        let previous = folder(vec![
+            file("kept.txt", 1),
+            file("edited.txt", 1),
+            file("gone.txt", 1),
+            file("unlisted.txt", 1),
+        ]);
+        let mut chmodded = file("kept.txt", 1);
+        chmodded.mode = Some(0o600);
+        let mut current = folder(vec![
+            chmodded,
+            file("edited.txt", 2),
+            file("new.txt", 2),
+            file("rusty-sync-structure.json", 2),
+        ]);
+        // Only the tombstoned file counts as removed, the other one is just missing
+        current.tombstones.push(Tombstone {
+            path: "gone.txt".to_string(),
+            deleted_at: 2,
+            machine_id: "other".to_string(),
+        });
+
+        assert_eq!(
+            diff_manifests(Some(&previous), &current),
+            RemoteChanges {
+                changed: vec!["kept.txt".to_string(), "edited.txt".to_string(), "new.txt".to_string()],
+                removed: vec!["gone.txt".to_string()],
+            }
+        );
+        assert!(diff_manifests(Some(&current), &current).is_empty());
+        assert_eq!(diff_manifests(None, &previous).changed.len(), 4);
+    }
+}
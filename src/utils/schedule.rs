@@ -0,0 +1,283 @@
+// schedule windows that limit when (and how fast) the daemon may transfer a folder
+// a window reads "mon-fri 19:00-08:00 limit=2M": days, a time range in local time and an optional cap
+
+use chrono::{Datelike, Local, Timelike};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+const DAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// A stretch of the week during which transfers may run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ScheduleWindow {
+    days: [bool; 7], // Monday first; for windows past midnight, the day they start on
+    start: u32,      // Minutes after midnight
+    end: u32,        // Minutes after midnight, at or before `start` for windows past midnight
+    pub limit: Option<u64>, // Bytes per second, None for full speed
+}
+
+impl ScheduleWindow {
+    /// Check if the window covers a minute of the week (weekday 0 is Monday)
+    fn contains(&self, weekday: usize, minute: u32) -> bool {
+        if self.start < self.end {
+            return self.days[weekday] && minute >= self.start && minute < self.end;
+        }
+        if self.start == self.end {
+            return self.days[weekday];
+        }
+        // Past midnight: the evening part belongs to today, the morning part to yesterday's window
+        (self.days[weekday] && minute >= self.start) || (self.days[(weekday + 6) % 7] && minute < self.end)
+    }
+}
+
+/// Whether a folder may transfer at a given moment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferWindow {
+    Open { limit: Option<u64> },
+    Closed,
+}
+
+/// What a schedule allows at a minute of the week, an empty schedule allows everything
+/// Where windows overlap the fastest one wins
+pub fn transfer_window(schedule: &[ScheduleWindow], weekday: usize, minute: u32) -> TransferWindow {
+    if schedule.is_empty() {
+        return TransferWindow::Open { limit: None };
+    }
+
+    schedule
+        .iter()
+        .filter(|window| window.contains(weekday, minute))
+        .map(|window| window.limit)
+        .max_by_key(|limit| limit.unwrap_or(u64::MAX))
+        .map_or(TransferWindow::Closed, |limit| TransferWindow::Open { limit })
+}
+
+/// Minutes until the schedule allows something different from now, None if it never does
+pub fn minutes_until_change(schedule: &[ScheduleWindow], weekday: usize, minute: u32) -> Option<u32> {
+    let now = transfer_window(schedule, weekday, minute);
+    (1..=7 * MINUTES_PER_DAY).find(|ahead| {
+        let later = minute + ahead;
+        transfer_window(schedule, (weekday + (later / MINUTES_PER_DAY) as usize) % 7, later % MINUTES_PER_DAY) != now
+    })
+}
+
+/// Current local weekday (Monday 0) and minute of the day
+pub fn local_now() -> (usize, u32) {
+    let now = Local::now();
+    (now.weekday().num_days_from_monday() as usize, now.hour() * 60 + now.minute())
+}
+
+/// Parse "2M", "500K" or "1048576" as bytes
+fn parse_rate(text: &str) -> Result<u64, String> {
+    let text = text.trim_end_matches("/s");
+    let (number, unit) = match text.char_indices().find(|(_, c)| c.is_ascii_alphabetic()) {
+        Some((i, _)) => text.split_at(i),
+        None => (text, ""),
+    };
+    let multiplier = match unit.to_ascii_uppercase().trim_end_matches('B') {
+        "" => 1,
+        "K" => 1024,
+        "M" => 1024 * 1024,
+        "G" => 1024 * 1024 * 1024,
+        _ => return Err(format!("Unknown unit in rate '{}'. Use K, M or G", text)),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n > 0.0)
+        .map(|n| (n * multiplier as f64) as u64)
+        .ok_or_else(|| format!("Invalid rate '{}'", text))
+}
+
+fn format_rate(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 * 1024 && b % (1024 * 1024 * 1024) == 0 => format!("{}G", b / (1024 * 1024 * 1024)),
+        b if b >= 1024 * 1024 && b % (1024 * 1024) == 0 => format!("{}M", b / (1024 * 1024)),
+        b if b >= 1024 && b % 1024 == 0 => format!("{}K", b / 1024),
+        b => b.to_string(),
+    }
+}
+
+fn parse_day(name: &str) -> Result<usize, String> {
+    let name = name.to_ascii_lowercase();
+    DAY_NAMES
+        .iter()
+        .position(|day| name.starts_with(day))
+        .ok_or_else(|| format!("Unknown day '{}'", name))
+}
+
+/// "mon-fri", "sat,sun", "mon,wed-fri" or "*"
+fn parse_days(text: &str) -> Result<[bool; 7], String> {
+    let mut days = [false; 7];
+    if text == "*" || text.eq_ignore_ascii_case("daily") {
+        return Ok([true; 7]);
+    }
+
+    for part in text.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (parse_day(from)?, parse_day(to)?);
+                let mut day = from;
+                loop {
+                    days[day] = true;
+                    if day == to {
+                        break;
+                    }
+                    day = (day + 1) % 7;
+                }
+            }
+            None => days[parse_day(part)?] = true,
+        }
+    }
+    Ok(days)
+}
+
+/// "HH:MM", where 24:00 means the end of the day
+fn parse_time(text: &str) -> Result<u32, String> {
+    let invalid = || format!("Invalid time '{}'. Use HH:MM", text);
+    let (hours, minutes) = text.split_once(':').ok_or_else(invalid)?;
+    let (hours, minutes): (u32, u32) = (
+        hours.parse().map_err(|_| invalid())?,
+        minutes.parse().map_err(|_| invalid())?,
+    );
+    if minutes >= 60 || hours * 60 + minutes > MINUTES_PER_DAY {
+        return Err(invalid());
+    }
+    Ok(hours * 60 + minutes)
+}
+
+fn format_days(days: &[bool; 7]) -> String {
+    if days.iter().all(|&d| d) {
+        return "*".to_string();
+    }
+
+    // Collapse runs of days into ranges
+    let mut parts = Vec::new();
+    let mut day = 0;
+    while day < 7 {
+        if !days[day] {
+            day += 1;
+            continue;
+        }
+        let start = day;
+        while day + 1 < 7 && days[day + 1] {
+            day += 1;
+        }
+        parts.push(match day - start {
+            0 => DAY_NAMES[start].to_string(),
+            1 => format!("{},{}", DAY_NAMES[start], DAY_NAMES[day]),
+            _ => format!("{}-{}", DAY_NAMES[start], DAY_NAMES[day]),
+        });
+        day += 1;
+    }
+    parts.join(",")
+}
+
+impl FromStr for ScheduleWindow {
+    type Err = String;
+
+    /// Parse "[days] [HH:MM-HH:MM] [limit=RATE]", any part may be left out
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut window = ScheduleWindow {
+            days: [true; 7],
+            start: 0,
+            end: 0,
+            limit: None,
+        };
+
+        for part in s.split_whitespace() {
+            if let Some(rate) = part.strip_prefix("limit=") {
+                window.limit = Some(parse_rate(rate)?);
+            } else if part.contains(':') {
+                let (start, end) = part
+                    .split_once('-')
+                    .ok_or_else(|| format!("Invalid time range '{}'. Use HH:MM-HH:MM", part))?;
+                window.start = parse_time(start)? % MINUTES_PER_DAY;
+                window.end = parse_time(end)? % MINUTES_PER_DAY;
+            } else {
+                window.days = parse_days(part)?;
+            }
+        }
+
+        if !window.days.iter().any(|&d| d) {
+            return Err(format!("Schedule window '{}' has no days", s));
+        }
+        Ok(window)
+    }
+}
+
+impl fmt::Display for ScheduleWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_days(&self.days))?;
+        if self.start != self.end {
+            write!(
+                f,
+                " {:02}:{:02}-{:02}:{:02}",
+                self.start / 60,
+                self.start % 60,
+                self.end / 60,
+                self.end % 60
+            )?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " limit={}", format_rate(limit))?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for ScheduleWindow {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ScheduleWindow> for String {
+    fn from(window: ScheduleWindow) -> Self {
+        window.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display_round_trip() {
+        for spec in ["mon-fri 19:00-08:00 limit=2M", "sat,sun", "* 12:00-13:30 limit=500K", "mon,wed-fri 09:00-17:00"] {
+            let window: ScheduleWindow = spec.parse().unwrap();
+            assert_eq!(window.to_string(), spec);
+        }
+        assert_eq!("daily limit=1.5M".parse::<ScheduleWindow>().unwrap().limit, Some(1_572_864));
+        assert!("mon 25:00-26:00".parse::<ScheduleWindow>().is_err());
+        assert!("someday".parse::<ScheduleWindow>().is_err());
+    }
+
+    #[test]
+    fn test_transfer_window() {
+        // Evenings and nights on weekdays capped, weekends at full speed
+        let schedule: Vec<ScheduleWindow> = ["mon-fri 19:00-08:00 limit=1M", "sat,sun"]
+            .iter()
+            .map(|spec| spec.parse().unwrap())
+            .collect();
+        let (mon, fri, sat) = (0, 4, 5);
+        let capped = TransferWindow::Open { limit: Some(1024 * 1024) };
+
+        assert_eq!(transfer_window(&schedule, mon, 12 * 60), TransferWindow::Closed);
+        assert_eq!(transfer_window(&schedule, mon, 20 * 60), capped);
+        assert_eq!(transfer_window(&schedule, mon, 7 * 60), TransferWindow::Closed); // Sunday has no night window
+        assert_eq!(transfer_window(&schedule, fri, 7 * 60), capped); // Thursday night's window
+        // Friday's night window overlaps the weekend, the faster one wins
+        assert_eq!(transfer_window(&schedule, sat, 3 * 60), TransferWindow::Open { limit: None });
+        assert_eq!(transfer_window(&[], mon, 12 * 60), TransferWindow::Open { limit: None });
+
+        assert_eq!(minutes_until_change(&schedule, mon, 18 * 60), Some(60));
+        assert_eq!(minutes_until_change(&schedule, fri, 23 * 60), Some(60)); // Weekend starts at midnight
+        assert_eq!(minutes_until_change(&[], mon, 0), None);
+    }
+}
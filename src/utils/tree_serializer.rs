@@ -4,12 +4,89 @@
 // this format will be stated in a documentation file
 
 //kinda weird but we will have a json module in tihs file since i hope this is the only one that will needd it (im prolly worng)
+use crate::config::folder_settings::{FolderSettings, SETTINGS_FILE_NAME};
+use crate::core::encryption::TempFile;
+use crate::utils::git_util::{GitDetector, GitRepoInfo, GitRepoMode};
+use crate::utils::path_safety::{key_to_local, local_to_key_component, validate_tree, NameIssue};
+use crate::utils::walker::{
+    symlink_stays_inside, DirWalker, SkipReason, SymlinkPolicy, WalkEntry, WalkError,
+};
+use crate::utils::xattr_util::XattrUtil;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::{self, Read};
-use std::path::Path;
-use std::time::SystemTime;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Directory inside a sync folder (and key prefix in the bucket) reserved for rusty-sync metadata
+pub const METADATA_DIR: &str = ".rusty-sync";
+
+/// Object metadata key recording what kind of entry an object holds
+pub const OBJECT_TYPE_METADATA: &str = "rusty-sync-type";
+
+/// Object metadata key recording the target of a stored symlink
+pub const LINK_TARGET_METADATA: &str = "rusty-sync-link-target";
+
+/// Object metadata keys recording permission bits (octal) and ownership
+pub const MODE_METADATA: &str = "rusty-sync-mode";
+pub const UID_METADATA: &str = "rusty-sync-uid";
+pub const GID_METADATA: &str = "rusty-sync-gid";
+
+/// Object metadata key recording extended attributes as JSON, and the largest value stored there
+pub const XATTRS_METADATA: &str = "rusty-sync-xattrs";
+const MAX_XATTR_METADATA_LEN: usize = 1536;
+
+/// Object key the structure file is uploaded under so other machines can see the remote tree
+pub const MANIFEST_KEY: &str = ".rusty-sync/structure.json";
+
+/// Object key a bundle-mode repository is uploaded under
+pub fn git_bundle_key(relative_path: &str) -> String {
+    if relative_path == "." {
+        format!("{}/bundles/repo.bundle", METADATA_DIR)
+    } else {
+        format!("{}/bundles/{}/repo.bundle", METADATA_DIR, relative_path)
+    }
+}
+
+/// Check if a relative path belongs to rusty-sync itself and must never be uploaded
+pub(crate) fn is_internal_path(relative_path: &str) -> bool {
+    relative_path == "rusty-sync-structure.json"
+        || relative_path == SETTINGS_FILE_NAME
+        || relative_path == METADATA_DIR
+        || relative_path.starts_with(&format!("{}/", METADATA_DIR))
+}
+
+/// Drop paths that sit below another path in the list
+fn topmost_paths(paths: impl Iterator<Item = String>) -> Vec<String> {
+    let mut paths: Vec<String> = paths.collect();
+    paths.sort();
+
+    let mut topmost: Vec<String> = Vec::new();
+    for path in paths {
+        if !topmost.iter().any(|parent| path.starts_with(&format!("{}/", parent))) {
+            topmost.push(path);
+        }
+    }
+    topmost
+}
+
+/// Read permission bits and, if asked for, ownership from file metadata
+#[cfg(unix)]
+fn file_permissions(metadata: &std::fs::Metadata, with_owner: bool) -> (Option<u32>, Option<u32>, Option<u32>) {
+    use std::os::unix::fs::MetadataExt;
+    let mode = Some(metadata.mode() & 0o7777);
+    if with_owner {
+        (mode, Some(metadata.uid()), Some(metadata.gid()))
+    } else {
+        (mode, None, None)
+    }
+}
+
+#[cfg(not(unix))]
+fn file_permissions(_metadata: &std::fs::Metadata, _with_owner: bool) -> (Option<u32>, Option<u32>, Option<u32>) {
+    (None, None, None)
+}
 
 /// Generate a unique machine ID based on hostname and user
 fn get_machine_id() -> String {
@@ -56,15 +133,39 @@ fn generate_sync_id() -> String {
     format!("sync_{}", timestamp)
 }
 
+/// How long a tombstone stays in the manifest, machines that pull less often keep the deleted paths
+const TOMBSTONE_RETENTION: u64 = 90 * 24 * 60 * 60;
+
+/// A path some machine deleted, published in the manifest so other machines remove it too
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Tombstone {
+    pub path: String,       // Relative path of the deleted file or folder
+    pub deleted_at: u64,    // Unix timestamp of the push that recorded it
+    pub machine_id: String, // Machine the path was deleted on
+}
+
 // definition sof structs for the nodes
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct FileNode {
     pub name: String,
-    pub r#type: String,        // "file" or "folder"
+    pub r#type: String,        // "file", "folder" or "symlink"
     pub path: String,          // Full absolute path to the file/folder
     pub relative_path: String, // Relative path from sync root (cross-platform)
     pub children: Option<Vec<FileNode>>,
+    pub skipped: Option<SkipReason>, // Folder the scan didn't look into, its contents are unknown rather than gone
     pub git_remote: Option<String>,
+    pub git_info: Option<GitRepoInfo>, // Set on git repository nodes only
+    pub link_target: Option<String>,   // Target of a stored symlink (None for files and folders)
+    // POSIX ownership and permissions (None where the platform has no such thing)
+    pub mode: Option<u32>,        // Permission bits, e.g. 0o755
+    pub uid: Option<u32>,         // Owner, only recorded with the preserve-owner setting
+    pub gid: Option<u32>,         // Group, only recorded with the preserve-owner setting
+    pub synced_mode: Option<u32>, // Permission bits at the last upload
+    // Extended attributes (name -> hex value), only captured with the xattrs setting
+    pub xattrs: Option<BTreeMap<String, String>>,
+    pub synced_xattrs: Option<BTreeMap<String, String>>, // Extended attributes at the last upload
+    #[serde(skip)]
+    pub xattrs_unreadable: bool, // Reading them failed on this scan, see `merge_sync_metadata`
     // File-specific metadata for sync tracking
     pub size: Option<u64>,     // File size in bytes (None for folders)
     pub modified: Option<u64>, // Last modified time as Unix timestamp (None for folders or if unavailable)
@@ -73,6 +174,9 @@ pub struct FileNode {
     pub last_synced: Option<u64>,   // Last sync timestamp
     pub sync_id: Option<String>,    // Unique ID for this sync folder
     pub machine_id: Option<String>, // Machine that last modified this file
+    // Deletions to propagate, only set on the root of a manifest
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tombstones: Vec<Tombstone>,
 }
 
 impl FileNode {
@@ -105,6 +209,122 @@ impl FileNode {
         }
     }
 
+    /// Get all plain folders (not git repositories, not the root) recursively from this tree
+    pub fn get_all_folders(&self) -> Vec<&FileNode> {
+        let mut folders = Vec::new();
+        self.collect_folders(&mut folders);
+        folders
+    }
+
+    fn collect_folders<'a>(&'a self, folders: &mut Vec<&'a FileNode>) {
+        if self.is_folder() && !self.is_git_repository() && self.relative_path != "." {
+            folders.push(self);
+        }
+
+        if let Some(children) = &self.children {
+            for child in children {
+                child.collect_folders(folders);
+            }
+        }
+    }
+
+    /// Check if this node represents a stored symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.r#type == "symlink"
+    }
+
+    /// Get every node that is stored as its own object (files and symlinks)
+    pub fn get_all_objects(&self) -> Vec<&FileNode> {
+        let mut objects = Vec::new();
+        self.collect_objects(&mut objects);
+        objects
+    }
+
+    fn collect_objects<'a>(&'a self, objects: &mut Vec<&'a FileNode>) {
+        if self.is_file() || self.is_symlink() {
+            objects.push(self);
+        }
+
+        if let Some(children) = &self.children {
+            for child in children {
+                child.collect_objects(objects);
+            }
+        }
+    }
+
+    /// Find a file/folder by its path relative to the sync root
+    pub fn find_by_relative_path(&self, relative_path: &str) -> Option<&FileNode> {
+        if self.relative_path == relative_path {
+            return Some(self);
+        }
+
+        if let Some(children) = &self.children {
+            for child in children {
+                if let Some(found) = child.find_by_relative_path(relative_path) {
+                    return Some(found);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Check if the tree can't tell whether `relative_path` exists because a folder above it was skipped
+    pub fn is_unscanned(&self, relative_path: &str) -> bool {
+        if self.skipped.is_some() {
+            return true;
+        }
+
+        self.children
+            .iter()
+            .flatten()
+            .find(|child| relative_path.starts_with(&format!("{}/", child.relative_path)))
+            .is_some_and(|child| child.is_unscanned(relative_path))
+    }
+
+    /// Check if a tombstone of this manifest covers `relative_path` (the path itself or a folder above it)
+    pub fn is_deleted(&self, relative_path: &str) -> bool {
+        self.tombstones.iter().any(|tombstone| {
+            tombstone.path == relative_path || relative_path.starts_with(&format!("{}/", tombstone.path))
+        })
+    }
+
+    /// Mutable version of `find_by_relative_path`
+    pub fn find_by_relative_path_mut(&mut self, relative_path: &str) -> Option<&mut FileNode> {
+        if self.relative_path == relative_path {
+            return Some(self);
+        }
+
+        self.children
+            .as_mut()?
+            .iter_mut()
+            .find_map(|child| child.find_by_relative_path_mut(relative_path))
+    }
+
+    /// Check if this node represents a git repository
+    pub fn is_git_repository(&self) -> bool {
+        self.git_info.is_some()
+    }
+
+    /// Get all git repository nodes recursively from this tree
+    pub fn get_git_repositories(&self) -> Vec<&FileNode> {
+        let mut repos = Vec::new();
+        self.collect_git_repositories(&mut repos);
+        repos
+    }
+
+    fn collect_git_repositories<'a>(&'a self, repos: &mut Vec<&'a FileNode>) {
+        if self.is_git_repository() {
+            repos.push(self);
+        }
+
+        if let Some(children) = &self.children {
+            for child in children {
+                child.collect_git_repositories(repos);
+            }
+        }
+    }
+
     /// Find a file/folder by path
     pub fn find_by_path(&self, target_path: &str) -> Option<&FileNode> {
         if self.path == target_path {
@@ -134,14 +354,30 @@ impl FileNode {
     /// Check if this file needs to be synced (has changes since last sync)
     pub fn needs_sync(&self) -> bool {
         if self.is_folder() {
-            return false; // Only sync files, not folders
+            // Only bundle-mode repos are uploaded as a whole, other folders sync file by file
+            return self
+                .git_info
+                .as_ref()
+                .is_some_and(|git| git.mode == GitRepoMode::Bundle)
+                && self.hash.is_some()
+                && self.last_synced.is_none();
         }
 
-        // Don't sync the structure file itself
-        if self.relative_path == "rusty-sync-structure.json" {
+        // Don't sync the structure file, settings or anything under the metadata dir
+        if is_internal_path(&self.relative_path) {
             return false;
         }
 
+        // Links carry no timestamp we trust, they are re-uploaded whenever the target changes
+        if self.is_symlink() {
+            return self.last_synced.is_none();
+        }
+
+        self.content_changed() || self.metadata_changed()
+    }
+
+    /// Check if the file contents changed since the last sync
+    pub fn content_changed(&self) -> bool {
         match (self.modified, self.last_synced) {
             (Some(modified), Some(last_synced)) => modified > last_synced,
             (Some(_), None) => true, // Never synced before
@@ -149,6 +385,57 @@ impl FileNode {
         }
     }
 
+    /// Check if only the permission bits changed since the last sync
+    pub fn permissions_changed(&self) -> bool {
+        self.is_file()
+            && !is_internal_path(&self.relative_path)
+            && !self.content_changed()
+            && self.synced_mode.is_some()
+            && self.mode != self.synced_mode
+    }
+
+    /// Check if only the extended attributes changed since the last sync
+    pub fn xattrs_changed(&self) -> bool {
+        self.is_file()
+            && !is_internal_path(&self.relative_path)
+            && !self.content_changed()
+            && self.last_synced.is_some()
+            && self.xattrs != self.synced_xattrs
+    }
+
+    /// Check if the object metadata needs rewriting while the contents stay the same
+    pub fn metadata_changed(&self) -> bool {
+        self.permissions_changed() || self.xattrs_changed()
+    }
+
+    /// Everything recorded as object metadata next to the contents
+    pub fn object_metadata(&self) -> BTreeMap<String, String> {
+        let mut metadata = self.permission_metadata();
+        if let Some(xattrs) = self.xattrs.as_ref().filter(|x| !x.is_empty()) {
+            let encoded = serde_json::to_string(xattrs).unwrap_or_default();
+            // S3 caps user metadata at 2KB, larger sets only travel in the manifest
+            if encoded.len() <= MAX_XATTR_METADATA_LEN {
+                metadata.insert(XATTRS_METADATA.to_string(), encoded);
+            }
+        }
+        metadata
+    }
+
+    /// Mode and ownership as object metadata
+    pub fn permission_metadata(&self) -> BTreeMap<String, String> {
+        let mut metadata = BTreeMap::new();
+        if let Some(mode) = self.mode {
+            metadata.insert(MODE_METADATA.to_string(), format!("{:o}", mode));
+        }
+        if let Some(uid) = self.uid {
+            metadata.insert(UID_METADATA.to_string(), uid.to_string());
+        }
+        if let Some(gid) = self.gid {
+            metadata.insert(GID_METADATA.to_string(), gid.to_string());
+        }
+        metadata
+    }
+
     /// Get the relative path for cross-platform compatibility
     pub fn get_cross_platform_path(&self) -> &str {
         &self.relative_path
@@ -187,6 +474,8 @@ impl FileNode {
                     SyncAction::Upload
                 } else if other_mod > self_mod {
                     SyncAction::Download
+                } else if self.mode != other.mode || self.xattrs != other.xattrs {
+                    SyncAction::UpdateMetadata
                 } else {
                     SyncAction::NoAction
                 }
@@ -200,9 +489,10 @@ impl FileNode {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum SyncAction {
-    Upload,   // Local file is newer, upload to cloud
-    Download, // Cloud file is newer, download from cloud
-    NoAction, // Files are in sync or no change needed
+    Upload,         // Local file is newer, upload to cloud
+    Download,       // Cloud file is newer, download from cloud
+    UpdateMetadata, // Same contents, only mode or xattrs differ: rewrite the object metadata
+    NoAction,       // Files are in sync or no change needed
 }
 
 pub struct JsonManager;
@@ -245,24 +535,275 @@ impl JsonValidator {
     }
 }
 
+/// Values shared by every node while a tree is generated
+struct TreeContext<'a> {
+    root: &'a str,
+    sync_id: &'a str,
+    machine_id: String,
+    current_time: u64,
+    settings: &'a FolderSettings,
+}
+
+impl TreeContext<'_> {
+    /// Relative path from the sync root, "." for the root itself
+    /// Local names are unescaped and normalized into the portable key form
+    fn relative_path(&self, path: &Path) -> String {
+        let relative = to_relative_path(&path.to_string_lossy(), self.root);
+        if relative.is_empty() {
+            return ".".to_string();
+        }
+
+        let key: Vec<String> = relative.split('/').map(local_to_key_component).collect();
+        self.settings.key_normalization.apply(&key.join("/"))
+    }
+}
+
+/// Paths git reports for a worktree-mode repository, plus every folder leading to them
+struct WorktreeFilter {
+    files: HashSet<PathBuf>,
+    folders: HashSet<PathBuf>,
+}
+
+impl WorktreeFilter {
+    fn new(repo_path: &Path, files: HashSet<PathBuf>) -> Self {
+        let mut folders = HashSet::new();
+        for file in &files {
+            for ancestor in file.ancestors().skip(1) {
+                if ancestor == repo_path || !folders.insert(ancestor.to_path_buf()) {
+                    break;
+                }
+            }
+        }
+        WorktreeFilter { files, folders }
+    }
+
+    fn includes(&self, path: &Path) -> bool {
+        self.files.contains(path) || self.folders.contains(path)
+    }
+}
+
+/// What a changed path means for the tree
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum RescanTarget {
+    Entry(PathBuf),     // Re-read this file or folder, or drop it if it is gone
+    GitMetadata(PathBuf), // Refs or config of this repository changed
+}
+
+/// Result of updating a tree for a set of changed paths
+pub struct PathRescan {
+    pub updated: Vec<String>, // Relative paths whose nodes were rebuilt or removed
+    pub errors: Vec<WalkError>,
+}
+
+/// Result of scanning a folder: the tree plus every path that could not be read
+pub struct TreeScan {
+    pub tree: FileNode,
+    pub errors: Vec<WalkError>,
+    pub name_issues: Vec<NameIssue>, // Names that break or collide on other platforms
+}
+
 pub struct ReadFileTree;
 
 impl ReadFileTree {
     pub fn generate_tree(path: &str) -> io::Result<FileNode> {
-        Self::generate_tree_with_sync_data(path, &generate_sync_id())
+        Self::scan_tree(path).map(|scan| scan.tree)
+    }
+
+    /// Scan a folder and also report the paths that could not be read
+    pub fn scan_tree(path: &str) -> io::Result<TreeScan> {
+        let settings = FolderSettings::load(path);
+        Self::scan_tree_with_settings(path, &generate_sync_id(), &settings)
     }
 
     /// Generate tree and merge with existing sync metadata if available
     pub fn generate_tree_preserving_sync_data(path: &str) -> io::Result<FileNode> {
-        let mut new_tree = Self::generate_tree(path)?;
+        Self::scan_tree_preserving_sync_data(path).map(|scan| scan.tree)
+    }
+
+    /// Scan a folder, merge existing sync metadata and report unreadable paths
+    pub fn scan_tree_preserving_sync_data(path: &str) -> io::Result<TreeScan> {
+        let mut scan = Self::scan_tree(path)?;
 
         // Try to load existing sync metadata
         let structure_file = format!("{}/rusty-sync-structure.json", path);
         if let Ok(existing_tree) = JsonManager::read_from_json::<FileNode>(&structure_file) {
-            Self::merge_sync_metadata(&mut new_tree, &existing_tree);
+            Self::merge_sync_metadata(&mut scan.tree, &existing_tree);
         }
 
-        Ok(new_tree)
+        Ok(scan)
+    }
+
+    /// Bring the nodes for `changed` paths up to date without rescanning the whole folder
+    /// Applies the same rules as a full scan: ignored paths, git repo modes, symlinks and max-depth
+    pub fn rescan_paths(
+        tree: &mut FileNode,
+        root: &str,
+        settings: &FolderSettings,
+        changed: &[PathBuf],
+    ) -> PathRescan {
+        let sync_id = tree.sync_id.clone().unwrap_or_else(generate_sync_id);
+        let context = TreeContext {
+            root,
+            sync_id: &sync_id,
+            machine_id: get_machine_id(),
+            current_time: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            settings,
+        };
+        let mut walker = DirWalker::new(settings.walk_options());
+
+        let mut targets: Vec<RescanTarget> = changed
+            .iter()
+            .filter_map(|path| Self::rescan_target(tree, &context, path))
+            .collect();
+        targets.sort();
+        targets.dedup();
+
+        let mut updated: Vec<String> = Vec::new();
+        for target in targets {
+            let relative_path = match &target {
+                RescanTarget::Entry(path) => {
+                    // A rebuilt folder already covers everything below it
+                    let relative_path = context.relative_path(path);
+                    if updated.iter().any(|done| relative_path.starts_with(&format!("{}/", done))) {
+                        continue;
+                    }
+                    Self::rescan_entry(tree, &context, &mut walker, path);
+                    relative_path
+                }
+                RescanTarget::GitMetadata(repo_path) => {
+                    let relative_path = context.relative_path(repo_path);
+                    if let Some(repo) = tree.find_by_relative_path_mut(&relative_path) {
+                        Self::refresh_git_metadata(repo, repo_path, settings);
+                    }
+                    relative_path
+                }
+            };
+            updated.push(relative_path);
+        }
+
+        PathRescan {
+            updated,
+            errors: walker.take_errors(),
+        }
+    }
+
+    /// Work out what has to be re-read for a changed path, None if the path is not part of the tree
+    fn rescan_target(tree: &FileNode, context: &TreeContext, path: &Path) -> Option<RescanTarget> {
+        let root = Path::new(context.root);
+        let components: Vec<_> = path.strip_prefix(root).ok()?.components().collect();
+        if components.is_empty() || is_internal_path(&context.relative_path(path)) {
+            return None;
+        }
+
+        // Anything inside a .git directory only changes the repository's metadata
+        if let Some(git_index) = components.iter().position(|c| c.as_os_str() == ".git") {
+            let repo_path: PathBuf = root.join(components[..git_index].iter().collect::<PathBuf>());
+            return match tree.find_by_relative_path(&context.relative_path(&repo_path)) {
+                Some(node) if node.is_git_repository() => Some(RescanTarget::GitMetadata(repo_path)),
+                _ => Some(RescanTarget::Entry(repo_path)), // A new repository, scan it as a whole
+            };
+        }
+
+        // The deepest folder the entry lives in must be within max-depth
+        if context
+            .settings
+            .max_depth
+            .is_some_and(|max| components.len() - 1 > max)
+        {
+            return None;
+        }
+
+        // Walk down the tree: stop at the first missing ancestor, skip anything hidden behind a repo or link
+        let mut current = root.to_path_buf();
+        let mut node = tree;
+        if Self::hides_contents(node) {
+            return None;
+        }
+        for (i, component) in components.iter().enumerate() {
+            current.push(component);
+            let Some(child) = node.find_by_relative_path(&context.relative_path(&current)) else {
+                return Some(RescanTarget::Entry(current));
+            };
+            if i + 1 < components.len() && (Self::hides_contents(child) || child.is_symlink()) {
+                return None;
+            }
+            node = child;
+        }
+
+        Some(RescanTarget::Entry(current))
+    }
+
+    /// Git repositories outside worktree mode don't sync their working files
+    fn hides_contents(node: &FileNode) -> bool {
+        node.git_info
+            .as_ref()
+            .is_some_and(|git| git.mode != GitRepoMode::Worktree)
+    }
+
+    /// Rebuild (or remove) the node for a single path and put it back under its parent
+    fn rescan_entry(tree: &mut FileNode, context: &TreeContext, walker: &mut DirWalker, path: &Path) {
+        let Some(parent_path) = path.parent() else {
+            return;
+        };
+        let relative_path = context.relative_path(path);
+        let depth = path
+            .strip_prefix(context.root)
+            .map(|relative| relative.components().count() - 1)
+            .unwrap_or_default();
+
+        // Inside a worktree-mode repo the same ignore filter as a full scan applies
+        let filter = path
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(context.root))
+            .find_map(|ancestor| {
+                tree.find_by_relative_path(&context.relative_path(ancestor))
+                    .filter(|node| node.is_git_repository())
+                    .map(|_| ancestor)
+            })
+            .and_then(|repo_path| {
+                GitDetector::list_worktree_files(repo_path).map(|files| WorktreeFilter::new(repo_path, files))
+            });
+
+        let Some(parent) = tree.find_by_relative_path_mut(&context.relative_path(parent_path)) else {
+            return;
+        };
+        let Some(children) = parent.children.as_mut() else {
+            return;
+        };
+
+        let previous = children
+            .iter()
+            .position(|child| child.relative_path == relative_path)
+            .map(|index| children.remove(index));
+
+        let rebuilt = WalkEntry::from_path(path)
+            .and_then(|entry| Self::build_entry_node(entry, context, walker, depth, filter.as_ref()));
+        if let Some(mut node) = rebuilt {
+            if let Some(previous) = &previous {
+                Self::merge_sync_metadata(&mut node, previous);
+            }
+            children.push(node);
+        }
+    }
+
+    /// Refresh branch, HEAD, remotes and the bundle fingerprint of a repository node
+    fn refresh_git_metadata(repo: &mut FileNode, repo_path: &Path, settings: &FolderSettings) {
+        let git_info = GitDetector::get_repo_info(repo_path, settings.git_mode);
+
+        if git_info.mode == GitRepoMode::Bundle {
+            let hash = GitDetector::get_refs_fingerprint(repo_path);
+            if hash != repo.hash {
+                repo.hash = hash;
+                repo.last_synced = None; // Refs moved, the bundle is stale
+            }
+        }
+
+        repo.git_remote = GitDetector::get_git_remotes(repo_path).first().cloned();
+        repo.git_info = Some(git_info);
     }
 
     /// Merge sync metadata from existing tree into new tree
@@ -272,14 +813,40 @@ impl ReadFileTree {
             && existing_tree.is_file()
             && new_tree.relative_path == existing_tree.relative_path
         {
+            // Attributes that couldn't be read are taken as unchanged, not as removed from the remote copy
+            if new_tree.xattrs_unreadable {
+                new_tree.xattrs = existing_tree.xattrs.clone();
+            }
             // Only preserve sync metadata if the file hasn't been modified since last sync
             if new_tree.modified == existing_tree.modified {
                 new_tree.last_synced = existing_tree.last_synced;
+                new_tree.synced_mode = existing_tree.synced_mode;
+                new_tree.synced_xattrs = existing_tree.synced_xattrs.clone();
                 new_tree.sync_id = existing_tree.sync_id.clone();
                 new_tree.machine_id = existing_tree.machine_id.clone();
             }
         }
 
+        // Links stay synced as long as they point at the same target
+        if new_tree.is_symlink()
+            && existing_tree.is_symlink()
+            && new_tree.relative_path == existing_tree.relative_path
+            && new_tree.link_target == existing_tree.link_target
+        {
+            new_tree.last_synced = existing_tree.last_synced;
+            new_tree.sync_id = existing_tree.sync_id.clone();
+            new_tree.machine_id = existing_tree.machine_id.clone();
+        }
+
+        // Bundle-mode repos stay synced as long as none of their refs moved
+        if new_tree.needs_sync()
+            && existing_tree.is_git_repository()
+            && new_tree.relative_path == existing_tree.relative_path
+            && new_tree.hash == existing_tree.hash
+        {
+            new_tree.last_synced = existing_tree.last_synced;
+        }
+
         // Recursively merge children
         if let (Some(new_children), Some(existing_children)) =
             (&mut new_tree.children, &existing_tree.children)
@@ -296,129 +863,278 @@ impl ReadFileTree {
     }
 
     pub fn generate_tree_with_sync_data(path: &str, sync_id: &str) -> io::Result<FileNode> {
-        use crate::utils::git_util::GitDetector;
+        let settings = FolderSettings::load(path);
+        Self::generate_tree_with_settings(path, sync_id, &settings)
+    }
+
+    /// Generate the tree using explicit folder settings instead of the ones saved in the folder
+    pub fn generate_tree_with_settings(
+        path: &str,
+        sync_id: &str,
+        settings: &FolderSettings,
+    ) -> io::Result<FileNode> {
+        Self::scan_tree_with_settings(path, sync_id, settings).map(|scan| scan.tree)
+    }
 
+    /// Scan with explicit folder settings, collecting unreadable paths instead of failing on them
+    pub fn scan_tree_with_settings(
+        path: &str,
+        sync_id: &str,
+        settings: &FolderSettings,
+    ) -> io::Result<TreeScan> {
         let root_path = std::path::Path::new(path);
-        let machine_id = get_machine_id();
-        let current_time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
 
-        // // write the real file path that the json is targetting
-        let json_file_path = format!("{}/EXAMPLE.struct_git.json", path);
+        let context = TreeContext {
+            root: path,
+            sync_id,
+            machine_id: get_machine_id(),
+            current_time: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            settings,
+        };
+        let mut walker = DirWalker::new(settings.walk_options());
 
-        // Write the real file path at the top of the json so when we upload we know where the root folder is on the system
-        let json_content = format!(r#"{{ "root": "{}" }}"#, root_path.display());
+        // Check if the root folder is a Git repository
+        let tree = if GitDetector::is_git_repository(root_path) {
+            Self::build_git_repo_node(root_path, &context, &mut walker, 0)
+        } else {
+            Self::build_folder_node(root_path, &context, &mut walker, 0, None)
+        };
+
+        // Only an unreadable root is fatal, anything below it is just reported
+        match tree {
+            Some(tree) => Ok(TreeScan {
+                name_issues: validate_tree(&tree),
+                tree,
+                errors: walker.take_errors(),
+            }),
+            None => Err(io::Error::other(
+                walker
+                    .errors()
+                    .first()
+                    .map(|e| e.to_string())
+                    .unwrap_or_else(|| format!("Failed to read {}", path)),
+            )),
+        }
+    }
 
-        //push content to json file, dont save it and stop writting
-        std::fs::write(&json_file_path, json_content)?;
+    /// Build the node for one entry of a folder at `depth`, None if the entry is skipped
+    fn build_entry_node(
+        entry: WalkEntry,
+        context: &TreeContext,
+        walker: &mut DirWalker,
+        depth: usize,
+        filter: Option<&WorktreeFilter>,
+    ) -> Option<FileNode> {
+        // Never descend into git internals or our own metadata directory
+        if entry.name == ".git" || context.relative_path(&entry.path) == METADATA_DIR {
+            return None;
+        }
 
-        // Check if the root folder is a Git repository
-        if GitDetector::is_git_repository(root_path) {
-            let git_remotes = GitDetector::get_git_remotes(root_path);
-            return Ok(FileNode {
-                name: root_path
-                    .file_name()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-                    .to_string(),
-                r#type: "folder".to_string(),
-                path: root_path.to_string_lossy().to_string(),
-                relative_path: ".".to_string(),
-                children: None,
-                git_remote: git_remotes.get(0).cloned(), // Use the first remote if available
-                size: None,                              // Folders don't have size
-                modified: None, // Could add folder modification time if needed
-                hash: None,     // Git repos don't need file hashes
-                last_synced: Some(current_time),
-                sync_id: Some(sync_id.to_string()),
-                machine_id: Some(machine_id),
-            });
+        // Inside a worktree-mode repo only keep what git itself would not ignore
+        if let Some(filter) = filter
+            && !filter.includes(&entry.path)
+        {
+            return None;
         }
 
-        let mut children = Vec::new();
-
-        for entry in std::fs::read_dir(path)? {
-            let entry = entry?;
-            let entry_path = entry.path();
-            let entry_name = entry.file_name().to_string_lossy().to_string();
-
-            if entry_path.is_dir() {
-                if GitDetector::is_git_repository(&entry_path) {
-                    let git_remotes = GitDetector::get_git_remotes(&entry_path);
-                    children.push(FileNode {
-                        name: entry_name,
-                        r#type: "folder".to_string(),
-                        path: entry_path.to_string_lossy().to_string(),
-                        relative_path: to_relative_path(&entry_path.to_string_lossy(), path),
-                        children: None,
-                        git_remote: git_remotes.get(0).cloned(),
-                        size: None,
-                        modified: None,
-                        hash: None,
-                        last_synced: Some(current_time),
-                        sync_id: Some(sync_id.to_string()),
-                        machine_id: Some(machine_id.clone()),
-                    });
-                } else {
-                    // Recursively process non-Git folders
-                    children.push(Self::generate_tree_with_sync_data(
-                        &entry_path.to_string_lossy(),
-                        sync_id,
-                    )?);
-                }
-            } else if entry_path.is_file() {
-                // Get file metadata
-                let metadata = entry_path.metadata().ok();
-                let size = metadata.as_ref().map(|m| m.len());
-                let modified = metadata
-                    .as_ref()
-                    .and_then(|m| m.modified().ok())
-                    .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
-                    .map(|duration| duration.as_secs());
-
-                // Add files to the children list
-                children.push(FileNode {
-                    name: entry_name,
-                    r#type: "file".to_string(),
-                    path: entry_path.to_string_lossy().to_string(),
-                    relative_path: to_relative_path(&entry_path.to_string_lossy(), path),
-                    children: None,
-                    git_remote: None,
-                    size,
-                    modified,
-                    hash: None,        // Will be computed later when needed for sync
-                    last_synced: None, // No files are synced initially - will be set after successful upload
-                    sync_id: Some(sync_id.to_string()),
-                    machine_id: Some(machine_id.clone()),
-                });
-            }
-        }
-
-        Ok(FileNode {
-            name: root_path
+        if entry.is_symlink {
+            match context.settings.symlinks {
+                SymlinkPolicy::Ignore => return None,
+                SymlinkPolicy::FollowInside
+                    if symlink_stays_inside(&entry.path, Path::new(context.root)) => {}
+                // Store, or a follow-inside link that escapes the root or dangles
+                _ => return Self::build_symlink_node(&entry.path, entry.name, context, walker),
+            }
+        }
+
+        if entry.is_dir {
+            if GitDetector::is_git_repository(&entry.path) {
+                Self::build_git_repo_node(&entry.path, context, walker, depth + 1)
+            } else {
+                // Recursively process non-Git folders
+                Self::build_folder_node(&entry.path, context, walker, depth + 1, filter)
+            }
+        } else if entry.is_file {
+            Some(Self::build_file_node(&entry.path, entry.name, context, walker))
+        } else {
+            None
+        }
+    }
+
+    /// Build a folder node and recurse into its children
+    /// A folder the walker skips is kept without children so it doesn't look deleted,
+    /// None for an unreadable root and for symlink loops, which can't be re-created elsewhere
+    fn build_folder_node(
+        dir_path: &Path,
+        context: &TreeContext,
+        walker: &mut DirWalker,
+        depth: usize,
+        filter: Option<&WorktreeFilter>,
+    ) -> Option<FileNode> {
+        let (children, skipped) = match walker.descend(dir_path, depth, |walker, entries| {
+            entries
+                .into_iter()
+                .filter_map(|entry| Self::build_entry_node(entry, context, walker, depth, filter))
+                .collect()
+        }) {
+            Ok(children) => (Some(children), None),
+            Err(SkipReason::Loop) => return None,
+            Err(_) if depth == 0 => return None,
+            Err(reason) => (None, Some(reason)),
+        };
+
+        let (mode, uid, gid) = std::fs::metadata(dir_path)
+            .map(|m| file_permissions(&m, context.settings.preserve_owner))
+            .unwrap_or_default();
+
+        Some(FileNode {
+            name: dir_path
                 .file_name()
                 .unwrap_or_default()
                 .to_string_lossy()
                 .to_string(),
             r#type: "folder".to_string(),
-            path: root_path.to_string_lossy().to_string(),
-            relative_path: ".".to_string(),
-            children: Some(children),
-            git_remote: None,
-            size: None,
-            modified: None,
-            hash: None,
-            last_synced: Some(current_time),
-            sync_id: Some(sync_id.to_string()),
-            machine_id: Some(machine_id),
+            path: dir_path.to_string_lossy().to_string(),
+            relative_path: context.relative_path(dir_path),
+            children,
+            skipped,
+            mode,
+            uid,
+            gid,
+            last_synced: Some(context.current_time),
+            sync_id: Some(context.sync_id.to_string()),
+            machine_id: Some(context.machine_id.clone()),
+            ..Default::default()
         })
     }
+
+    /// Build a node for a git repository according to the folder's git mode
+    fn build_git_repo_node(
+        repo_path: &Path,
+        context: &TreeContext,
+        walker: &mut DirWalker,
+        depth: usize,
+    ) -> Option<FileNode> {
+        // The repo kind may override the configured mode, e.g. bare repos have no worktree
+        let git_info = GitDetector::get_repo_info(repo_path, context.settings.git_mode);
+        let mode = git_info.mode;
+        let git_remotes = GitDetector::get_git_remotes(repo_path);
+
+        let mut node = if mode == GitRepoMode::Worktree {
+            // Fall back to walking everything except .git if git can't list the files
+            let filter = GitDetector::list_worktree_files(repo_path)
+                .map(|files| WorktreeFilter::new(repo_path, files));
+            Self::build_folder_node(repo_path, context, walker, depth, filter.as_ref())?
+        } else {
+            FileNode {
+                name: repo_path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                r#type: "folder".to_string(),
+                path: repo_path.to_string_lossy().to_string(),
+                relative_path: context.relative_path(repo_path),
+                last_synced: Some(context.current_time),
+                sync_id: Some(context.sync_id.to_string()),
+                machine_id: Some(context.machine_id.clone()),
+                ..Default::default() // No contents, size or hash, the repo is described by its git info
+            }
+        };
+
+        node.git_remote = git_remotes.first().cloned(); // Use the first remote if available
+        node.git_info = Some(git_info);
+
+        if mode == GitRepoMode::Bundle {
+            // The bundle is re-uploaded whenever any ref changes
+            node.hash = GitDetector::get_refs_fingerprint(repo_path);
+            node.last_synced = None;
+        }
+
+        Some(node)
+    }
+
+    /// Build a node for a symlink that is stored as a link instead of being followed
+    fn build_symlink_node(
+        link_path: &Path,
+        name: String,
+        context: &TreeContext,
+        walker: &mut DirWalker,
+    ) -> Option<FileNode> {
+        let target = match std::fs::read_link(link_path) {
+            Ok(target) => target,
+            Err(e) => {
+                walker.record_error(link_path, e.to_string());
+                return None;
+            }
+        };
+
+        Some(FileNode {
+            name,
+            r#type: "symlink".to_string(),
+            path: link_path.to_string_lossy().to_string(),
+            relative_path: context.relative_path(link_path),
+            link_target: Some(target.to_string_lossy().to_string()),
+            last_synced: None, // Set after the link object is uploaded
+            sync_id: Some(context.sync_id.to_string()),
+            machine_id: Some(context.machine_id.clone()),
+            ..Default::default()
+        })
+    }
+
+    /// Build a node for a regular file
+    fn build_file_node(
+        file_path: &Path,
+        name: String,
+        context: &TreeContext,
+        walker: &mut DirWalker,
+    ) -> FileNode {
+        // Get file metadata
+        let metadata = file_path.metadata().ok();
+        let size = metadata.as_ref().map(|m| m.len());
+        let modified = metadata
+            .as_ref()
+            .and_then(|m| m.modified().ok())
+            .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+        let (mode, uid, gid) = metadata
+            .as_ref()
+            .map(|m| file_permissions(m, context.settings.preserve_owner))
+            .unwrap_or_default();
+        let xattrs = if context.settings.xattrs {
+            XattrUtil::read(file_path)
+                .map_err(|e| walker.record_error(file_path, e))
+                .ok()
+        } else {
+            None
+        };
+        let xattrs_unreadable = context.settings.xattrs && xattrs.is_none();
+
+        FileNode {
+            name,
+            r#type: "file".to_string(),
+            path: file_path.to_string_lossy().to_string(),
+            relative_path: context.relative_path(file_path),
+            mode,
+            uid,
+            gid,
+            xattrs,
+            xattrs_unreadable,
+            size,
+            modified,
+            last_synced: None, // No files are synced initially - will be set after successful upload
+            sync_id: Some(context.sync_id.to_string()),
+            machine_id: Some(context.machine_id.clone()),
+            ..Default::default() // Synced mode and xattrs are set together with last_synced
+        }
+    }
     pub fn generate_tree_as_string(path: &str) -> io::Result<String> {
         let tree = Self::generate_tree(path)?;
         let json_string = serde_json::to_string_pretty(&tree)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+            .map_err(|e| io::Error::other(e.to_string()))?;
         Ok(json_string)
     }
 
@@ -442,15 +1158,19 @@ pub struct SyncManager;
 
 impl SyncManager {
     /// Upload files that need syncing to MinIO
+    /// `progress` is called with (done, total) as uploads complete
     pub fn upload_changed_files(
         file_tree: &mut FileNode,
         bucket: &str,
+        progress: &mut dyn FnMut(usize, usize),
     ) -> Result<Vec<String>, String> {
+        use crate::core::minio_util::MinioUtil;
+
         let mut uploaded_files = Vec::new();
 
-        // Get all files that need syncing
+        // Get all files and links that need syncing
         let files_to_sync: Vec<String> = file_tree
-            .get_all_files()
+            .get_all_objects()
             .into_iter()
             .filter(|f| f.needs_sync())
             .map(|f| f.relative_path.clone())
@@ -458,15 +1178,32 @@ impl SyncManager {
 
         println!("Found {} files that need syncing", files_to_sync.len());
 
+        let bundles_to_sync: Vec<String> = file_tree
+            .get_git_repositories()
+            .into_iter()
+            .filter(|r| r.needs_sync())
+            .map(|r| r.relative_path.clone())
+            .collect();
+
+        let total = files_to_sync.len() + bundles_to_sync.len();
+        progress(0, total);
+
         for relative_path in files_to_sync {
             // Find the file again to get the reference
             if let Some(file) = Self::find_file_by_relative_path(file_tree, &relative_path) {
-                match Self::upload_file_to_minio(file, bucket) {
+                // A chmod or xattr change alone only rewrites the object's metadata, the contents stay put
+                let result = if file.metadata_changed() {
+                    MinioUtil::update_object_metadata(bucket, &relative_path, &file.object_metadata())
+                } else {
+                    Self::upload_file_to_minio(file, bucket)
+                };
+                match result {
                     Ok(_) => {
                         println!("Uploaded: {}", relative_path);
                         // Mark file as synced after successful upload
                         Self::mark_file_as_synced(file_tree, &relative_path)?;
                         uploaded_files.push(relative_path);
+                        progress(uploaded_files.len(), total);
                     }
                     Err(e) => {
                         eprintln!("✗ Failed to upload {}: {}", relative_path, e);
@@ -476,9 +1213,137 @@ impl SyncManager {
             }
         }
 
+        // Upload a fresh bundle for every bundle-mode repo whose refs changed
+        for relative_path in bundles_to_sync {
+            if let Some(repo) = Self::find_file_by_relative_path(file_tree, &relative_path) {
+                let bundle_key = git_bundle_key(&relative_path);
+                match Self::upload_git_bundle(repo, bucket, &bundle_key) {
+                    Ok(_) => {
+                        println!("Uploaded bundle: {}", relative_path);
+                        Self::mark_file_as_synced(file_tree, &relative_path)?;
+                        uploaded_files.push(bundle_key);
+                        progress(uploaded_files.len(), total);
+                    }
+                    Err(e) => {
+                        eprintln!("✗ Failed to bundle {}: {}", relative_path, e);
+                        return Err(format!("Failed to bundle {}: {}", relative_path, e));
+                    }
+                }
+            }
+        }
+
         Ok(uploaded_files)
     }
 
+    /// Bundle a git repository into a temporary file and upload it
+    fn upload_git_bundle(repo: &FileNode, bucket: &str, bundle_key: &str) -> Result<(), String> {
+        use crate::core::minio_util::MinioUtil;
+
+        let bundle = TempFile::new()?;
+        GitDetector::create_bundle(Path::new(&repo.path), &bundle.path)?;
+        MinioUtil::upload_file(&bundle.path.to_string_lossy(), bucket, bundle_key)
+    }
+
+    /// Files and folders of the last known remote tree that were deleted here, topmost first
+    /// Anything the scan left out (skipped folders, ignored entries) but still exists is not a deletion
+    pub fn deleted_paths(known_remote: &FileNode, current_tree: &FileNode, root: &Path) -> Vec<String> {
+        let candidates = known_remote.get_all_folders().into_iter().chain(known_remote.get_all_objects());
+        let deleted = candidates
+            .filter(|node| !is_internal_path(&node.relative_path))
+            .filter(|node| current_tree.find_by_relative_path(&node.relative_path).is_none())
+            .filter(|node| !current_tree.is_unscanned(&node.relative_path))
+            .filter(|node| std::fs::symlink_metadata(Self::local_path(Some(current_tree), root, &node.relative_path)).is_err())
+            .map(|node| node.relative_path.clone());
+        topmost_paths(deleted)
+    }
+
+    /// Publish the deletions seen here as tombstones on `current_tree`, returns the newly deleted paths
+    /// Tombstones of the last known remote tree are kept until their path exists again or they expire
+    pub fn record_deletions(current_tree: &mut FileNode, known_remote: Option<&FileNode>, root: &Path) -> Vec<String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let Some(known_remote) = known_remote else {
+            current_tree.tombstones = Vec::new();
+            return Vec::new();
+        };
+
+        let deleted = Self::deleted_paths(known_remote, current_tree, root);
+        let mut tombstones: Vec<Tombstone> = known_remote
+            .tombstones
+            .iter()
+            .filter(|tombstone| current_tree.find_by_relative_path(&tombstone.path).is_none())
+            .filter(|tombstone| now.saturating_sub(tombstone.deleted_at) < TOMBSTONE_RETENTION)
+            .filter(|tombstone| !deleted.iter().any(|path| {
+                tombstone.path == *path || tombstone.path.starts_with(&format!("{}/", path))
+            }))
+            .cloned()
+            .collect();
+        tombstones.extend(deleted.iter().map(|path| Tombstone {
+            path: path.clone(),
+            deleted_at: now,
+            machine_id: get_machine_id(),
+        }));
+
+        current_tree.tombstones = tombstones;
+        deleted
+    }
+
+    /// Folders another machine deleted since the previous remote tree
+    pub fn folders_removed_remotely(previous_remote: &FileNode, remote: &FileNode) -> Vec<String> {
+        let removed = previous_remote
+            .get_all_folders()
+            .into_iter()
+            .filter(|folder| remote.find_by_relative_path(&folder.relative_path).is_none())
+            .filter(|folder| remote.is_deleted(&folder.relative_path))
+            .map(|folder| folder.relative_path.clone());
+        topmost_paths(removed)
+    }
+
+    /// Where the object `key` lives below `root`, using the on-disk names `local_tree` found for its existing parts
+    /// Keys are normalized (NFC by default) but names on disk may not be, so joining the key alone could miss
+    /// an existing file or folder and create a twin next to it
+    pub fn local_path(local_tree: Option<&FileNode>, root: &Path, key: &str) -> PathBuf {
+        let mut path = root.to_path_buf();
+        let mut node = local_tree;
+        let components: Vec<&str> = key.split('/').collect();
+
+        for i in 0..components.len() {
+            let prefix = components[..=i].join("/");
+            let Some(child) = node.and_then(|node| node.children.iter().flatten().find(|child| child.relative_path == prefix))
+            else {
+                return path.join(key_to_local(&components[i..].join("/")));
+            };
+            path = PathBuf::from(&child.path);
+            node = Some(child);
+        }
+
+        path
+    }
+
+    /// Create every folder of the remote tree locally, so empty folders survive a clone
+    /// Folders `local_tree` already has are kept under their on-disk names
+    pub fn restore_folders(remote_tree: &FileNode, local_tree: Option<&FileNode>, root: &Path) -> Result<(), String> {
+        for folder in remote_tree.get_all_folders() {
+            let local_path = Self::local_path(local_tree, root, &folder.relative_path);
+            std::fs::create_dir_all(&local_path)
+                .map_err(|e| format!("Failed to create directory {}: {}", local_path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Apply folder modes from the remote tree, deepest first so read-only parents come last
+    pub fn apply_folder_permissions(remote_tree: &FileNode, local_tree: Option<&FileNode>, root: &Path) -> Result<(), String> {
+        for folder in remote_tree.get_all_folders().into_iter().rev() {
+            let local_path = Self::local_path(local_tree, root, &folder.relative_path);
+            if local_path.is_dir() {
+                Self::apply_permissions(folder, &local_path)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Upload a single file to MinIO using the existing MinioUtil
     fn upload_file_to_minio(file: &FileNode, bucket: &str) -> Result<(), String> {
         use crate::core::minio_util::MinioUtil;
@@ -486,7 +1351,135 @@ impl SyncManager {
         // Use relative path as the object name for cross-platform compatibility
         let object_name = &file.relative_path;
 
-        MinioUtil::upload_file(&file.path, bucket, object_name)
+        if file.is_symlink() {
+            return Self::upload_symlink(file, bucket);
+        }
+
+        let metadata = file.object_metadata();
+        if metadata.is_empty() {
+            MinioUtil::upload_file(&file.path, bucket, object_name)
+        } else {
+            MinioUtil::upload_file_with_metadata(&file.path, bucket, object_name, &metadata)
+        }
+    }
+
+    /// Upload a link as a small object holding its target, marked as a symlink in the metadata
+    fn upload_symlink(link: &FileNode, bucket: &str) -> Result<(), String> {
+        use crate::core::minio_util::MinioUtil;
+
+        let target = link.link_target.as_deref().unwrap_or_default();
+        let placeholder = TempFile::new()?;
+        std::fs::write(&placeholder.path, target)
+            .map_err(|e| format!("Failed to write link placeholder: {}", e))?;
+
+        let metadata = BTreeMap::from([
+            (OBJECT_TYPE_METADATA.to_string(), "symlink".to_string()),
+            (LINK_TARGET_METADATA.to_string(), target.to_string()),
+        ]);
+        MinioUtil::upload_file_with_metadata(
+            &placeholder.path.to_string_lossy(),
+            bucket,
+            &link.relative_path,
+            &metadata,
+        )
+    }
+
+    /// Download one object into `local_path`, re-creating it as a link if the remote tree says it is one
+    pub fn download_entry(
+        bucket: &str,
+        object_name: &str,
+        local_path: &str,
+        remote_tree: Option<&FileNode>,
+    ) -> Result<(), String> {
+        use crate::core::minio_util::MinioUtil;
+
+        let remote_node = remote_tree.and_then(|tree| tree.find_by_relative_path(object_name));
+        if let Some(link) = remote_node.filter(|node| node.is_symlink()) {
+            let target = link.link_target.as_deref().unwrap_or_default();
+            return Self::create_symlink(target, Path::new(local_path));
+        }
+
+        MinioUtil::download_file(bucket, object_name, local_path)?;
+
+        if let Some(file) = remote_node {
+            // Keep the pushing machine's mtime, so the next push describes the file the same way
+            if let Some(modified) = file.modified {
+                File::options()
+                    .write(true)
+                    .open(local_path)
+                    .and_then(|f| f.set_modified(UNIX_EPOCH + Duration::from_secs(modified)))
+                    .map_err(|e| format!("Failed to set mtime on {}: {}", local_path, e))?;
+            }
+            Self::apply_permissions(file, Path::new(local_path))?;
+            // tmpfs, NFS or a mount without user_xattr may refuse them, the contents are still good
+            if let Some(xattrs) = &file.xattrs
+                && let Err(e) = XattrUtil::apply(Path::new(local_path), xattrs)
+            {
+                eprintln!("Warning: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the mode and ownership recorded for a file to its local copy
+    #[cfg(unix)]
+    pub fn apply_permissions(file: &FileNode, local_path: &Path) -> Result<(), String> {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Some(mode) = file.mode {
+            std::fs::set_permissions(local_path, std::fs::Permissions::from_mode(mode))
+                .map_err(|e| format!("Failed to set mode on {}: {}", local_path.display(), e))?;
+        }
+
+        if file.uid.is_some() || file.gid.is_some() {
+            match std::os::unix::fs::chown(local_path, file.uid, file.gid) {
+                Ok(()) => {}
+                // Only root may give files away, everyone else keeps their own ownership
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {}
+                Err(e) => {
+                    return Err(format!("Failed to set owner on {}: {}", local_path.display(), e));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply_permissions(_file: &FileNode, _local_path: &Path) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Create (or replace) a symlink at `link_path` pointing to `target`
+    pub fn create_symlink(target: &str, link_path: &Path) -> Result<(), String> {
+        if let Some(parent) = link_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
+        }
+
+        // Replace whatever is there, but never follow an existing link while doing so
+        if let Ok(existing) = std::fs::symlink_metadata(link_path) {
+            if existing.is_dir() {
+                return Err(format!("{} is a directory", link_path.display()));
+            }
+            std::fs::remove_file(link_path)
+                .map_err(|e| format!("Failed to replace {}: {}", link_path.display(), e))?;
+        }
+
+        #[cfg(unix)]
+        let result = std::os::unix::fs::symlink(target, link_path);
+
+        #[cfg(windows)]
+        let result = {
+            let resolved = link_path.parent().unwrap_or(Path::new(".")).join(target);
+            if resolved.is_dir() {
+                std::os::windows::fs::symlink_dir(target, link_path)
+            } else {
+                std::os::windows::fs::symlink_file(target, link_path)
+            }
+        };
+
+        result.map_err(|e| format!("Failed to create symlink {}: {}", link_path.display(), e))
     }
 
     /// Mark a file as synced by updating its last_synced timestamp
@@ -503,6 +1496,8 @@ impl SyncManager {
 
         if let Some(file) = Self::find_file_by_relative_path_mut(file_tree, relative_path) {
             file.last_synced = Some(current_time);
+            file.synced_mode = file.mode;
+            file.synced_xattrs = file.xattrs.clone();
             Ok(())
         } else {
             Err(format!("File not found: {}", relative_path))
@@ -557,7 +1552,7 @@ impl SyncManager {
 
         // Check for files that exist remotely but not locally
         for remote_file in remote_files {
-            if !local_tree.find_by_path(&remote_file.path).is_some() {
+            if local_tree.find_by_path(&remote_file.path).is_none() {
                 sync_plan.push((remote_file.relative_path.clone(), SyncAction::Download));
             }
         }
@@ -580,6 +1575,17 @@ impl SyncManager {
                         println!("Uploaded: {}", relative_path);
                     }
                 }
+                SyncAction::UpdateMetadata => {
+                    if let Some(file) = Self::find_file_by_relative_path(local_tree, &relative_path)
+                    {
+                        crate::core::minio_util::MinioUtil::update_object_metadata(
+                            bucket,
+                            &relative_path,
+                            &file.object_metadata(),
+                        )?;
+                        println!("Updated metadata: {}", relative_path);
+                    }
+                }
                 SyncAction::Download => {
                     // TODO: Implement download functionality
                     println!(
@@ -624,19 +1630,18 @@ impl BucketManager {
     pub fn detect_bucket_name(folder_path: &str) -> Result<String, String> {
         // First, check if there's a structure file with bucket info
         let structure_file = format!("{}/rusty-sync-structure.json", folder_path);
-        if Path::new(&structure_file).exists() {
-            if let Ok(bucket) = Self::read_bucket_from_structure(&structure_file) {
-                return Ok(bucket);
-            }
+        if Path::new(&structure_file).exists()
+            && let Ok(bucket) = Self::read_bucket_from_structure(&structure_file)
+        {
+            return Ok(bucket);
         }
         
         // Second, check if there's a .rusty-sync-config file in the folder
         let config_file = format!("{}/.rusty-sync-bucket", folder_path);
-        if Path::new(&config_file).exists() {
-            match std::fs::read_to_string(&config_file) {
-                Ok(bucket) => return Ok(bucket.trim().to_string()),
-                Err(_) => {}
-            }
+        if Path::new(&config_file).exists()
+            && let Ok(bucket) = std::fs::read_to_string(&config_file)
+        {
+            return Ok(bucket.trim().to_string());
         }
         
         // Third, try to infer from folder name
@@ -666,12 +1671,273 @@ impl BucketManager {
             .map_err(|e| format!("Failed to read structure file: {}", e))?;
             
         // Try to parse as JSON and look for bucket metadata
-        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&file_content) {
-            if let Some(bucket) = json.get("bucket_name").and_then(|b| b.as_str()) {
-                return Ok(bucket.to_string());
-            }
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&file_content)
+            && let Some(bucket) = json.get("bucket_name").and_then(|b| b.as_str())
+        {
+            return Ok(bucket.to_string());
         }
         
         Err("No bucket information found in structure file".to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::walker::SymlinkPolicy;
+    use crate::utils::temp_dir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policies() {
+        let root = temp_dir("symlinks");
+        let outside = temp_dir("symlinks-outside");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs/a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink("docs", root.join("inside")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("outside")).unwrap();
+        std::os::unix::fs::symlink("missing", root.join("dangling")).unwrap();
+
+        let scan = |symlinks| {
+            let settings = FolderSettings {
+                symlinks,
+                ..Default::default()
+            };
+            ReadFileTree::scan_tree_with_settings(&root.to_string_lossy(), "test", &settings)
+                .unwrap()
+                .tree
+        };
+
+        let tree = scan(SymlinkPolicy::Ignore);
+        assert!(tree.find_by_relative_path("inside").is_none());
+        assert!(tree.find_by_relative_path("dangling").is_none());
+
+        let tree = scan(SymlinkPolicy::Store);
+        let link = tree.find_by_relative_path("inside").unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(link.link_target.as_deref(), Some("docs"));
+        assert!(link.needs_sync());
+        assert!(tree.find_by_relative_path("dangling").unwrap().is_symlink());
+
+        // Only the link that stays inside the root is followed
+        let tree = scan(SymlinkPolicy::FollowInside);
+        assert!(tree.find_by_relative_path("inside/a.txt").is_some());
+        assert!(tree.find_by_relative_path("outside").unwrap().is_symlink());
+        assert!(tree.find_by_relative_path("dangling").unwrap().is_symlink());
+
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_dir_all(&outside);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_permission_only_changes_are_tracked_apart() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = temp_dir("permissions");
+        let script = root.join("run.sh");
+        std::fs::write(&script, "#!/bin/sh").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let path = root.to_string_lossy().to_string();
+        let mut tree = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+        assert_eq!(tree.find_by_relative_path("run.sh").unwrap().mode, Some(0o644));
+
+        // Pretend the file was uploaded well after it was written
+        SyncManager::mark_file_as_synced(&mut tree, "run.sh").unwrap();
+        let synced = SyncManager::find_file_by_relative_path_mut(&mut tree, "run.sh").unwrap();
+        synced.last_synced = synced.modified.map(|modified| modified + 10);
+
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut rescanned = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+        ReadFileTree::merge_sync_metadata(&mut rescanned, &tree);
+
+        let file = rescanned.find_by_relative_path("run.sh").unwrap();
+        assert!(!file.content_changed());
+        assert!(file.permissions_changed());
+        assert!(file.needs_sync());
+        assert_eq!(file.permission_metadata()[MODE_METADATA], "755");
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_unreadable_xattrs_keep_their_last_value() {
+        let root = temp_dir("xattrs-unreadable");
+        std::fs::write(root.join("tagged.txt"), "data").unwrap();
+        let path = root.to_string_lossy().to_string();
+        let mut tree = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+        let file = SyncManager::find_file_by_relative_path_mut(&mut tree, "tagged.txt").unwrap();
+        file.xattrs = Some(BTreeMap::from([("user.tag".to_string(), "6869".to_string())]));
+        SyncManager::mark_file_as_synced(&mut tree, "tagged.txt").unwrap();
+
+        let mut rescanned = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+        let file = SyncManager::find_file_by_relative_path_mut(&mut rescanned, "tagged.txt").unwrap();
+        file.xattrs = None;
+        file.xattrs_unreadable = true;
+        ReadFileTree::merge_sync_metadata(&mut rescanned, &tree);
+
+        let file = rescanned.find_by_relative_path("tagged.txt").unwrap();
+        assert_eq!(file.xattrs, tree.find_by_relative_path("tagged.txt").unwrap().xattrs);
+        assert!(!file.needs_sync());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_empty_and_deleted_folders() {
+        let root = temp_dir("folders");
+        std::fs::create_dir_all(root.join("empty")).unwrap();
+        std::fs::create_dir_all(root.join("gone/nested")).unwrap();
+        let path = root.to_string_lossy().to_string();
+        let before = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+
+        // Empty folders are part of the tree and come back on restore
+        let restored = temp_dir("folders-restored");
+        SyncManager::restore_folders(&before, None, &restored).unwrap();
+        assert!(restored.join("empty").is_dir());
+        assert!(restored.join("gone/nested").is_dir());
+
+        // Only the topmost deleted folder gets a tombstone
+        std::fs::remove_dir_all(root.join("gone")).unwrap();
+        let mut after = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+        assert_eq!(SyncManager::record_deletions(&mut after, Some(&before), &root), vec!["gone".to_string()]);
+        assert_eq!(after.tombstones.len(), 1);
+        assert_eq!(SyncManager::folders_removed_remotely(&before, &after), vec!["gone".to_string()]);
+
+        // Without the tombstone a missing folder is not a deletion
+        let mut untold = after.clone();
+        untold.tombstones.clear();
+        assert!(SyncManager::folders_removed_remotely(&before, &untold).is_empty());
+
+        // The tombstone is carried over until the folder comes back
+        let mut later = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+        assert!(SyncManager::record_deletions(&mut later, Some(&after), &root).is_empty());
+        assert_eq!(later.tombstones, after.tombstones);
+        std::fs::create_dir_all(root.join("gone")).unwrap();
+        let mut recreated = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+        SyncManager::record_deletions(&mut recreated, Some(&later), &root);
+        assert!(recreated.tombstones.is_empty());
+
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_dir_all(&restored);
+    }
+
+    #[test]
+    fn test_nfd_names_are_not_deletions_or_twins() {
+        let root = temp_dir("nfd");
+        let nfd = root.join("cafe\u{301}");
+        std::fs::create_dir_all(&nfd).unwrap();
+        std::fs::write(nfd.join("x.txt"), "x").unwrap();
+        let path = root.to_string_lossy().to_string();
+        let known = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+
+        // Keys are NFC, the NFC name itself doesn't exist here
+        assert!(known.find_by_relative_path("caf\u{e9}/x.txt").is_some());
+        assert!(!root.join("caf\u{e9}").exists());
+
+        let mut current = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+        assert!(SyncManager::deleted_paths(&known, &current, &root).is_empty());
+        assert!(SyncManager::record_deletions(&mut current, Some(&known), &root).is_empty());
+
+        // Pulls write into the folder that is there instead of creating an NFC twin
+        assert_eq!(SyncManager::local_path(Some(&current), &root, "caf\u{e9}/x.txt"), nfd.join("x.txt"));
+        assert_eq!(SyncManager::local_path(Some(&current), &root, "caf\u{e9}/new/y.txt"), nfd.join("new/y.txt"));
+        SyncManager::restore_folders(&known, Some(&current), &root).unwrap();
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 1);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_skipped_folders_are_not_deletions() {
+        let root = temp_dir("skipped");
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        std::fs::write(root.join("a/b/deep.txt"), "deep").unwrap();
+        let path = root.to_string_lossy().to_string();
+        let full = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+
+        let settings = FolderSettings {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let shallow = ReadFileTree::scan_tree_with_settings(&path, "test", &settings).unwrap().tree;
+
+        // "a/b" stays in the tree without contents, so nothing below it counts as removed
+        let skipped = shallow.find_by_relative_path("a/b").unwrap();
+        assert_eq!(skipped.skipped, Some(SkipReason::MaxDepth));
+        assert!(skipped.children.is_none());
+        assert!(shallow.is_unscanned("a/b/deep.txt"));
+        assert!(!shallow.is_unscanned("a/other.txt"));
+        assert!(SyncManager::folders_removed_remotely(&full, &shallow).is_empty());
+        assert!(crate::utils::remote_watcher::diff_manifests(Some(&full), &shallow).removed.is_empty());
+
+        // Pushing the shallow scan publishes no deletion for what it didn't look at
+        let mut pushed = shallow.clone();
+        assert!(SyncManager::record_deletions(&mut pushed, Some(&full), &root).is_empty());
+        assert!(!pushed.is_deleted("a/b/deep.txt"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_scans_write_nothing_into_the_folder() {
+        let root = temp_dir("scan-writes");
+        let path = root.to_string_lossy().to_string();
+
+        let tree = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+        assert!(tree.get_all_files().is_empty());
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+
+        // A file with the name of the old debug file is the user's, left in place and synced like any other
+        let contents = format!(r#"{{ "root": "{}" }}"#, path);
+        std::fs::write(root.join("EXAMPLE.struct_git.json"), &contents).unwrap();
+        let tree = ReadFileTree::generate_tree_with_sync_data(&path, "test").unwrap();
+        assert!(tree.find_by_relative_path("EXAMPLE.struct_git.json").unwrap().needs_sync());
+        assert_eq!(std::fs::read_to_string(root.join("EXAMPLE.struct_git.json")).unwrap(), contents);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_rescan_paths_matches_a_full_scan() {
+        let root = temp_dir("rescan");
+        std::fs::create_dir_all(root.join("keep")).unwrap();
+        std::fs::create_dir_all(root.join("drop/inner")).unwrap();
+        std::fs::write(root.join("keep/a.txt"), "a").unwrap();
+        let path = root.to_string_lossy().to_string();
+        let settings = FolderSettings::default();
+        let mut tree = ReadFileTree::generate_tree_with_settings(&path, "test", &settings).unwrap();
+
+        std::fs::remove_dir_all(root.join("drop")).unwrap();
+        std::fs::create_dir_all(root.join("new/deep")).unwrap();
+        std::fs::write(root.join("new/deep/b.txt"), "b").unwrap();
+        std::fs::write(root.join("keep/a.txt"), "changed").unwrap();
+
+        let changed = [
+            root.join("drop/inner"),
+            root.join("drop"),
+            root.join("new/deep/b.txt"),
+            root.join("new"),
+            root.join("keep/a.txt"),
+            root.join("rusty-sync-structure.json"),
+        ];
+        let rescan = ReadFileTree::rescan_paths(&mut tree, &path, &settings, &changed);
+        assert_eq!(rescan.updated, vec!["drop", "keep/a.txt", "new"]);
+
+        let paths = |tree: &FileNode| {
+            let mut paths: Vec<String> = tree
+                .get_all_folders()
+                .into_iter()
+                .chain(tree.get_all_files())
+                .map(|node| node.relative_path.clone())
+                .collect();
+            paths.sort();
+            paths
+        };
+        let full = ReadFileTree::generate_tree_with_settings(&path, "test", &settings).unwrap();
+        assert_eq!(paths(&tree), paths(&full));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,324 @@
+// shared directory walker used by the tree generator, git ignore scan and folder manager
+// unreadable paths are collected as errors instead of aborting the whole walk
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// What a scan does with symbolic links found inside the sync folder
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    /// Leave links out of the tree entirely
+    Ignore,
+    /// Store the link itself with its target and re-create it on pull
+    #[default]
+    Store,
+    /// Follow links whose target stays inside the sync root, store the others as links
+    FollowInside,
+}
+
+impl SymlinkPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SymlinkPolicy::Ignore => "ignore",
+            SymlinkPolicy::Store => "store",
+            SymlinkPolicy::FollowInside => "follow-inside",
+        }
+    }
+}
+
+impl fmt::Display for SymlinkPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(SymlinkPolicy::Ignore),
+            "store" => Ok(SymlinkPolicy::Store),
+            "follow-inside" => Ok(SymlinkPolicy::FollowInside),
+            _ => Err(format!(
+                "Unknown symlink policy '{}'. Use ignore, store or follow-inside",
+                s
+            )),
+        }
+    }
+}
+
+/// Check if a symlink resolves to somewhere inside `root` (both must exist)
+pub fn symlink_stays_inside(link: &Path, root: &Path) -> bool {
+    match (fs::canonicalize(link), fs::canonicalize(root)) {
+        (Ok(target), Ok(root)) => target.starts_with(root),
+        _ => false,
+    }
+}
+
+/// Options controlling how far a walk goes
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    pub max_depth: Option<usize>, // Deepest folder level to descend into, root is depth 0
+    pub one_file_system: bool,    // Don't cross into other mounted filesystems
+}
+
+/// A path that could not be read during a walk
+#[derive(Debug, Clone)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+/// Why a walk left a folder out
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipReason {
+    MaxDepth,        // Deeper than max-depth
+    OtherFileSystem, // On another filesystem with one-file-system set
+    Loop,            // A symlink leading back to a folder being walked
+    Unreadable,      // Could not be read
+}
+
+/// A single directory entry found by the walker
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub is_dir: bool,     // Follows symlinks, like Path::is_dir
+    pub is_file: bool,    // Follows symlinks, like Path::is_file
+    pub is_symlink: bool, // The entry itself is a symbolic link
+}
+
+impl WalkEntry {
+    /// Inspect a single path the way a walk would, None if it no longer exists
+    pub fn from_path(path: &Path) -> Option<WalkEntry> {
+        let is_symlink = fs::symlink_metadata(path).ok()?.file_type().is_symlink();
+        let (is_dir, is_file) = fs::metadata(path)
+            .map(|metadata| (metadata.is_dir(), metadata.is_file()))
+            .unwrap_or((false, false));
+
+        Some(WalkEntry {
+            name: path.file_name()?.to_string_lossy().to_string(),
+            path: path.to_path_buf(),
+            is_dir,
+            is_file,
+            is_symlink,
+        })
+    }
+}
+
+/// Identity of a directory used to detect loops and filesystem boundaries
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DirIdentity {
+    device: u64,
+    id: DirId,
+}
+
+#[cfg(unix)]
+type DirId = u64; // inode number
+
+#[cfg(not(unix))]
+type DirId = PathBuf; // canonical path
+
+#[cfg(unix)]
+fn dir_identity(path: &Path) -> std::io::Result<DirIdentity> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path)?;
+    Ok(DirIdentity {
+        device: metadata.dev(),
+        id: metadata.ino(),
+    })
+}
+
+#[cfg(not(unix))]
+fn dir_identity(path: &Path) -> std::io::Result<DirIdentity> {
+    // No portable device id here, so one_file_system has no effect on these platforms
+    Ok(DirIdentity {
+        device: 0,
+        id: fs::canonicalize(path)?,
+    })
+}
+
+pub struct DirWalker {
+    options: WalkOptions,
+    errors: Vec<WalkError>,
+    ancestors: Vec<DirIdentity>, // Directories currently being walked, root first
+}
+
+impl DirWalker {
+    pub fn new(options: WalkOptions) -> Self {
+        DirWalker {
+            options,
+            errors: Vec::new(),
+            ancestors: Vec::new(),
+        }
+    }
+
+    /// Paths that could not be read so far
+    pub fn errors(&self) -> &[WalkError] {
+        &self.errors
+    }
+
+    /// Take the collected errors, leaving the walker with none
+    pub fn take_errors(&mut self) -> Vec<WalkError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Record a path that could not be read
+    pub fn record_error(&mut self, path: &Path, message: impl Into<String>) {
+        self.errors.push(WalkError {
+            path: path.to_path_buf(),
+            message: message.into(),
+        });
+    }
+
+    /// Read `dir` at `depth` and hand its entries to `visit`
+    /// Returns why the folder was skipped instead when it is too deep, on another filesystem, a loop or unreadable
+    pub fn descend<T>(
+        &mut self,
+        dir: &Path,
+        depth: usize,
+        visit: impl FnOnce(&mut Self, Vec<WalkEntry>) -> T,
+    ) -> Result<T, SkipReason> {
+        if self.options.max_depth.is_some_and(|max| depth > max) {
+            return Err(SkipReason::MaxDepth);
+        }
+
+        let identity = match dir_identity(dir) {
+            Ok(identity) => identity,
+            Err(e) => {
+                self.record_error(dir, e.to_string());
+                return Err(SkipReason::Unreadable);
+            }
+        };
+
+        if self.options.one_file_system
+            && let Some(root) = self.ancestors.first()
+            && root.device != identity.device
+        {
+            return Err(SkipReason::OtherFileSystem);
+        }
+
+        // Seeing an ancestor again means a symlink leads back up the tree
+        if self.ancestors.contains(&identity) {
+            self.record_error(dir, "symlink loop detected, not descending");
+            return Err(SkipReason::Loop);
+        }
+
+        let entries = match self.read_entries(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.record_error(dir, e.to_string());
+                return Err(SkipReason::Unreadable);
+            }
+        };
+
+        self.ancestors.push(identity);
+        let result = visit(self, entries);
+        self.ancestors.pop();
+        Ok(result)
+    }
+
+    /// List one directory, recording entries that can't be inspected
+    fn read_entries(&mut self, dir: &Path) -> std::io::Result<Vec<WalkEntry>> {
+        let mut entries = Vec::new();
+
+        for entry in fs::read_dir(dir)? {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.record_error(dir, format!("failed to read entry: {}", e));
+                    continue;
+                }
+            };
+
+            let path = entry.path();
+            let is_symlink = entry.file_type().map(|t| t.is_symlink()).unwrap_or(false);
+            let (is_dir, is_file) = match fs::metadata(&path) {
+                Ok(metadata) => (metadata.is_dir(), metadata.is_file()),
+                Err(e) => {
+                    // A dangling symlink is not an error, it simply points nowhere
+                    if !is_symlink {
+                        self.record_error(&path, e.to_string());
+                    }
+                    (false, false)
+                }
+            };
+
+            entries.push(WalkEntry {
+                name: entry.file_name().to_string_lossy().to_string(),
+                path,
+                is_dir,
+                is_file,
+                is_symlink,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Walk everything below `root` and return a flat list of entries, folders before their contents
+    pub fn walk(&mut self, root: &Path) -> Vec<WalkEntry> {
+        let mut found = Vec::new();
+        self.walk_into(root, 0, &mut found);
+        found
+    }
+
+    fn walk_into(&mut self, dir: &Path, depth: usize, found: &mut Vec<WalkEntry>) {
+        let _ = self.descend(dir, depth, |walker, entries| {
+            for entry in entries {
+                let is_dir = entry.is_dir;
+                let path = entry.path.clone();
+                found.push(entry);
+                if is_dir {
+                    walker.walk_into(&path, depth + 1, found);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp_dir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_stops_at_symlink_loops_and_max_depth() {
+        let root = temp_dir("walker");
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::write(root.join("a/b/c/deep.txt"), "deep").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("a/loop")).unwrap();
+
+        // The loop is reported once and the walk still finishes
+        let mut walker = DirWalker::new(WalkOptions::default());
+        let entries = walker.walk(&root);
+        assert!(entries.iter().any(|e| e.path.ends_with("a/b/c/deep.txt")));
+        assert!(entries.iter().any(|e| e.path.ends_with("a/loop") && e.is_symlink));
+        assert_eq!(walker.errors().len(), 1);
+        assert!(walker.errors()[0].path.ends_with("a/loop"));
+
+        // Depth 1 lists the contents of "a" but not of "a/b"
+        let mut walker = DirWalker::new(WalkOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        });
+        let entries = walker.walk(&root);
+        assert!(entries.iter().any(|e| e.path.ends_with("a/b")));
+        assert!(!entries.iter().any(|e| e.path.ends_with("a/b/c")));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
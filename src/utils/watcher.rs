@@ -0,0 +1,155 @@
+// watches a sync folder for changes and hands them out in debounced batches
+// uses the platform watcher from the notify crate (inotify on Linux)
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Longest a batch is held back while events keep arriving
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(30);
+
+/// Changes collected during one burst of events
+#[derive(Debug, Default)]
+pub struct WatchBatch {
+    pub paths: BTreeSet<PathBuf>, // Every path that was reported as changed
+    pub rescan: bool,             // Events were lost (queue overflow), only a full rescan is safe
+}
+
+pub struct FolderWatcher {
+    _watcher: RecommendedWatcher, // Stops watching when dropped
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl FolderWatcher {
+    /// Start watching everything below `root`
+    pub fn new(root: &Path) -> Result<Self, String> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })
+        .map_err(|e| format!("Failed to create watcher: {}", e))?;
+
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", root.display(), e))?;
+
+        Ok(FolderWatcher {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Wait up to `timeout` (forever if None) for changes, then keep collecting until the folder
+    /// has been quiet for `debounce`. Ok(None) means nothing changed before the timeout
+    pub fn wait_for_changes(
+        &self,
+        debounce: Duration,
+        timeout: Option<Duration>,
+    ) -> Result<Option<WatchBatch>, String> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let first = match deadline {
+                Some(deadline) => match self.events.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => return Ok(None),
+                    Err(RecvTimeoutError::Disconnected) => return Err("Watcher stopped".to_string()),
+                },
+                None => self.events.recv().map_err(|_| "Watcher stopped".to_string())?,
+            };
+
+            let mut batch = WatchBatch::default();
+            Self::add_event(&mut batch, first);
+
+            let started = Instant::now();
+            while started.elapsed() < MAX_BATCH_DELAY {
+                // Steady reads must not hold the caller past its timeout
+                if batch.paths.is_empty() && !batch.rescan && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return Ok(None);
+                }
+                match self.events.recv_timeout(debounce) {
+                    Ok(event) => Self::add_event(&mut batch, event),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return Err("Watcher stopped".to_string()),
+                }
+            }
+
+            // Reads don't change anything, a batch of only those is not worth a push
+            if !batch.paths.is_empty() || batch.rescan {
+                return Ok(Some(batch));
+            }
+        }
+    }
+
+    fn add_event(batch: &mut WatchBatch, event: notify::Result<Event>) {
+        match event {
+            Ok(event) if event.need_rescan() => batch.rescan = true,
+            Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+            Ok(event) => batch.paths.extend(event.paths),
+            Err(e) => {
+                // The backend could not tell us what changed, so assume anything did
+                eprintln!("Warning: Watch error, falling back to a full rescan: {}", e);
+                batch.rescan = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_of_changes_arrives_as_one_batch() {
+        let root = std::env::temp_dir().join(format!("rusty-sync-watch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let watcher = FolderWatcher::new(&root).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(root.join(name), name).unwrap();
+        }
+
+        let batch = watcher
+            .wait_for_changes(Duration::from_millis(200), Some(Duration::from_secs(5)))
+            .unwrap()
+            .expect("no events within 5s");
+        assert!(!batch.rescan);
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            assert!(batch.paths.contains(&root.join(name)), "{} missing", name);
+        }
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_reads_alone_time_out() {
+        let root = std::env::temp_dir().join(format!("rusty-sync-watch-reads-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("read.txt");
+        std::fs::write(&file, "read me").unwrap();
+
+        let watcher = FolderWatcher::new(&root).unwrap();
+        let reading = file.clone();
+        let reader = std::thread::spawn(move || {
+            let started = Instant::now();
+            while started.elapsed() < Duration::from_secs(4) {
+                let _ = std::fs::read(&reading);
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        });
+
+        let started = Instant::now();
+        let batch = watcher
+            .wait_for_changes(Duration::from_millis(200), Some(Duration::from_secs(1)))
+            .unwrap();
+        assert!(batch.is_none());
+        assert!(started.elapsed() < Duration::from_secs(3), "took {:?}", started.elapsed());
+
+        reader.join().unwrap();
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
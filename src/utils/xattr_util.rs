@@ -0,0 +1,130 @@
+// reads and writes extended attributes (user.* tags and POSIX ACLs) for files in a sync folder
+// values are kept hex encoded so they survive the JSON tree and S3 object metadata untouched
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Attributes that are captured: user tags plus the POSIX ACLs, which live in system.* xattrs
+#[cfg(unix)]
+fn is_captured(name: &str) -> bool {
+    name.starts_with("user.") || name == "system.posix_acl_access" || name == "system.posix_acl_default"
+}
+
+#[cfg(unix)]
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(unix)]
+fn from_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(format!("Invalid hex value '{}'", hex));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("Invalid hex value '{}'", hex))
+        })
+        .collect()
+}
+
+pub struct XattrUtil;
+
+impl XattrUtil {
+    /// Read the captured extended attributes of a file as name -> hex value
+    #[cfg(unix)]
+    pub fn read(path: &Path) -> Result<BTreeMap<String, String>, String> {
+        let mut attributes = BTreeMap::new();
+
+        let names = match xattr::list(path) {
+            Ok(names) => names,
+            // Filesystems without xattr support simply have none
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => return Ok(attributes),
+            Err(e) => return Err(format!("Failed to list xattrs of {}: {}", path.display(), e)),
+        };
+
+        for name in names {
+            // Object metadata only carries ASCII, skip anything else
+            let Some(name) = name.to_str().filter(|n| n.is_ascii() && is_captured(n)) else {
+                continue;
+            };
+
+            if let Some(value) = xattr::get(path, name)
+                .map_err(|e| format!("Failed to read xattr {} of {}: {}", name, path.display(), e))?
+            {
+                attributes.insert(name.to_string(), to_hex(&value));
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    #[cfg(not(unix))]
+    pub fn read(_path: &Path) -> Result<BTreeMap<String, String>, String> {
+        Ok(BTreeMap::new())
+    }
+
+    /// Set extended attributes on a file from name -> hex value
+    /// Every attribute is tried, the error lists the ones that couldn't be set
+    #[cfg(unix)]
+    pub fn apply(path: &Path, attributes: &BTreeMap<String, String>) -> Result<(), String> {
+        let failures: Vec<String> = attributes
+            .iter()
+            .filter_map(|(name, value)| {
+                from_hex(value)
+                    .and_then(|value| xattr::set(path, name, &value).map_err(|e| e.to_string()))
+                    .err()
+                    .map(|e| format!("{} ({})", name, e))
+            })
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Failed to set xattrs on {}: {}", path.display(), failures.join(", ")))
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply(_path: &Path, _attributes: &BTreeMap<String, String>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hex_round_trip() {
+        let value = b"provenance:\x00\xffrun-42";
+        assert_eq!(from_hex(&to_hex(value)).unwrap(), value);
+        assert!(from_hex("abc").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_and_apply_user_attributes() {
+        let dir = std::env::temp_dir().join(format!("rusty-sync-xattrs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.dat");
+        let copy = dir.join("copy.dat");
+        std::fs::write(&source, "data").unwrap();
+        std::fs::write(&copy, "data").unwrap();
+
+        // Not every filesystem the tests run on supports user xattrs
+        if xattr::set(&source, "user.provenance", b"run-42").is_err() {
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        }
+
+        let attributes = XattrUtil::read(&source).unwrap();
+        assert_eq!(attributes.get("user.provenance").map(String::as_str), Some("72756e2d3432"));
+
+        XattrUtil::apply(&copy, &attributes).unwrap();
+        assert_eq!(xattr::get(&copy, "user.provenance").unwrap(), Some(b"run-42".to_vec()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,108 @@
+// helpers shared by the end-to-end tests, which run rusty-sync against the fake S3 server
+// not every test uses all of them
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Kills the wrapped process when the test ends, however it ends
+pub struct Background(pub Child);
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+pub fn project_root() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Start the fake server with extra `args` and return it with its endpoint URL
+pub fn start_fake_server(args: &[&str]) -> Option<(Background, String)> {
+    let boto3 = Command::new("python3")
+        .args(["-c", "import boto3"])
+        .output();
+    if !boto3.is_ok_and(|output| output.status.success()) {
+        eprintln!("python3 with boto3 not available, skipping");
+        return None;
+    }
+
+    let mut server = Command::new("python3")
+        .arg(project_root().join("src/core/minio/fake_server.py"))
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let mut line = String::new();
+    BufReader::new(server.stdout.take()?)
+        .read_line(&mut line)
+        .ok()?;
+    let url = line.trim().rsplit(' ').next()?.to_string();
+    Some((Background(server), url))
+}
+
+/// Home directory whose config has `url` as the only, active server
+pub fn home_with_server(root: &Path, url: &str) -> PathBuf {
+    let home = root.join("home");
+    std::fs::create_dir_all(home.join(".rusty-sync")).unwrap();
+    std::fs::write(
+        home.join(".rusty-sync/config.json"),
+        format!(
+            r#"{{"minio_servers": [{{"endpoint_url": "{}", "access_key": "test", "secret_key": "test", "alias": null}}], "current_server": 0}}"#,
+            url
+        ),
+    )
+    .unwrap();
+    home
+}
+
+pub fn rusty_sync(home: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rusty-sync"));
+    // The bridge scripts are found relative to the working directory
+    command
+        .current_dir(project_root())
+        .env("HOME", home)
+        .args(args);
+    // Requests go straight to the fake server unless a test configures a proxy
+    for name in [
+        "HTTP_PROXY",
+        "HTTPS_PROXY",
+        "NO_PROXY",
+        "http_proxy",
+        "https_proxy",
+        "no_proxy",
+    ] {
+        command.env_remove(name);
+    }
+    command
+}
+
+pub fn run(home: &Path, args: &[&str]) {
+    let output = rusty_sync(home, args).output().unwrap();
+    assert!(
+        output.status.success(),
+        "rusty-sync {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+pub fn wait_for(what: &str, condition: impl Fn() -> bool) {
+    let started = Instant::now();
+    while !condition() {
+        assert!(
+            started.elapsed() < Duration::from_secs(30),
+            "timed out waiting for {}",
+            what
+        );
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
+pub fn read(path: PathBuf) -> String {
+    std::fs::read_to_string(path).unwrap_or_default()
+}
@@ -0,0 +1,51 @@
+// end-to-end check of watch-remote against the fake S3 server in src/core/minio
+// needs python3 with boto3, skipped otherwise
+
+mod common;
+
+use common::{home_with_server, read, run, rusty_sync, start_fake_server, wait_for, Background};
+use std::process::Stdio;
+
+#[test]
+fn test_watch_remote_pulls_changes_pushed_elsewhere() {
+    let Some((_server, url)) = start_fake_server(&[]) else {
+        return;
+    };
+
+    let root = std::env::temp_dir().join(format!("rusty-sync-remote-watch-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let home = home_with_server(&root, &url);
+    let (here, there) = (root.join("here"), root.join("there"));
+    std::fs::create_dir_all(&here).unwrap();
+
+    std::fs::write(here.join("notes.txt"), "first").unwrap();
+    let (here_str, there_str) = (here.to_str().unwrap(), there.to_str().unwrap());
+    run(&home, &["init", here_str]);
+    run(&home, &["remote", "add", here_str, "shared"]);
+    run(&home, &["push", here_str]);
+    run(&home, &["clone", "shared", there_str]);
+    assert_eq!(read(there.join("notes.txt")), "first");
+
+    // Polling alone, then polling woken up by bucket notifications
+    for (round, notify) in [(1, false), (2, true)] {
+        let mut args = vec!["watch-remote", there_str, "--interval", "1"];
+        if notify {
+            args.push("--notify");
+        }
+        let _watch = Background(rusty_sync(&home, &args).stdout(Stdio::null()).spawn().unwrap());
+
+        let added = format!("added-{}.txt", round);
+        std::fs::write(here.join("notes.txt"), format!("edit {}", round)).unwrap();
+        std::fs::write(here.join(&added), "new").unwrap();
+        run(&home, &["push", here_str]);
+
+        wait_for("the edit to arrive", || read(there.join("notes.txt")) == format!("edit {}", round));
+        wait_for("the new file to arrive", || there.join(&added).exists());
+
+        std::fs::remove_file(here.join(&added)).unwrap();
+        run(&home, &["push", here_str]);
+        wait_for("the deletion to arrive", || !there.join(&added).exists());
+    }
+
+    let _ = std::fs::remove_dir_all(&root);
+}
@@ -0,0 +1,188 @@
+// end-to-end checks of the per-server S3 compatibility settings, against the fake S3 server
+// emulating servers that differ from MinIO; needs python3 with boto3, skipped otherwise
+
+mod common;
+
+use common::{home_with_server, read, run, rusty_sync, start_fake_server};
+use std::path::Path;
+use std::process::Command;
+
+/// A server dialect: how the fake server is started and what rusty-sync needs to talk to it
+struct Mode {
+    name: &'static str,
+    server: &'static [&'static str],           // Fake server options
+    settings: &'static [&'static str],         // `config compat` options that make it work
+    mismatch: Option<&'static [&'static str]>, // `config compat` options the server refuses
+    proxy: Proxy,
+}
+
+/// How requests reach the fake server: directly, or as (BUCKET.)s3.test through it acting as a proxy
+#[derive(PartialEq)]
+enum Proxy {
+    None,
+    Http,
+    Socks, // socks5h, the proxy resolves s3.test
+}
+
+// No compatibility settings needed
+const MINIO: Mode = Mode {
+    name: "minio",
+    server: &[],
+    settings: &[],
+    mismatch: None,
+    proxy: Proxy::None,
+};
+
+// Path-style only, with its own region name
+const GARAGE: Mode = Mode {
+    name: "garage",
+    server: &["--region", "garage", "--addressing", "path"],
+    settings: &["--region", "garage", "--addressing", "path"],
+    mismatch: Some(&["--addressing", "path"]),
+    proxy: Proxy::None,
+};
+
+// Older releases reject flexible checksum headers
+const CEPH_RGW: Mode = Mode {
+    name: "ceph-rgw",
+    server: &["--addressing", "path", "--no-checksums"],
+    settings: &["--addressing", "path", "--checksums", "when-required"],
+    mismatch: Some(&["--addressing", "path"]),
+    proxy: Proxy::None,
+};
+
+// Older servers, or gateways in front of them, that only check signature version 2
+const SIGV2: Mode = Mode {
+    name: "sigv2",
+    server: &["--signature", "v2"],
+    settings: &["--signature", "v2"],
+    mismatch: Some(&[]),
+    proxy: Proxy::None,
+};
+
+// Only virtual-host style requests, the bucket in the host name
+const VIRTUAL_HOST: Mode = Mode {
+    name: "virtual-host",
+    server: &["--domain", "s3.test", "--addressing", "virtual"],
+    settings: &["--addressing", "virtual"],
+    mismatch: Some(&["--addressing", "path"]),
+    proxy: Proxy::Http,
+};
+
+// Any server, reached through a SOCKS proxy
+const SOCKS: Mode = Mode {
+    name: "socks",
+    server: &[],
+    settings: &[],
+    mismatch: None,
+    proxy: Proxy::Socks,
+};
+
+fn fails(home: &Path, args: &[&str]) -> bool {
+    !rusty_sync(home, args).output().unwrap().status.success()
+}
+
+/// Sync a folder through the fake server emulating `mode`, and check doctor notices mismatched settings
+fn check_mode(mode: &Mode) {
+    let Some((_server, url)) = start_fake_server(mode.server) else {
+        return;
+    };
+    let pysocks = Command::new("python3").args(["-c", "import socks"]).output();
+    if mode.proxy == Proxy::Socks && !pysocks.is_ok_and(|output| output.status.success()) {
+        eprintln!("PySocks not available, skipping");
+        return;
+    }
+
+    let root = std::env::temp_dir().join(format!(
+        "rusty-sync-compat-{}-{}",
+        mode.name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&root);
+    let endpoint = match mode.proxy {
+        Proxy::None => url.clone(),
+        _ => url.replace("127.0.0.1", "s3.test"),
+    };
+    let home = home_with_server(&root, &endpoint);
+    match mode.proxy {
+        Proxy::None => {}
+        Proxy::Http => run(&home, &["config", "network", "--proxy", &url]),
+        Proxy::Socks => run(&home, &["config", "network", "--proxy", &url.replace("http://", "socks5h://")]),
+    }
+    let (here, there) = (root.join("here"), root.join("there"));
+    std::fs::create_dir_all(&here).unwrap();
+    std::fs::write(here.join("notes.txt"), "first").unwrap();
+    let (here_str, there_str) = (here.to_str().unwrap(), there.to_str().unwrap());
+    run(&home, &["init", here_str]);
+
+    let compat = |options: &[&str]| {
+        run(
+            &home,
+            &[["config", "compat", "--clear"].as_slice(), options].concat(),
+        )
+    };
+    compat(mode.settings);
+    run(&home, &["remote", "add", here_str, "compat"]);
+    run(&home, &["push", here_str]);
+    run(&home, &["clone", "compat", there_str]);
+    assert_eq!(
+        read(there.join("notes.txt")),
+        "first",
+        "{}: clone",
+        mode.name
+    );
+
+    // The server refuses requests made the wrong way, which doctor's probe of the bucket reports
+    if let Some(mismatch) = mode.mismatch {
+        compat(mismatch);
+        assert!(
+            fails(&home, &["doctor", here_str]),
+            "{}: mismatched settings went unnoticed",
+            mode.name
+        );
+        compat(mode.settings);
+    }
+    run(&home, &["doctor", here_str]);
+
+    std::fs::write(here.join("notes.txt"), "second").unwrap();
+    run(&home, &["push", here_str]);
+    run(&home, &["pull", "compat", there_str]);
+    assert_eq!(
+        read(there.join("notes.txt")),
+        "second",
+        "{}: pull",
+        mode.name
+    );
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_minio() {
+    check_mode(&MINIO);
+}
+
+#[test]
+fn test_garage() {
+    check_mode(&GARAGE);
+}
+
+#[test]
+fn test_ceph_rgw() {
+    check_mode(&CEPH_RGW);
+}
+
+#[test]
+fn test_signature_v2() {
+    check_mode(&SIGV2);
+}
+
+#[test]
+fn test_virtual_host_addressing() {
+    check_mode(&VIRTUAL_HOST);
+}
+
+#[test]
+fn test_socks_proxy() {
+    check_mode(&SOCKS);
+}
@@ -0,0 +1,100 @@
+// end-to-end checks that watch notices failed or refused uploads instead of counting them as synced
+// needs python3 with boto3, skipped otherwise
+
+mod common;
+
+use common::{home_with_server, read, run, rusty_sync, start_fake_server, wait_for, Background};
+use std::fs::File;
+use std::path::Path;
+
+#[test]
+fn test_watch_keeps_unsynced_files_when_the_server_goes_away() {
+    let Some((server, url)) = start_fake_server(&[]) else {
+        return;
+    };
+
+    let root = std::env::temp_dir().join(format!("rusty-sync-watch-failures-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let home = home_with_server(&root, &url);
+    let here = root.join("here");
+    std::fs::create_dir_all(&here).unwrap();
+    std::fs::write(here.join("a.txt"), "first").unwrap();
+    let here_str = here.to_str().unwrap();
+    run(&home, &["init", here_str]);
+    run(&home, &["remote", "add", here_str, "shared"]);
+    run(&home, &["push", here_str]);
+
+    let log = root.join("watch.log");
+    let output = File::create(&log).unwrap();
+    let mut watch = Background(
+        rusty_sync(&home, &["watch", here_str, "--debounce", "0.2"])
+            .stdout(output.try_clone().unwrap())
+            .stderr(output)
+            .spawn()
+            .unwrap(),
+    );
+    wait_for("the watch to start", || read(log.clone()).contains("Watching"));
+    std::fs::write(here.join("c.txt"), "while up").unwrap();
+    wait_for("c.txt to be pushed", || read(log.clone()).contains("Uploaded: c.txt"));
+
+    drop(server);
+    std::fs::write(here.join("b.txt"), "while down").unwrap();
+    wait_for("the failed push", || read(log.clone()).contains("Push failed"));
+
+    let output = read(log.clone());
+    assert!(!output.contains("Uploaded: b.txt"), "failed upload reported as done:\n{}", output);
+    assert!(!read(here.join("rusty-sync-structure.json")).contains("b.txt"), "b.txt marked as synced");
+    assert!(watch.0.try_wait().unwrap().is_none(), "watch stopped:\n{}", output);
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_watch_stops_uploading_after_a_rekey_elsewhere() {
+    let Some((_server, url)) = start_fake_server(&[]) else {
+        return;
+    };
+
+    let root = std::env::temp_dir().join(format!("rusty-sync-watch-rekey-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let home = home_with_server(&root, &url);
+    let other_home = home_with_server(&root.join("other"), &url);
+    let with_passphrase = |home: &Path, args: &[&str]| {
+        let output = rusty_sync(home, args).env("RUSTY_SYNC_PASSPHRASE", "secret").output().unwrap();
+        assert!(output.status.success(), "{:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    };
+
+    let here = root.join("here");
+    std::fs::create_dir_all(&here).unwrap();
+    std::fs::write(here.join("a.txt"), "first").unwrap();
+    let here_str = here.to_str().unwrap();
+    run(&home, &["init", here_str]);
+    run(&home, &["remote", "add", here_str, "shared"]);
+    run(&home, &["push", here_str]);
+    with_passphrase(&home, &["encryption", "enable", here_str]);
+    let there = root.join("there");
+    with_passphrase(&other_home, &["clone", "shared", there.to_str().unwrap()]);
+
+    let log = root.join("watch.log");
+    let output = File::create(&log).unwrap();
+    let _watch = Background(
+        rusty_sync(&home, &["watch", here_str, "--debounce", "0.2"])
+            .stdout(output.try_clone().unwrap())
+            .stderr(output)
+            .spawn()
+            .unwrap(),
+    );
+    wait_for("the watch to start", || read(log.clone()).contains("Watching"));
+    std::fs::write(here.join("c.txt"), "before").unwrap();
+    wait_for("c.txt to be pushed", || read(log.clone()).contains("Uploaded: c.txt"));
+
+    // The other machine switches the bucket to a new key this watch doesn't have
+    with_passphrase(&other_home, &["encryption", "rekey", there.to_str().unwrap()]);
+    std::fs::write(here.join("b.txt"), "after").unwrap();
+    wait_for("the refused push", || read(log.clone()).contains("was changed"));
+
+    let output = read(log.clone());
+    assert!(!output.contains("Uploaded: b.txt"), "uploaded with a stale key:\n{}", output);
+
+    let _ = std::fs::remove_dir_all(&root);
+}